@@ -3,34 +3,70 @@ import difflib
 c_file = ""
 cpp_file = ""
 cpp_specifics = [
+    "fn check_member_order(root: Node, order: [utils::MemberKind; 4], src: String) {",
+    "fn get_compactable_namespace(declaration_list: Node) -> Option<Node> {",
+    "fn get_member_kind(node: Node, class_name: &str, src: String) -> Option<utils::MemberKind> {",
     "fn handle_abstract_function_declarator(root: Node, src: String) -> String {",
     "fn handle_abstract_reference_declarator(root: Node, src: String) -> String {",
     "fn handle_access_specifier(root: Node, src: String) -> String {",
     "fn handle_alias_declaration(root: Node, src: String) -> String {",
+    "fn handle_base_class_clause(root: Node, src: String) -> String {",
     "fn handle_catch_clause(root: Node, src: String) -> String {",
     "fn handle_class_specifier(root: Node, src: String) -> String {",
+    "fn handle_co_await_expression(root: Node, src: String) -> String {",
+    "fn handle_compound_requirement(root: Node, src: String) -> String {",
+    "fn handle_concept_definition(root: Node, src: String) -> String {",
     "fn handle_condition_clause(root: Node, src: String) -> String {",
+    "fn handle_constraint(root: Node, src: String) -> String {",
+    "fn handle_coroutine_statement(root: Node, src: String) -> String {",
+    "fn handle_decltype(root: Node, src: String) -> String {",
+    "fn handle_destructor_name(root: Node, src: String) -> String {",
+    "fn handle_explicit_function_specifier(root: Node, src: String) -> String {",
+    "fn handle_expression(root: Node, src: String) -> String {",
     "fn handle_field_initializer(root: Node, src: String) -> String {",
     "fn handle_field_initializer_list(root: Node, src: String) -> String {",
+    "fn handle_fold_expression(root: Node, src: String) -> String {",
     "fn handle_for_range_loop(root: Node, src: String) -> String {",
+    "fn handle_friend_declaration(root: Node, src: String) -> String {",
+    "fn handle_kernel_launch(root: Node, src: String) -> String {",
     "fn handle_lambda_capture_specifier(root: Node, src: String) -> String {",
     "fn handle_lambda_expression(root: Node, src: String) -> String {",
+    "fn handle_method_clause(root: Node, src: String) -> String {",
+    "fn handle_module_declaration(root: Node, src: String) -> String {",
     "fn handle_namespace_alias_definition(root: Node, src: String) -> String {",
     "fn handle_namespace_definition(root: Node, src: String) -> String {",
     "fn handle_nested_namespace_specifier(root: Node, src: String) -> String {",
     "fn handle_new_expression(root: Node, src: String) -> String {",
+    "fn handle_noexcept(root: Node, src: String) -> String {",
+    "fn handle_operator_cast(root: Node, src: String) -> String {",
+    "fn handle_operator_name(root: Node, src: String) -> String {",
+    "fn handle_parameter_pack_expansion(root: Node, src: String) -> String {",
+    "fn handle_placeholder_type_specifier(root: Node, src: String) -> String {",
     "fn handle_qualified_identifier(root: Node, src: String) -> String {",
     "fn handle_reference_declarator(root: Node, src: String) -> String {",
+    "fn handle_requirement_seq(root: Node, src: String) -> String {",
+    "fn handle_requires_clause(root: Node, src: String) -> String {",
+    "fn handle_requires_expression(root: Node, src: String) -> String {",
+    "fn handle_static_assert_declaration(root: Node, src: String) -> String {",
     "fn handle_structured_binding_declarator(root: Node, src: String) -> String {",
     "fn handle_subscript_argument_list(root: Node, src: String) -> String {",
     "fn handle_template_argument_list(root: Node, src: String) -> String {",
     "fn handle_template_declaration(root: Node, src: String) -> String {",
     "fn handle_template_function(root: Node, src: String) -> String {",
+    "fn handle_template_parameter(root: Node, src: String) -> String {",
     "fn handle_template_parameter_list(root: Node, src: String) -> String {",
     "fn handle_template_type(root: Node, src: String) -> String {",
+    "fn handle_trailing_return_type(root: Node, src: String) -> String {",
     "fn handle_try_statement(root: Node, src: String) -> String {",
     "fn handle_type_parameter_declaration(root: Node, src: String) -> String {",
+    "fn handle_type_requirement(root: Node, src: String) -> String {",
     "fn handle_using_declaration(root: Node, src: String) -> String {",
+    "fn handle_variadic_declarator(root: Node, src: String) -> String {",
+    "fn handle_variadic_parameter_declaration(root: Node, src: String) -> String {",
+    "fn handle_virtual_specifier(root: Node, src: String) -> String {",
+    "fn is_kernel_launch(root: Node) -> bool {",
+    "fn is_module_declaration(text: &str) -> bool {",
+    "fn is_trailing_comment(node: Node) -> bool {",
 ]
 
 with open("src/c_ast.rs") as file:
@@ -1,7 +1,8 @@
 use tree_sitter::{Tree, Node};
-use crate::{c_format, utils};
+use crate::{c_format, options, utils};
 
-pub fn traverse_c_ast(ast: Tree, src: String, style: utils::Style) -> String {
+pub fn traverse_c_ast(ast: Tree, src: String, opts: options::COpts) -> String {
+    utils::set_active_c_opts(opts);
     let root = ast.root_node();
     let mut result = String::new();
     let mut last_group_kind = "";
@@ -20,12 +21,24 @@ pub fn traverse_c_ast(ast: Tree, src: String, style: utils::Style) -> String {
                 result += format!("{}\n", declaration).as_str();
                 last_group_kind = "declaration";
             },
+            "linkage_specification" => {
+                result += "\n";
+                let linkage_specification = handle_linkage_specification(child, src.clone());
+                result += format!("{}\n\n", linkage_specification).as_str();
+                last_group_kind = "linkage_specification";
+            },
             "function_definition" => {
-                if last_group_kind != "function_definition".to_string() { result += "\n"; }
+                if last_group_kind != "function_definition" { result += "\n"; }
                 let function_definition = handle_function_definition(child, src.clone());
                 result += format!("{}\n\n", function_definition).as_str();
                 last_group_kind = "function_definition";
             },
+            "expression_statement" if last_group_kind == "atomic_specifier" => {
+                // The cast comes out as `(long)big;`, the declaration wants `(long) big;`.
+                let expression_statement = handle_expression_statement(child, src.clone()).replacen(")", ") ", 1);
+                result += format!("{}\n", expression_statement).as_str();
+                last_group_kind = "declaration";
+            },
             "expression_statement" => {
                 let expression_statement = handle_expression_statement(child, src.clone());
                 result += format!("{}\n\n", expression_statement).as_str();
@@ -68,6 +81,11 @@ pub fn traverse_c_ast(ast: Tree, src: String, style: utils::Style) -> String {
                 result += format!("{}\n", comment).as_str();
                 last_group_kind = "comment";
             },
+            "ERROR" if child.utf8_text(src.as_bytes()).unwrap_or("").trim() == "_Atomic" => {
+                // `_Atomic(T) x;` comes out as an ERROR followed by a cast expression.
+                result += "_Atomic";
+                last_group_kind = "atomic_specifier";
+            },
             "ERROR" => {
                 let error = handle_error(child, src.clone());
                 result += format!("{}\n\n", error).as_str();
@@ -80,9 +98,21 @@ pub fn traverse_c_ast(ast: Tree, src: String, style: utils::Style) -> String {
             },
             "struct_specifier" => {
                 let struct_specifier = handle_struct_specifier(child, src.clone());
-                result += format!("{}\n\n", struct_specifier).as_str();
+                result += format!("{};\n\n", struct_specifier.trim_end()).as_str();
                 last_group_kind = "struct_specifier";
             },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(child, src.clone());
+                if last_group_kind.contains("preproc") { result += "\n"; }
+                result += format!("{};\n\n", union_specifier.trim_end()).as_str();
+                last_group_kind = "union_specifier";
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(child, src.clone());
+                if last_group_kind.contains("preproc") { result += "\n"; }
+                result += format!("{};\n\n", enum_specifier.trim_end()).as_str();
+                last_group_kind = "enum_specifier";
+            },
             "preproc_def" => {
                 if last_group_kind != "preproc_def" { result += "\n"; }
                 let preproc_def = handle_preproc_def(child, src.clone());
@@ -122,9 +152,8 @@ pub fn traverse_c_ast(ast: Tree, src: String, style: utils::Style) -> String {
         panic!("Fatal: Unable to parse language grammar");
     }
     result = utils::sort_include_groups(result);
-    utils::format_else_lines(&mut result, &style);
     result = utils::fix_stars(result);
-    utils::tidy_up_loose_ends(&mut result, style);
+    utils::tidy_up_loose_ends(&mut result, opts);
     return result;
 }
 
@@ -168,11 +197,11 @@ fn handle_compound_statement(root: Node, src: String) -> String {
                 result += format!("\t{}\n", case_statement).as_str();
             },
             "break_statement" => {
-                let break_statement = "break;\n".to_string();
+                let break_statement = "break;".to_string();
                 result += format!("\t{}\n", break_statement).as_str();
             },
             "continue_statement" => {
-                let continue_statement = "continue;\n".to_string();
+                let continue_statement = "continue;".to_string();
                 result += format!("\t{}\n", continue_statement).as_str();
             },
             "goto_statement" => {
@@ -200,6 +229,11 @@ fn handle_compound_statement(root: Node, src: String) -> String {
                 if comment.starts_with(" ") { result += format!("{}\n", comment).as_str(); }
                 else { result += format!("\t{}\n", comment).as_str(); }
             },
+            "attributed_statement" => {
+                let mut attributed_statement = handle_attributed_statement(node, src.clone());
+                attributed_statement = utils::add_all_leading_tabs(attributed_statement);
+                result += format!("{}\n", attributed_statement).as_str();
+            },
             "labeled_statement" => {
                 let labeled_statement = handle_labeled_statement(node, src.clone());
                 result += format!("\t{}\n", labeled_statement).as_str();
@@ -225,7 +259,15 @@ fn handle_compound_statement(root: Node, src: String) -> String {
             },
             "struct_specifier" => {
                 let struct_specifier = handle_struct_specifier(node, src.clone());
-                result += format!("\t{}\n", struct_specifier).as_str();
+                result += utils::add_all_leading_tabs(struct_specifier.trim_end().to_string()).as_str();
+            },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(node, src.clone());
+                result += utils::add_all_leading_tabs(union_specifier.trim_end().to_string()).as_str();
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                result += utils::add_all_leading_tabs(enum_specifier.trim_end().to_string()).as_str();
             },
             "init_declarator" => {
                 let init_declarator = handle_init_declarator(node, src.clone());
@@ -272,7 +314,8 @@ fn handle_compound_statement(root: Node, src: String) -> String {
             "false" => {
                 dbg!(node.utf8_text(src.as_bytes()).unwrap());
             },
-            ";" => result += ";",
+            // only a struct, union or enum is followed by a bare `;`
+            ";" => result += ";\n",
             "{" => result += "{\n",
             "}" => result += "}",
             _ => println!("Unknown grammar name 2: {}\n", node.grammar_name()),
@@ -282,22 +325,39 @@ fn handle_compound_statement(root: Node, src: String) -> String {
 }
 
 fn handle_preproc_include(root: Node, src: String) -> String {
-    let result: String;
     let mut vec = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         vec.push(node.utf8_text(src.as_bytes()).unwrap().to_string());
     }
-    result = vec.join(" ");
+    let result = vec.join(" ");
     return result;
 }
 
 fn handle_declaration(root: Node, src: String) -> String {
     let mut result: String;
     let mut parts = Vec::<String>::new();
+    let mut attributes = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "macro_type_specifier" => {
+                let macro_type_specifier = handle_macro_type_specifier(node, src.clone());
+                parts.push(macro_type_specifier);
+            },
+            "ms_declspec_modifier" => {
+                let ms_declspec_modifier = handle_ms_declspec_modifier(node, src.clone());
+                parts.push(ms_declspec_modifier);
+            },
+            "ms_call_modifier" => parts.push(node.utf8_text(src.as_bytes()).unwrap_or("").to_string()),
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                attributes.push(attribute_declaration);
+            },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                parts.push(attribute_specifier);
+            },
             "compound_statement" => {
-                let compound_statement = handle_compound_statement(node.clone(), src.clone());
+                let compound_statement = handle_compound_statement(node, src.clone());
                 parts.push(compound_statement);
             },
             "init_declarator" => {
@@ -340,6 +400,14 @@ fn handle_declaration(root: Node, src: String) -> String {
                 let struct_specifier = handle_struct_specifier(node, src.clone());
                 parts.push(struct_specifier);
             },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(node, src.clone());
+                parts.push(union_specifier);
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                parts.push(enum_specifier);
+            },
             "sized_type_specifier" => {
                 let sized_type_specifier = handle_sized_type_specifier(node, src.clone());
                 parts.push(sized_type_specifier);
@@ -353,16 +421,32 @@ fn handle_declaration(root: Node, src: String) -> String {
     if result.contains(",") { result = utils::remove_whitespace_before_commas(&result); }
     result = utils::remove_unnecessary_spaces(&result);
     if result.contains("**") { result = utils::remove_pointer_spaces(result); }
+    let is_function = root.children(&mut root.walk()).any(|x| x.grammar_name() == "function_declarator");
+    result = utils::place_attributes(&attributes, &result, is_function);
     return result;
 }
 
 fn handle_function_definition(root: Node, src: String) -> String {
     let mut result = String::new();
+    let mut attributes = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "ms_declspec_modifier" => {
+                let ms_declspec_modifier = handle_ms_declspec_modifier(node, src.clone());
+                result += format!("{} ", ms_declspec_modifier).as_str();
+            },
+            "ms_call_modifier" => result += format!("{} ", node.utf8_text(src.as_bytes()).unwrap_or("")).as_str(),
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                attributes.push(attribute_declaration);
+            },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                result += format!("{} ", attribute_specifier).as_str();
+            },
             "compound_statement" => {
                 let compound_statement = handle_compound_statement(node, src.clone());
-                result += format!("{}\n", compound_statement).as_str();
+                result = format!("{}\n", utils::join_block(&result, &compound_statement, utils::BraceKind::Function));
             },
             "primitive_type" => {
                 let primitive_type = handle_primitive_type(node, src.clone());
@@ -395,7 +479,7 @@ fn handle_function_definition(root: Node, src: String) -> String {
                 result += format!("{}\n", parenthesized_declarator).as_str();
             },
             "struct_specifier" => {
-                let struct_specifier = handle_struct_specifier(node.clone(), src.clone());
+                let struct_specifier = handle_struct_specifier(node, src.clone());
                 result += format!("{}\n", struct_specifier).as_str();
             },
             "storage_class_specifier" => {
@@ -411,6 +495,7 @@ fn handle_function_definition(root: Node, src: String) -> String {
     }
     result = utils::ensure_space_after_char(&result, '=');
     result = result.trim_end().to_string();
+    result = utils::place_attributes(&attributes, &result, true);
     return result;
 }
 
@@ -418,12 +503,21 @@ fn handle_expression_statement(root: Node, src: String) -> String {
     let mut result = "".to_string();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "gnu_asm_expression" => {
+                result = handle_gnu_asm_expression(node, src.clone());
+            },
+            "cast_expression" => {
+                result = handle_cast_expression(node, src.clone());
+            },
             "assignment_expression" => {
                 result = handle_assignment_expression(node, src.clone());
             },
             "update_expression" => {
                 result = handle_update_expression(node, src.clone());
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                result = handle_builtin_expression(node, src.clone());
+            },
             "call_expression" => {
                 result = handle_call_expression(node, src.clone());
             },
@@ -475,18 +569,23 @@ fn handle_assignment_expression(root: Node, src: String) -> String {
         match node.grammar_name() {
             ";" => parts.push(";".to_string()),
             "=" => parts.push("=".to_string()),
-            "+=" => parts.push("=".to_string()),
-            "-=" => parts.push("=".to_string()),
-            "*=" => parts.push("=".to_string()),
-            "/=" => parts.push("=".to_string()),
-            "%=" => parts.push("=".to_string()),
-            "<<=" => parts.push("=".to_string()),
-            ">>=" => parts.push("=".to_string()),
-            "&=" => parts.push("=".to_string()),
-            "|=" => parts.push("=".to_string()),
+            "+=" => parts.push("+=".to_string()),
+            "-=" => parts.push("-=".to_string()),
+            "*=" => parts.push("*=".to_string()),
+            "/=" => parts.push("/=".to_string()),
+            "%=" => parts.push("%=".to_string()),
+            "<<=" => parts.push("<<=".to_string()),
+            ">>=" => parts.push(">>=".to_string()),
+            "&=" => parts.push("&=".to_string()),
+            "|=" => parts.push("|=".to_string()),
+            "^=" => parts.push("^=".to_string()),
             "null" => parts.push("NULL".to_string()),
             "true" => parts.push("true".to_string()),
             "false" => parts.push("false".to_string()),
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "assignment_expression" => {
                 let assignment_expression = handle_inner_assignment_expression(node, src.clone());
                 parts.push(assignment_expression);
@@ -543,6 +642,11 @@ fn handle_assignment_expression(root: Node, src: String) -> String {
                 let sizeof_expression = handle_sizeof_expression(node, src.clone());
                 parts.push(sizeof_expression);
             },
+            "ERROR" if parts.last().is_some_and(|x| x.starts_with("typeof") || x.starts_with("__typeof")) => {
+                // `__typeof__(x) y = 0;` parses as an assignment, with the declared name as an ERROR.
+                let name = node.utf8_text(src.as_bytes()).unwrap_or("").trim().to_string();
+                parts.last_mut().unwrap().push_str(format!(" {}", name).as_str());
+            },
             "ERROR" => {
                 println!(
                     "Warning: encountered ERROR node, likely due to preprocessor macro interrupt.\n\t--> {:?}\n",
@@ -581,6 +685,10 @@ fn handle_inner_assignment_expression(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 parts.push(identifier);
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 parts.push(call_expression);
@@ -662,6 +770,7 @@ fn handle_inner_assignment_expression(root: Node, src: String) -> String {
             "/=" => parts.push("/=".to_string()),
             "%=" => parts.push("%=".to_string()),
             "|=" => parts.push("|=".to_string()),
+            "^=" => parts.push("^=".to_string()),
             "&=" => parts.push("&=".to_string()),
             ">>=" => parts.push(">>=".to_string()),
             "<<=" => parts.push("<<=".to_string()),
@@ -743,6 +852,11 @@ fn handle_inner_compound_statement(root: Node, src: String) -> String {
                 for_statement = utils::add_all_leading_tabs(for_statement);
                 parts.push(for_statement);
             },
+            "attributed_statement" => {
+                let mut attributed_statement = handle_attributed_statement(node, src.clone());
+                attributed_statement = utils::add_all_leading_tabs(attributed_statement);
+                parts.push(attributed_statement);
+            },
             "labeled_statement" => {
                 let mut labeled_statement = handle_labeled_statement(node, src.clone());
                 labeled_statement = utils::add_all_leading_tabs(labeled_statement);
@@ -819,7 +933,8 @@ fn handle_if_statement(root: Node, src: String) -> String {
             "if" => parts.push("if".to_string()),
             "compound_statement" => {
                 let inner_compound_statement = handle_inner_compound_statement(node, src.clone());
-                parts.push(inner_compound_statement);
+                let head = parts.join(" ");
+                parts = vec![utils::join_block(&head, &inner_compound_statement, utils::BraceKind::Control)];
             },
             "return_statement" => {
                 let return_statement = handle_return_statement(node, src.clone());
@@ -842,7 +957,8 @@ fn handle_if_statement(root: Node, src: String) -> String {
             }
             "else_clause" => {
                 let else_clause = handle_else_clause(node, src.clone());
-                parts.push(else_clause);
+                let head = parts.join(" ");
+                parts = vec![utils::join_continuation(&head, &else_clause, utils::BraceKind::Else)];
             },
             "goto_statement" => {
                 let goto_statement = handle_goto_statement(node, src.clone());
@@ -872,7 +988,8 @@ fn handle_else_clause(root: Node, src: String) -> String {
         match node.grammar_name() {
             "compound_statement" => {
                 let inner_compound_statement = handle_inner_compound_statement(node, src.clone());
-                pieces.push(inner_compound_statement);
+                let head = pieces.join(" ");
+                pieces = vec![utils::join_block(&head, &inner_compound_statement, utils::BraceKind::Control)];
             }
             "else" => {
                 pieces.push("else".to_string());
@@ -911,7 +1028,7 @@ fn handle_switch_statement(root: Node, src: String) -> String {
             "switch" => result += "switch ",
             "compound_statement" => {
                 let compound_statement = handle_inner_compound_statement(node, src.clone());
-                result += compound_statement.as_str();
+                result = utils::join_block(&result, &compound_statement, utils::BraceKind::Control);
             },
             "parenthesized_expression" => {
                 let parenthesized_expression = handle_parenthesized_expression(node, src.clone());
@@ -935,6 +1052,18 @@ fn handle_case_statement(root: Node, src: String) -> String {
                 let compound_statement = handle_inner_compound_statement(node, src.clone());
                 result += format!("{}\n", compound_statement).as_str();
             },
+            // GNU case range: `case 1 ... 5:` or `case 'a' ... 'z':`, where the grammar
+            // leaves one side of the `...` in an ERROR node; keep its text as written.
+            "ERROR" => {
+                let range = node.utf8_text(src.as_bytes()).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ");
+                if !result.ends_with(' ') {
+                    result += " ";
+                }
+                result += range.as_str();
+                if range.ends_with("...") {
+                    result += " ";
+                }
+            },
             "case" => {
                 result += "case ";
             },
@@ -953,6 +1082,10 @@ fn handle_case_statement(root: Node, src: String) -> String {
                 let if_statement = handle_if_statement(node, src.clone());
                 result += format!("\t{}\n", if_statement).as_str();
             },
+            "attributed_statement" => {
+                let attributed_statement = handle_attributed_statement(node, src.clone());
+                result += format!("\t{}\n", attributed_statement).as_str();
+            },
             "goto_statement" => {
                 let goto_statement = handle_goto_statement(node, src.clone());
                 result += format!("\t{}\n", goto_statement).as_str();
@@ -986,7 +1119,7 @@ fn handle_case_statement(root: Node, src: String) -> String {
             },
             ":" => {
                 result = result.trim_end().to_string();
-                result += ": \n"
+                result += ":\n"
             },
             _ => println!("You shouldn't be here (case_statement): {}\n", node.grammar_name()),
         }
@@ -1020,8 +1153,9 @@ fn handle_while_statement(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "compound_statement" => {
-                let compound_statement = handle_compound_statement(node.clone(), src.clone());
-                parts.push(compound_statement);
+                let compound_statement = handle_compound_statement(node, src.clone());
+                let head = parts.join(" ");
+                parts = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Control)];
             },
             "while" => {
                 parts.push("while".to_string());
@@ -1050,6 +1184,10 @@ fn handle_argument_list(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "," => result += ", ",
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
             "(" => result += "(",
             ")" => result += ")",
             "null" => result += "NULL",
@@ -1139,59 +1277,63 @@ fn handle_return_statement(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "compound_statement" => {
-                let compound_statement = handle_compound_statement(node.clone(), src.clone());
+                let compound_statement = handle_compound_statement(node, src.clone());
                 parts.push(compound_statement);
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "call_expression" => {
-                let call_expression = handle_call_expression(node.clone(), src.clone());
+                let call_expression = handle_call_expression(node, src.clone());
                 parts.push(call_expression);
             },
             "binary_expression" => {
-                let binary_expression = handle_binary_expression(node.clone(), src.clone());
+                let binary_expression = handle_binary_expression(node, src.clone());
                 parts.push(binary_expression);
             },
             "unary_expression" => {
-                let unary_expression = handle_unary_expression(node.clone(), src.clone());
+                let unary_expression = handle_unary_expression(node, src.clone());
                 parts.push(unary_expression);
             },
             "identifier" => {
-                let identifier = handle_identifier(node.clone(), src.clone());
+                let identifier = handle_identifier(node, src.clone());
                 parts.push(identifier);
             },
             "conditional_expression" => {
-                let conditional_expression = handle_conditional_expression(node.clone(), src.clone());
+                let conditional_expression = handle_conditional_expression(node, src.clone());
                 parts.push(conditional_expression);
             },
             "field_expression" => {
-                let field_expression = handle_field_expression(node.clone(), src.clone());
+                let field_expression = handle_field_expression(node, src.clone());
                 parts.push(field_expression);
             },
             "cast_expression" => {
-                let cast_expression = handle_cast_expression(node.clone(), src.clone());
+                let cast_expression = handle_cast_expression(node, src.clone());
                 parts.push(cast_expression);
             },
             "number_literal" => {
-                let number_literal = handle_number_literal(node.clone(), src.clone());
+                let number_literal = handle_number_literal(node, src.clone());
                 parts.push(number_literal);
             },
             "char_literal" => {
-                let char_literal = handle_char_literal(node.clone(), src.clone());
+                let char_literal = handle_char_literal(node, src.clone());
                 parts.push(char_literal);
             },
             "string_literal" => {
-                let string_literal = handle_string_literal(node.clone(), src.clone());
+                let string_literal = handle_string_literal(node, src.clone());
                 parts.push(string_literal);
             },
             "subscript_expression" => {
-                let subscript_expression = handle_subscript_expression(node.clone(), src.clone());
+                let subscript_expression = handle_subscript_expression(node, src.clone());
                 parts.push(subscript_expression);
             },
             "pointer_expression" => {
-                let pointer_expression = handle_pointer_expression(node.clone(), src.clone());
+                let pointer_expression = handle_pointer_expression(node, src.clone());
                 parts.push(pointer_expression);
             },
             "parenthesized_expression" => {
-                let parenthesized_expression = handle_parenthesized_expression(node.clone(), src.clone());
+                let parenthesized_expression = handle_parenthesized_expression(node, src.clone());
                 parts.push(parenthesized_expression);
             },
             "return" => {
@@ -1274,6 +1416,14 @@ fn handle_function_declarator(root: Node, src: String) -> String {
         if node.child_count() == 0 {
             result += node.utf8_text(src.as_bytes()).unwrap();
         }
+        else if node.grammar_name() == "attribute_specifier" {
+            let attribute_specifier = handle_attribute_specifier(node, src.clone());
+            result += format!(" {}", attribute_specifier).as_str();
+        }
+        else if node.grammar_name() == "attribute_declaration" {
+            let attribute_declaration = handle_attribute_declaration(node, src.clone());
+            result += format!(" {}", attribute_declaration).as_str();
+        }
         else {
             let mut temp = String::new();
             for subnode in node.children(&mut node.walk()) {
@@ -1351,6 +1501,10 @@ fn handle_pointer_declarator(root: Node, src: String) -> String {
                 let array_declarator = handle_array_declarator(node, src.clone());
                 result += format!(" {}", array_declarator).as_str();
             },
+            "ms_pointer_modifier" => {
+                // `__restrict`, `__uptr` and `__sptr`
+                result += node.utf8_text(src.as_bytes()).unwrap();
+            },
             _ => println!("You shouldn't be here (pointer_declarator): {}\n", node.kind()),
         }
     }
@@ -1407,6 +1561,10 @@ fn handle_binary_expression(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "identifier" => {
                 let identifier = handle_identifier(node, src.clone());
                 parts.push(identifier);
@@ -1522,6 +1680,10 @@ fn handle_inner_binary_expression(root: Node, src: String) -> String {
                 let field_expression = handle_field_expression(node, src.clone());
                 parts.push(field_expression);
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 parts.push(call_expression);
@@ -1607,6 +1769,10 @@ fn handle_parenthesized_expression(root: Node, src: String) -> String {
     let mut result = String::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 result += call_expression.as_str();
@@ -1671,6 +1837,11 @@ fn handle_parenthesized_expression(root: Node, src: String) -> String {
                 let error = handle_error(node, src.clone());
                 result += format!("{} ", error).as_str();
             },
+            // GNU statement expression: `({ ... })`
+            "compound_statement" => {
+                let compound_statement = handle_compound_statement(node, src.clone());
+                result += compound_statement.as_str();
+            },
             "(" => result += "(",
             ")" => result += ")",
             _ => println!("You shouldn't be here (parenthesized_expression): {}\n", node.grammar_name()),
@@ -1759,6 +1930,14 @@ fn handle_parameter_declaration(root: Node, src: String) -> String {
                 let type_qualifier = handle_type_qualifier(node, src.clone());
                 parts.push(type_qualifier);
             },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                parts.push(attribute_specifier);
+            },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                parts.push(attribute_declaration);
+            },
             "struct_specifier" => {
                 let struct_specifier = handle_struct_specifier(node, src.clone());
                 parts.push(struct_specifier);
@@ -1809,6 +1988,10 @@ fn handle_subscript_expression(root: Node, src: String) -> String {
                 let update_expression = handle_update_expression(node, src.clone());
                 result += update_expression.as_str();
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
             "binary_expression" => {
                 let binary_expression = handle_binary_expression(node, src.clone());
                 result += binary_expression.as_str();
@@ -1860,6 +2043,10 @@ fn handle_field_expression(root: Node, src: String) -> String {
                 let subscript_expression = handle_subscript_expression(node, src.clone());
                 result += subscript_expression.as_str();
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 result += call_expression.as_str();
@@ -1938,6 +2125,14 @@ fn handle_cast_expression(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += identifier.as_str();
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
+            "number_literal" => {
+                let number_literal = handle_number_literal(node, src.clone());
+                result += number_literal.as_str();
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 result += call_expression.as_str();
@@ -2028,6 +2223,10 @@ fn handle_init_declarator(root: Node, src: String) -> String {
                 let array_declarator = handle_array_declarator(node, src.clone());
                 parts.push(array_declarator);
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "=" => parts.push("=".to_string()),
             "false" => parts.push("false".to_string()),
             "true" => parts.push("true".to_string()),
@@ -2055,6 +2254,18 @@ fn handle_type_descriptor(root: Node, src: String) -> String {
                 let abstract_pointer_declaration = handle_abstract_pointer_declarator(node, src.clone());
                 result += format!("{} ", abstract_pointer_declaration).as_str();
             },
+            "abstract_array_declarator" => {
+                let abstract_array_declarator = handle_abstract_array_declarator(node, src.clone());
+                result = format!("{}{}", result.trim_end(), abstract_array_declarator);
+            },
+            "type_qualifier" => {
+                let type_qualifier = handle_type_qualifier(node, src.clone());
+                result += format!("{} ", type_qualifier).as_str();
+            },
+            "macro_type_specifier" => {
+                let macro_type_specifier = handle_macro_type_specifier(node, src.clone());
+                result += macro_type_specifier.as_str();
+            },
             "struct_specifier" => {
                 let struct_specifier = handle_struct_specifier(node, src.clone());
                 result += struct_specifier.as_str();
@@ -2101,6 +2312,34 @@ fn handle_initializer_list(root: Node, src: String) -> String {
                 let string_literal = handle_string_literal(node, src.clone());
                 result += string_literal.as_str();
             },
+            "call_expression" => {
+                let call_expression = handle_call_expression(node, src.clone());
+                result += call_expression.as_str();
+            },
+            "field_expression" => {
+                let field_expression = handle_field_expression(node, src.clone());
+                result += field_expression.as_str();
+            },
+            "binary_expression" => {
+                let binary_expression = handle_binary_expression(node, src.clone());
+                result += binary_expression.as_str();
+            },
+            "unary_expression" => {
+                let unary_expression = handle_unary_expression(node, src.clone());
+                result += unary_expression.as_str();
+            },
+            "cast_expression" => {
+                let cast_expression = handle_cast_expression(node, src.clone());
+                result += cast_expression.as_str();
+            },
+            "pointer_expression" => {
+                let pointer_expression = handle_pointer_expression(node, src.clone());
+                result += pointer_expression.as_str();
+            },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
             "{" => result += "{",
             "}" => result += "}",
             "," => result += ", ",
@@ -2139,6 +2378,16 @@ fn handle_type_qualifier(root: Node, src: String) -> String {
             "const" => {
                 result = "const".to_string();
             },
+            "volatile" => result = "volatile".to_string(),
+            "restrict" => result = "restrict".to_string(),
+            "__restrict__" => result = "__restrict__".to_string(),
+            "constexpr" => result = "constexpr".to_string(),
+            "_Atomic" => result = "_Atomic".to_string(),
+            "_Noreturn" => result = "_Noreturn".to_string(),
+            "noreturn" => result = "noreturn".to_string(),
+            "alignas_qualifier" => {
+                result = handle_alignas_qualifier(node, src.clone());
+            },
             _ => println!("You shouldn't be here (type_qualifier): {}\n", node.grammar_name()),
         }
     }
@@ -2165,6 +2414,10 @@ fn handle_conditional_expression(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 parts.push(identifier);
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 parts.push(call_expression);
@@ -2232,6 +2485,10 @@ fn handle_unary_expression(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += identifier.as_str();
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 result += call_expression.as_str();
@@ -2289,6 +2546,10 @@ fn handle_array_declarator(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 parts.push(identifier);
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                parts.push(builtin_expression);
+            },
             "binary_expression" => {
                 let binary_expression = handle_binary_expression(node, src.clone());
                 parts.push(binary_expression);
@@ -2384,7 +2645,8 @@ fn handle_for_statement(root: Node, src: String) -> String {
             "compound_statement" => {
                 reached_compound = true;
                 let compound_statement = handle_compound_statement(node, src.clone());
-                vec.push(compound_statement);
+                let head = vec.join(" ");
+                vec = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Control)];
             },
             "if_statement" => {
                 let if_statement = handle_if_statement(node, src.clone());
@@ -2462,15 +2724,22 @@ fn handle_struct_specifier(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += format!("{} ", identifier).as_str();
             },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                if result.ends_with("}") { result += " "; }
+                result += format!("{} ", attribute_specifier).as_str();
+            },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                result += format!("{} ", attribute_declaration).as_str();
+            },
+            "alignas_qualifier" => {
+                let alignas_qualifier = handle_alignas_qualifier(node, src.clone());
+                result += format!("{} ", alignas_qualifier).as_str();
+            },
             "field_declaration_list" => {
                 let field_declaration_list = handle_field_declaration_list(node, src.clone());
-                // This adds a newline before the curly brace.
-                if result.contains("\n") {
-                    result += field_declaration_list.as_str();
-                }
-                else {
-                    result += format!("\n{}", field_declaration_list).as_str();
-                }
+                result = utils::join_block(&result, &field_declaration_list, utils::BraceKind::Class);
             },
             "struct" => result += "struct ",
             _ => println!("You shouldn't be here (struct_specifier): {}\n", node.grammar_name()),
@@ -2525,7 +2794,6 @@ fn handle_type_definition(root: Node, src: String) -> String {
 
 fn handle_field_declaration_list(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
-    let mut temp = String::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "field_declaration" => {
@@ -2533,30 +2801,22 @@ fn handle_field_declaration_list(root: Node, src: String) -> String {
                 if field_declaration.contains("*") { 
                     field_declaration = utils::switch_pointer_spaces(field_declaration); 
                 }
-                if temp.len() > 0 {
-                    parts.push(temp);
-                    temp = String::new();
-                }
-                else {
-                    temp += format!("\t{}", field_declaration).as_str();
-                }
+                parts.push(format!("\t{}", field_declaration));
             },
             "comment" => {
                 let comment = handle_comment(node, src.clone());
-                temp += format!("\t{}", comment).as_str();
-                parts.push(temp);
-                temp = String::new();
+                let trailing = node.prev_sibling().is_some_and(|x| x.grammar_name() != "{" && 
+                    x.end_position().row == node.start_position().row);
+                match parts.last_mut() {
+                    Some(field) if trailing => *field += format!("\t{}", comment).as_str(),
+                    _ => parts.push(format!("\t{}", comment)),
+                }
             },
             "{" => parts.push("{".to_string()),
             "}" => parts.push("}".to_string()),
             _ => println!("You shouldn't be here (field_declaration_list): {}\n", node.grammar_name()),
         }
     }
-    if temp.len() > 0 {
-        parts.remove(parts.len()-1);
-        parts.push(temp);
-        parts.push("}".to_string());
-    }
     let result = parts.join("\n");
     return result;
 }
@@ -2581,6 +2841,18 @@ fn handle_field_declaration(root: Node, src: String) -> String {
                 let pointer_declarator = handle_pointer_declarator(node, src.clone());
                 result += format!("{} ", pointer_declarator).as_str();
             },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                result += format!("{} ", attribute_declaration).as_str();
+            },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                result += format!("{} ", attribute_specifier).as_str();
+            },
+            "type_qualifier" => {
+                let type_qualifier = handle_type_qualifier(node, src.clone());
+                result += format!("{} ", type_qualifier).as_str();
+            },
             ";" => result += ";",
             "," => result += ", ",
             _ => println!("You shouldn't be here (field_declaration): {}\n", node.grammar_name()),
@@ -2651,17 +2923,11 @@ fn handle_enum_specifier(root: Node, src: String) -> String {
             },
             "enumerator_list" => {
                 let enumerator_list = handle_enumerator_list(node, src.clone());
-                result += format!("{} ", enumerator_list).as_str();
+                result = format!("{} ", utils::join_block(&result, &enumerator_list, utils::BraceKind::Enum));
             },
             "field_declaration_list" => {
                 let field_declaration_list = handle_field_declaration_list(node, src.clone());
-                // This adds a newline before the curly brace.
-                if result.contains("\n") {
-                    result += field_declaration_list.as_str();
-                }
-                else {
-                    result += format!("\n{}", field_declaration_list).as_str();
-                }
+                result = utils::join_block(&result, &field_declaration_list, utils::BraceKind::Enum);
             },
             "enum" => result += "enum ",
             _ => println!("You shouldn't be here (enum_specifier): {}\n", node.grammar_name()),
@@ -2678,15 +2944,21 @@ fn handle_union_specifier(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += format!("{} ", identifier).as_str();
             },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                result += format!("{} ", attribute_specifier).as_str();
+            },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                result += format!("{} ", attribute_declaration).as_str();
+            },
+            "alignas_qualifier" => {
+                let alignas_qualifier = handle_alignas_qualifier(node, src.clone());
+                result += format!("{} ", alignas_qualifier).as_str();
+            },
             "field_declaration_list" => {
                 let field_declaration_list = handle_field_declaration_list(node, src.clone());
-                // This adds a newline before the curly brace.
-                if result.contains("\n") {
-                    result += field_declaration_list.as_str();
-                }
-                else {
-                    result += format!("\n{}", field_declaration_list).as_str();
-                }
+                result = utils::join_block(&result, &field_declaration_list, utils::BraceKind::Union);
             },
             "union" => result += "union ",
             _ => println!("You shouldn't be here (union_specifier): {}\n", node.grammar_name()),
@@ -2728,6 +3000,11 @@ fn handle_storage_class_specifier(root: Node, src: String) -> String {
         match node.grammar_name() {
             "static" => result += "static",
             "extern" => result += "extern",
+            "inline" => result += "inline",
+            "__inline" => result += "__inline",
+            "__inline__" => result += "__inline__",
+            "_Thread_local" => result += "_Thread_local",
+            "thread_local" => result += "thread_local",
             _ => println!("You shouldn't be here (storage_class_specifier): {}\n", node.grammar_name()),
         }
     }
@@ -2737,6 +3014,7 @@ fn handle_storage_class_specifier(root: Node, src: String) -> String {
 
 fn handle_preproc_ifdef(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
+    let mut last_linkage = false;
     let mut temp = String::new();
     let mut last_kind = "";
     for node in root.children(&mut root.walk()) {
@@ -2749,6 +3027,15 @@ fn handle_preproc_ifdef(root: Node, src: String) -> String {
                 temp += "#ifdef ";
                 last_kind = "#ifdef";
             },
+            // A guard like `#ifdef __cplusplus` opening an `extern "C"` block has its
+            // `#endif` inside the block, so this one is missing.
+            "#endif" if node.is_missing() => (),
+            "linkage_specification" => {
+                let linkage_specification = handle_linkage_specification(node, src.clone());
+                parts.push(linkage_specification);
+                last_linkage = true;
+            },
+            "#endif" if last_linkage => parts.push("#endif".to_string()),
             "#endif" => {
                 parts.push("\n#endif".to_string());
                 last_kind = "#endif";
@@ -2828,6 +3115,16 @@ fn handle_preproc_if(root: Node, src: String) -> String {
                 parts.push(temp);
                 temp = String::new();
             },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                temp += identifier.as_str();
+                parts.push(temp);
+                temp = String::new();
+            },
+            "declaration" => {
+                let declaration = handle_declaration(node, src.clone());
+                parts.push(declaration);
+            },
             "return_statement" => {
                 let return_statement = handle_return_statement(node, src.clone());
                 parts.push(return_statement);
@@ -2977,11 +3274,11 @@ fn handle_do_statement(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "do" => result += "do ",
-            "while" => result += "while ",
+            "while" => result += " while ",
             ";" => result += ";",
             "compound_statement" => {
                 let compound_statement = handle_compound_statement(node, src.clone());
-                result += compound_statement.as_str();
+                result = utils::join_block(&result, &compound_statement, utils::BraceKind::Control);
             },
             "parenthesized_expression" => {
                 let parenthesized_expression = handle_parenthesized_expression(node, src.clone());
@@ -3002,6 +3299,7 @@ fn handle_inner_field_expression(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "->" => result += "->",
+            "." => result += ".",
             "identifier" => {
                 let identifier = handle_identifier(node, src.clone());
                 result += identifier.as_str();
@@ -3010,6 +3308,10 @@ fn handle_inner_field_expression(root: Node, src: String) -> String {
                 let field_expression = handle_field_expression(node, src.clone());
                 result += field_expression.as_str();
             },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 result += call_expression.as_str();
@@ -3023,9 +3325,7 @@ fn handle_inner_field_expression(root: Node, src: String) -> String {
 fn handle_parameter_list(root: Node, src: String) -> String {
     let mut result = String::new();
     for node in root.children(&mut root.walk()) {
-        match node.grammar_name() {
-            _ => println!("Parameter list: {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
-        }
+        println!("Parameter list: {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or(""));
     }
     return result;
 }
@@ -3043,7 +3343,48 @@ fn handle_initializer_pair(root: Node, src: String) -> String {
                 let string_literal = handle_string_literal(node, src.clone());
                 result += string_literal.as_str();
             },
-            "=" => result += format!("= ").as_str(),
+            "=" => result += "= ",
+            "subscript_designator" => result += format!("{} ", node.utf8_text(src.as_bytes()).unwrap()).as_str(),
+            "number_literal" => {
+                let number_literal = handle_number_literal(node, src.clone());
+                result += number_literal.as_str();
+            },
+            "char_literal" => {
+                let char_literal = handle_char_literal(node, src.clone());
+                result += char_literal.as_str();
+            },
+            "field_expression" => {
+                let field_expression = handle_field_expression(node, src.clone());
+                result += field_expression.as_str();
+            },
+            "call_expression" => {
+                let call_expression = handle_call_expression(node, src.clone());
+                result += call_expression.as_str();
+            },
+            "binary_expression" => {
+                let binary_expression = handle_binary_expression(node, src.clone());
+                result += binary_expression.as_str();
+            },
+            "unary_expression" => {
+                let unary_expression = handle_unary_expression(node, src.clone());
+                result += unary_expression.as_str();
+            },
+            "initializer_list" => {
+                let initializer_list = handle_initializer_list(node, src.clone());
+                result += initializer_list.as_str();
+            },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
+            "cast_expression" => {
+                let cast_expression = handle_cast_expression(node, src.clone());
+                result += cast_expression.as_str();
+            },
+            "pointer_expression" => {
+                let pointer_expression = handle_pointer_expression(node, src.clone());
+                result += pointer_expression.as_str();
+            },
             _ => println!("Initializer Pair: {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3062,8 +3403,23 @@ fn handle_gnu_asm_expression(root: Node, src: String) -> String {
     let mut result = String::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
-            "gnu_asm_expression" => {
-                result += node.utf8_text(src.as_bytes()).unwrap();
+            "asm" => result += "asm",
+            "__asm__" => result += "__asm__",
+            "__asm" => result += "__asm",
+            "gnu_asm_qualifier" => result += format!(" {}", node.utf8_text(src.as_bytes()).unwrap_or("")).as_str(),
+            "(" => result += "(",
+            ")" => result += ")",
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                result += string_literal.as_str();
+            },
+            "concatenated_string" => {
+                let concatenated_string = handle_concatenated_string(node, src.clone());
+                result += concatenated_string.as_str();
+            },
+            "gnu_asm_output_operand_list" | "gnu_asm_input_operand_list" | "gnu_asm_clobber_list" | "gnu_asm_goto_list" => {
+                let operand_list = handle_gnu_asm_operand_list(node, src.clone());
+                result += format!(" {}", operand_list).as_str();
             },
             ";" => result += ";",
             _ => println!("Gnu asm expression: {} : {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
@@ -3072,6 +3428,34 @@ fn handle_gnu_asm_expression(root: Node, src: String) -> String {
     return result;
 }
 
+// One `: ...` section of an asm statement: outputs, inputs, clobbers or goto labels.
+fn handle_gnu_asm_operand_list(root: Node, src: String) -> String {
+    let mut operands = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            ":" | "," => (),
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                operands.push(string_literal);
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                operands.push(identifier);
+            },
+            "gnu_asm_output_operand" | "gnu_asm_input_operand" => {
+                // `[name] "constraint" (expression)`
+                let text = node.utf8_text(src.as_bytes()).unwrap_or("");
+                let operand = text.split_whitespace().collect::<Vec<&str>>().join(" ").replace("\" (", "\"(");
+                operands.push(operand);
+            },
+            _ => println!("Gnu asm operand list: {} : {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    if operands.is_empty() { return ":".to_string(); }
+    let result = format!(": {}", operands.join(", "));
+    return result;
+}
+
 fn handle_preproc_defined(root: Node, src: String) -> String {
     let mut result = String::new();
     for node in root.children(&mut root.walk()) {
@@ -3159,3 +3543,652 @@ fn handle_declaration_list(root: Node, src: String) -> String {
     return result;
 }
 
+fn handle_attribute_declaration(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "[[" => result += "[[",
+            "]]" => result += "]]",
+            "," => result += ", ",
+            "attribute" => {
+                let attribute = handle_attribute(node, src.clone());
+                result += attribute.as_str();
+            },
+            _ => println!("You shouldn't be here (attribute_declaration): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_attribute(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "argument_list" => {
+                let argument_list = handle_argument_list(node, src.clone());
+                result += argument_list.as_str();
+            },
+            "::" => result += "::",
+            "using" => result += "using ",
+            ":" => result += ": ",
+            _ => println!("You shouldn't be here (attribute): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_attribute_specifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "__attribute__" => result += "__attribute__",
+            "__attribute" => result += "__attribute",
+            "(" => result += "(",
+            ")" => result += ")",
+            "argument_list" => {
+                let argument_list = handle_argument_list(node, src.clone());
+                result += argument_list.as_str();
+            },
+            _ => println!("You shouldn't be here (attribute_specifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_alignas_qualifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "alignas" => result += "alignas",
+            "_Alignas" => result += "_Alignas",
+            "(" => result += "(",
+            ")" => result += ")",
+            "number_literal" => {
+                let number_literal = handle_number_literal(node, src.clone());
+                result += number_literal.as_str();
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.as_str();
+            },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
+            "sizeof_expression" => {
+                let sizeof_expression = handle_sizeof_expression(node, src.clone());
+                result += sizeof_expression.as_str();
+            },
+            "binary_expression" => {
+                let binary_expression = handle_binary_expression(node, src.clone());
+                result += binary_expression.as_str();
+            },
+            _ => println!("You shouldn't be here (alignas_qualifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_attributed_statement(root: Node, src: String) -> String {
+    let mut attributes = Vec::<String>::new();
+    let mut statement = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                attributes.push(attribute_declaration);
+            },
+            "expression_statement" => statement = handle_expression_statement(node, src.clone()),
+            "return_statement" => statement = handle_return_statement(node, src.clone()),
+            "if_statement" => statement = handle_if_statement(node, src.clone()),
+            "switch_statement" => statement = handle_switch_statement(node, src.clone()),
+            "while_statement" => statement = handle_while_statement(node, src.clone()),
+            "for_statement" => statement = handle_for_statement(node, src.clone()),
+            "do_statement" => statement = handle_do_statement(node, src.clone()),
+            "goto_statement" => statement = handle_goto_statement(node, src.clone()),
+            "labeled_statement" => statement = handle_labeled_statement(node, src.clone()).trim_start().to_string(),
+            "compound_statement" => statement = handle_inner_compound_statement(node, src.clone()),
+            "break_statement" => statement = "break;".to_string(),
+            "continue_statement" => statement = "continue;".to_string(),
+            _ => println!("You shouldn't be here (attributed_statement): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    // An empty statement like `[[fallthrough]];` keeps the semicolon attached.
+    if statement.trim() == ";" { return format!("{};", attributes.join(" ")); }
+    return format!("{} {}", attributes.join(" "), statement);
+}
+
+// `_Generic`, compound literals, `alignof` and `offsetof` can show up anywhere an expression can.
+fn handle_builtin_expression(root: Node, src: String) -> String {
+    let result = match root.grammar_name() {
+        "generic_expression" => handle_generic_expression(root, src.clone()),
+        "compound_literal_expression" => handle_compound_literal_expression(root, src.clone()),
+        "alignof_expression" => handle_alignof_expression(root, src.clone()),
+        "offsetof_expression" => handle_offsetof_expression(root, src.clone()),
+        _ => {
+            println!("You shouldn't be here (builtin_expression): {}: {}\n", root.grammar_name(), root.utf8_text(src.as_bytes()).unwrap_or(""));
+            String::new()
+        },
+    };
+    return result;
+}
+
+fn handle_generic_expression(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "_Generic" => result += "_Generic",
+            "(" => result += "(",
+            ")" => result += ")",
+            "," => result += ", ",
+            ":" => result += ": ",
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.trim();
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "field_expression" => {
+                let field_expression = handle_field_expression(node, src.clone());
+                result += field_expression.as_str();
+            },
+            "pointer_expression" => {
+                let pointer_expression = handle_pointer_expression(node, src.clone());
+                result += pointer_expression.as_str();
+            },
+            "parenthesized_expression" => {
+                let parenthesized_expression = handle_parenthesized_expression(node, src.clone());
+                result += parenthesized_expression.as_str();
+            },
+            "generic_expression" | "compound_literal_expression" | "alignof_expression" | "offsetof_expression" => {
+                let builtin_expression = handle_builtin_expression(node, src.clone());
+                result += builtin_expression.as_str();
+            },
+            "call_expression" => {
+                let call_expression = handle_call_expression(node, src.clone());
+                result += call_expression.as_str();
+            },
+            "number_literal" => {
+                let number_literal = handle_number_literal(node, src.clone());
+                result += number_literal.as_str();
+            },
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                result += string_literal.as_str();
+            },
+            "char_literal" => {
+                let char_literal = handle_char_literal(node, src.clone());
+                result += char_literal.as_str();
+            },
+            _ => println!("You shouldn't be here (generic_expression): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_compound_literal_expression(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "(" => result += "(",
+            ")" => result += ")",
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.trim();
+            },
+            "initializer_list" => {
+                let initializer_list = handle_initializer_list(node, src.clone());
+                result += initializer_list.as_str();
+            },
+            _ => println!("You shouldn't be here (compound_literal_expression): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_alignof_expression(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "alignof" | "_Alignof" | "__alignof__" | "__alignof" | "_alignof" => result += node.grammar_name(),
+            "(" => result += "(",
+            ")" => result += ")",
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.trim();
+            },
+            _ => println!("You shouldn't be here (alignof_expression): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_offsetof_expression(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "offsetof" => result += "offsetof",
+            "(" => result += "(",
+            ")" => result += ")",
+            "," => result += ", ",
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.trim();
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "field_identifier" => {
+                let field_identifier = handle_field_identifier(node, src.clone());
+                result += field_identifier.as_str();
+            },
+            _ => println!("You shouldn't be here (offsetof_expression): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+// `typeof(x)`, `__typeof__(x)` and other macro-like type specifiers.
+fn handle_macro_type_specifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "(" => result += "(",
+            ")" => result += ")",
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.trim();
+            },
+            _ => println!("You shouldn't be here (macro_type_specifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_linkage_specification(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "extern" => result += "extern ",
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                result += string_literal.as_str();
+            },
+            "declaration_list" => {
+                let declaration_list = handle_linkage_declaration_list(node, src.clone());
+                result = utils::join_block(&result, &declaration_list, utils::BraceKind::Extern);
+            },
+            "declaration" => {
+                let declaration = handle_declaration(node, src.clone());
+                result += format!(" {}", declaration).as_str();
+            },
+            "function_definition" => {
+                let function_definition = handle_function_definition(node, src.clone());
+                result += format!(" {}", function_definition).as_str();
+            },
+            _ => println!("You shouldn't be here (linkage_specification): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_linkage_declaration_list(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    let mut last_kind = "";
+    for node in root.children(&mut root.walk()) {
+        let kind = node.grammar_name();
+        // The `;` after a struct body, already added below.
+        if kind == ";" { continue; }
+        // Keep declarations grouped, but give everything else some room.
+        let needs_space = {
+            !last_kind.is_empty() && last_kind != "{" &&
+            (kind != last_kind || kind == "function_definition")
+        };
+        if needs_space && kind != "}" { parts.push(String::new()); }
+        match kind {
+            "{" => parts.push("{".to_string()),
+            "}" => parts.push("}".to_string()),
+            "declaration" => {
+                let mut declaration = handle_declaration(node, src.clone());
+                if declaration.contains("*") { declaration = utils::remove_pointer_spaces(declaration); }
+                parts.push(utils::add_all_leading_tabs(declaration));
+            },
+            "function_definition" => {
+                let function_definition = handle_function_definition(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(function_definition));
+            },
+            "struct_specifier" => {
+                let struct_specifier = handle_struct_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", struct_specifier.trim_end())));
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", enum_specifier.trim_end())));
+            },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", union_specifier.trim_end())));
+            },
+            // `_Static_assert(...)` is parsed as a call.
+            "expression_statement" => {
+                let expression_statement = handle_expression_statement(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(expression_statement));
+            },
+            "type_definition" => {
+                let type_definition = handle_type_definition(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(type_definition));
+            },
+            "comment" => {
+                let comment = handle_comment(node, src.clone());
+                parts.push(format!("\t{}", comment));
+            },
+            "preproc_include" => {
+                let preproc_include = handle_preproc_include(node, src.clone());
+                parts.push(preproc_include);
+            },
+            "preproc_def" => {
+                let preproc_def = handle_preproc_def(node, src.clone());
+                parts.push(preproc_def);
+            },
+            "preproc_function_def" => {
+                let preproc_function_def = handle_preproc_function_def(node, src.clone());
+                parts.push(preproc_function_def);
+            },
+            "preproc_call" => {
+                let preproc_call = handle_preproc_call(node, src.clone());
+                parts.push(preproc_call.trim_end().to_string());
+            },
+            "preproc_ifdef" => {
+                let preproc_ifdef = handle_preproc_ifdef(node, src.clone());
+                parts.push(preproc_ifdef.trim().to_string());
+            },
+            "preproc_if" => {
+                let preproc_if = handle_preproc_if(node, src.clone());
+                parts.push(preproc_if.trim().to_string());
+            },
+            "linkage_specification" => {
+                let linkage_specification = handle_linkage_specification(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(linkage_specification));
+            },
+            _ => println!("You shouldn't be here (linkage_declaration_list): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+        last_kind = kind;
+    }
+    let result = parts.join("\n");
+    return result;
+}
+
+fn handle_ms_declspec_modifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "__declspec" => result += "__declspec",
+            "(" => result += "(",
+            ")" => result += ")",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "call_expression" => {
+                let call_expression = handle_call_expression(node, src.clone());
+                result += call_expression.as_str();
+            },
+            _ => println!("You shouldn't be here (ms_declspec_modifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(src: &str) -> String {
+        let ast = c_format::parse_existing_c_file(src.to_string());
+        return traverse_c_ast(ast, src.to_string(), options::COpts::default());
+    }
+
+    fn format_with(src: &str, args: &[&str]) -> String {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        let ast = c_format::parse_existing_c_file(src.to_string());
+        return traverse_c_ast(ast, src.to_string(), options::get_c_opts(&args));
+    }
+
+    #[test]
+    fn builtin_expressions() {
+        let src = "\
+int main() {
+	int *p = (int[]){1, 2, 3};
+	size_t s = alignof(double) + _Alignof(int) + offsetof(struct S, a);
+	f((int[2]){1, 2});
+	char buf[alignof(int)];
+	int z = c ? alignof(int) : 0;
+	return alignof(int) * 2;
+}
+";
+        let expected = "\
+int main()
+{
+	int* p = (int[]){1, 2, 3};
+	size_t s = alignof(double) + _Alignof(int) + offsetof(struct S, a);
+	f((int[2]){1, 2});
+	char buf[alignof(int)];
+	int z = c ? alignof(int) : 0;
+	return alignof(int) * 2;
+}";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn compound_assignments() {
+        let src = "\
+int f(int h) {
+	h += 2; h -= 1; h *= 3; h /= 2; h %= 5;
+	h <<= 1; h >>= 1; h &= 7; h |= 8; h ^= 1;
+	a = b += 1;
+	return h;
+}
+";
+        let expected = "\
+int f(int h)
+{
+	h += 2;
+	h -= 1;
+	h *= 3;
+	h /= 2;
+	h %= 5;
+	h <<= 1;
+	h >>= 1;
+	h &= 7;
+	h |= 8;
+	h ^= 1;
+	a = b += 1;
+	return h;
+}";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn case_ranges() {
+        let src = "\
+int f(int c) {
+	switch (c) {
+	case 1 ... 5:
+		return 1;
+	case 'a' ... 'z':
+		return 2;
+	}
+}
+";
+        let expected = "\
+int f(int c)
+{
+	switch (c) {
+	case 1 ... 5:
+		return 1;
+	case 'a' ... 'z':
+		return 2;
+	}
+}";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn extern_c_blocks() {
+        let src = "\
+extern \"C\" {
+_Static_assert(sizeof(int) == 4, \"int\");
+#if X
+int y;
+#endif
+int x;
+}
+";
+        let expected = "\
+extern \"C\"
+{
+	_Static_assert(sizeof(int) == 4, \"int\");
+#if X
+	int y;
+#endif
+	int x;
+}";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn unions_and_enums() {
+        let src = "\
+struct point { int x; int y; };
+
+union value {
+    int i;
+    float f;   /* the other view */
+};
+
+enum color { RED, GREEN };
+
+int main(void) {
+    union value v;
+    union local { int a; float b; };
+    enum dir { UP, DOWN };
+    return 0;
+}
+";
+        let cases = [
+            (vec![], "\
+struct point
+{
+	int x;
+	int y;
+};
+
+union value
+{
+	int i;
+	float f;	/* the other view */
+};
+
+enum color
+{
+	RED,
+	GREEN
+};
+
+int main(void)
+{
+	union value v;
+	union local
+	{
+		int a;
+		float b;
+	};
+	enum dir
+	{
+		UP,
+		DOWN
+	};
+	return 0;
+}"),
+            (vec!["-b", "union=attach", "enum=attach"], "\
+struct point
+{
+	int x;
+	int y;
+};
+
+union value {
+	int i;
+	float f;	/* the other view */
+};
+
+enum color {
+	RED,
+	GREEN
+};
+
+int main(void)
+{
+	union value v;
+	union local {
+		int a;
+		float b;
+	};
+	enum dir {
+		UP,
+		DOWN
+	};
+	return 0;
+}"),
+            (vec!["-s", "k&r", "-b", "union=newline"], "\
+struct point {
+	int x;
+	int y;
+};
+
+union value
+{
+	int i;
+	float f;	/* the other view */
+};
+
+enum color {
+	RED,
+	GREEN
+};
+
+int main(void)
+{
+	union value v;
+	union local
+	{
+		int a;
+		float b;
+	};
+	enum dir {
+		UP,
+		DOWN
+	};
+	return 0;
+}"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+// CMake files are read with a small scanner (there's no tree-sitter grammar for
+// them here) and reprinted one command per line: command names in lower case,
+// `if()/endif()`, `foreach()`, `while()`, `function()`, `macro()` and `block()`
+// bodies indented, and arguments that don't fit on one line wrapped before
+// their keywords (PUBLIC, COMMAND, AND, ...) with the `)` on its own line.
+
+const INDENT: &str = "    ";
+const MAX_WIDTH: usize = 80;
+
+const BLOCK_OPENERS: [&str; 6] = ["if", "foreach", "while", "function", "macro", "block"];
+const BLOCK_CLOSERS: [&str; 6] = ["endif", "endforeach", "endwhile", "endfunction", "endmacro", "endblock"];
+
+enum CMakeItem {
+    // the name, the arguments and a comment after the `)`
+    Command(String, Vec<String>, Option<String>),
+    Comment(String),
+    Blank,
+}
+
+pub fn format_cmake(src: String) -> String {
+    let items = match parse_cmake(&src) {
+        Some(items) => items,
+        None => {
+            eprintln!("Syntax errors found, the CMake file was left as it is.");
+            return src;
+        },
+    };
+    let mut lines = Vec::<String>::new();
+    let mut depth: usize = 0;
+    for item in items {
+        match item {
+            CMakeItem::Blank => if !lines.is_empty() { lines.push(String::new()); },
+            CMakeItem::Comment(comment) => lines.push(indent_first_line(&comment, depth)),
+            CMakeItem::Command(name, args, comment) => {
+                if BLOCK_CLOSERS.contains(&name.as_str()) { depth = depth.saturating_sub(1); }
+                let command_depth = if name == "else" || name == "elseif" { depth.saturating_sub(1) } else { depth };
+                let mut command = format_command(&name, &args, command_depth);
+                if let Some(comment) = comment {
+                    let last = command.len() - 1;
+                    command[last] = format!("{} {}", command[last], comment);
+                }
+                lines.extend(command);
+                if BLOCK_OPENERS.contains(&name.as_str()) { depth += 1; }
+            },
+        }
+    }
+    while lines.last().is_some_and(|x| x.is_empty()) { lines.pop(); }
+    if lines.is_empty() { return String::new(); }
+    return lines.join("\n") + "\n";
+}
+
+fn indent_first_line(text: &str, depth: usize) -> String {
+    return format!("{}{}", INDENT.repeat(depth), text);
+}
+
+// Arguments in upper case (PUBLIC, COMMAND, AND, ...) start a new line when
+// the arguments get wrapped.
+fn is_keyword(arg: &str) -> bool {
+    return arg.len() > 1
+        && arg.starts_with(|c: char| c.is_ascii_uppercase())
+        && arg.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+}
+
+fn format_command(name: &str, args: &[String], depth: usize) -> Vec<String> {
+    let indent = INDENT.repeat(depth);
+    let has_comments = args.iter().any(|x| x.starts_with('#'));
+    let has_breaks = args.iter().any(|x| x.contains('\n'));
+    let one_line = format!("{}{}({})", indent, name, args.join(" "));
+    if !has_comments && !has_breaks && one_line.len() <= MAX_WIDTH { return vec![one_line]; }
+    let inner = INDENT.repeat(depth + 1);
+    let mut lines = vec![format!("{}{}(", indent, name)];
+    // whether the current line still takes arguments, and whether it has any
+    let mut is_open = true;
+    let mut is_empty = true;
+    for (i, arg) in args.iter().enumerate() {
+        let last = lines.len() - 1;
+        let fits = lines[last].len() + 1 + arg.split('\n').next().unwrap_or("").len() <= MAX_WIDTH;
+        // only the first argument stays after the `(`
+        let starts_line = !is_open || (i > 0 && (i == 1 || is_keyword(arg) || !fits)) && !arg.starts_with('#');
+        if starts_line {
+            lines.push(format!("{}{}", inner, arg));
+        }
+        else {
+            let separator = if is_empty { "" } else { " " };
+            lines[last] = format!("{}{}{}", lines[last], separator, arg);
+        }
+        is_empty = false;
+        // comments end their line, and so do arguments spanning several lines
+        is_open = !arg.starts_with('#') && !arg.contains('\n');
+        if arg.contains('\n') {
+            let last = lines.len() - 1;
+            let joined = lines.remove(last);
+            lines.extend(joined.split('\n').map(|x| x.to_string()));
+        }
+    }
+    // a `)` right after a quoted or bracket argument spanning lines stays there
+    if args.last().is_some_and(|x| x.contains('\n')) {
+        let last = lines.len() - 1;
+        lines[last] += ")";
+    }
+    else {
+        lines.push(format!("{})", indent));
+    }
+    return lines;
+}
+
+// Splits the file into commands, comments and blank lines. Returns None when a
+// command isn't closed or something that isn't a command is found.
+fn parse_cmake(src: &str) -> Option<Vec<CMakeItem>> {
+    let chars = src.chars().collect::<Vec<char>>();
+    let mut items = Vec::<CMakeItem>::new();
+    let mut i = 0;
+    let mut newlines = 1;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            newlines += 1;
+            if newlines == 2 && !items.is_empty() { items.push(CMakeItem::Blank); }
+            i += 1;
+        }
+        else if c.is_whitespace() {
+            i += 1;
+        }
+        else if c == '#' {
+            let (comment, end) = read_comment(&chars, i);
+            let after_command = newlines == 0 && matches!(items.last(), Some(CMakeItem::Command(_, _, None)));
+            if after_command {
+                if let Some(CMakeItem::Command(_, _, trailing)) = items.last_mut() { *trailing = Some(comment); }
+            }
+            else {
+                items.push(CMakeItem::Comment(comment));
+            }
+            newlines = 0;
+            i = end;
+        }
+        else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') { i += 1; }
+            let name = chars[start..i].iter().collect::<String>().to_lowercase();
+            while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') { i += 1; }
+            if i >= chars.len() || chars[i] != '(' { return None; }
+            let (args, end) = read_arguments(&chars, i + 1)?;
+            items.push(CMakeItem::Command(name, args, None));
+            newlines = 0;
+            i = end;
+        }
+        else {
+            return None;
+        }
+    }
+    while matches!(items.last(), Some(CMakeItem::Blank)) { items.pop(); }
+    return Some(items);
+}
+
+// The length of the `[==[` that opens a bracket argument or comment at `i`, if there's one.
+fn bracket_open(chars: &[char], i: usize) -> Option<usize> {
+    if chars.get(i) != Some(&'[') { return None; }
+    let mut j = i + 1;
+    while chars.get(j) == Some(&'=') { j += 1; }
+    if chars.get(j) != Some(&'[') { return None; }
+    return Some(j + 1 - i);
+}
+
+// Reads `[==[ ... ]==]` starting at `i`, returning the text and the index after it.
+fn read_bracket(chars: &[char], i: usize, open: usize) -> (String, usize) {
+    let close = format!("]{}]", "=".repeat(open - 2)).chars().collect::<Vec<char>>();
+    let mut j = i + open;
+    while j < chars.len() && !chars[j..].starts_with(&close) { j += 1; }
+    let end = (j + close.len()).min(chars.len());
+    return (chars[i..end].iter().collect(), end);
+}
+
+fn read_comment(chars: &[char], i: usize) -> (String, usize) {
+    if let Some(open) = bracket_open(chars, i + 1) {
+        let (text, end) = read_bracket(chars, i + 1, open);
+        return (format!("#{}", text), end);
+    }
+    let mut j = i;
+    while j < chars.len() && chars[j] != '\n' { j += 1; }
+    return (chars[i..j].iter().collect::<String>().trim_end().to_string(), j);
+}
+
+// Reads the arguments of a command up to its `)`. Parentheses nested in the
+// arguments stick to the argument next to them, so `(A OR B)` reads as `(A`,
+// `OR` and `B)`.
+fn read_arguments(chars: &[char], start: usize) -> Option<(Vec<String>, usize)> {
+    let mut args = Vec::<String>::new();
+    let mut depth = 1;
+    let mut glue_next = false;
+    let mut i = start;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() { i += 1; continue; }
+        let arg;
+        if c == '(' {
+            depth += 1;
+            i += 1;
+            if glue_next { let last = args.len() - 1; args[last].push('('); }
+            else { args.push("(".to_string()); }
+            glue_next = true;
+            continue;
+        }
+        if c == ')' {
+            depth -= 1;
+            i += 1;
+            if depth == 0 { return Some((args, i)); }
+            match args.last_mut() {
+                Some(last) if !last.starts_with('#') => last.push(')'),
+                _ => args.push(")".to_string()),
+            }
+            glue_next = false;
+            continue;
+        }
+        if c == '#' {
+            let (comment, end) = read_comment(chars, i);
+            args.push(comment);
+            glue_next = false;
+            i = end;
+            continue;
+        }
+        if let Some(open) = bracket_open(chars, i) {
+            let (text, end) = read_bracket(chars, i, open);
+            arg = text;
+            i = end;
+        }
+        else {
+            let begin = i;
+            let mut in_quotes = false;
+            while i < chars.len() {
+                let d = chars[i];
+                if d == '\\' { i += 2; continue; }
+                if d == '"' { in_quotes = !in_quotes; }
+                else if !in_quotes && (d.is_whitespace() || d == '(' || d == ')') { break; }
+                i += 1;
+            }
+            if in_quotes { return None; }
+            arg = chars[begin..i.min(chars.len())].iter().collect();
+        }
+        if glue_next { let last = args.len() - 1; args[last].push_str(&arg); }
+        else { args.push(arg); }
+        glue_next = false;
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_are_indented() {
+        let src = "\
+CMAKE_MINIMUM_REQUIRED(VERSION 3.16)
+IF(WIN32)  # windows
+set(SRC main.c)
+ELSE()
+foreach(f a b)
+message(STATUS ${f})
+endforeach()
+endif()
+
+
+project(blfmt C)
+";
+        let expected = "\
+cmake_minimum_required(VERSION 3.16)
+if(WIN32) # windows
+    set(SRC main.c)
+else()
+    foreach(f a b)
+        message(STATUS ${f})
+    endforeach()
+endif()
+
+project(blfmt C)
+";
+        assert_eq!(format_cmake(src.to_string()), expected);
+    }
+
+    #[test]
+    fn long_commands_wrap_before_keywords() {
+        let src = "\
+target_link_libraries(blfmt PUBLIC tree-sitter tree-sitter-c tree-sitter-cpp PRIVATE regex-static)
+add_custom_command(OUTPUT gen.c COMMAND python3 gen.py  # generator
+DEPENDS gen.py)
+";
+        let expected = "\
+target_link_libraries(blfmt
+    PUBLIC tree-sitter tree-sitter-c tree-sitter-cpp
+    PRIVATE regex-static
+)
+add_custom_command(OUTPUT
+    gen.c
+    COMMAND python3 gen.py # generator
+    DEPENDS gen.py
+)
+";
+        assert_eq!(format_cmake(src.to_string()), expected);
+    }
+}
@@ -1,16 +1,37 @@
 use tree_sitter::{Tree, Node};
-use crate::{cpp_format, utils};
+use crate::{cpp_format, options, utils};
 
-pub fn traverse_cpp_ast(ast: Tree, src: String, style: utils::Style) -> String {
+pub fn traverse_cpp_ast(ast: Tree, src: String, opts: options::COpts) -> String {
+    utils::set_active_c_opts(opts);
     let root = ast.root_node();
     let mut result = String::new();
     let mut last_group_kind = String::new();
     let lines_before_blank_lines = utils::scan_for_lines_before_blank_lines(src.clone());
-    let mut i = 0;
+    // what follows an `export { ... }` block, already formatted
+    let mut rest_of_file = String::new();
     dbg!(&src);
     dbg!(&root.named_child_count());
     for child in root.children(&mut root.walk()) {
-        i += 1;
+        if is_module_declaration(child.utf8_text(src.as_bytes()).unwrap_or("")) {
+            if let Some((open, close)) = find_export_block(&src, child.start_byte()) {
+                let export_block = handle_export_block(&src[open + 1..close], opts);
+                result += format!("\n{}\n\n", export_block).as_str();
+                // The grammar has no modules, so the nodes after the block can't be
+                // trusted: the rest of the file is formatted on its own.
+                let rest = src[close + 1..].to_string();
+                if !rest.trim().is_empty() {
+                    let ast = cpp_format::parse_existing_cpp_file(rest.clone());
+                    rest_of_file = traverse_cpp_ast(ast, rest, opts);
+                    utils::set_active_c_opts(opts);
+                }
+                break;
+            }
+            let module_declaration = handle_module_declaration(child, src.clone());
+            if module_declaration.contains("\n") { result += format!("\n{}\n\n", module_declaration).as_str(); }
+            else { result += format!("{}\n", module_declaration).as_str(); }
+            last_group_kind = "module_declaration".to_string();
+            continue;
+        }
         match child.grammar_name() {
             "preproc_include" => {
                 if !last_group_kind.contains("preproc") { result += "\n"; }
@@ -19,14 +40,27 @@ pub fn traverse_cpp_ast(ast: Tree, src: String, style: utils::Style) -> String {
                 last_group_kind = "preproc_include".to_string();
             },
             "declaration" => {
-                if last_group_kind.contains("preproc") { result += "\n"; }
+                if last_group_kind.contains("preproc") || last_group_kind == "module_declaration" { result += "\n"; }
                 let declaration = handle_declaration(child, src.clone());
                 result += format!("{}\n", declaration).as_str();
                 last_group_kind = "declaration".to_string();
             },
+            "static_assert_declaration" => {
+                if last_group_kind.contains("preproc") { result += "\n"; }
+                let static_assert_declaration = handle_static_assert_declaration(child, src.clone());
+                result += format!("{}\n", static_assert_declaration).as_str();
+                last_group_kind = "declaration".to_string();
+            },
+            "linkage_specification" => {
+                result += "\n";
+                let linkage_specification = handle_linkage_specification(child, src.clone());
+                result += format!("{}\n\n", linkage_specification).as_str();
+                last_group_kind = "linkage_specification".to_string();
+            },
             "function_definition" => {
                 let should_add_space = {
                     last_group_kind.contains("preproc")    ||
+                    last_group_kind == "module_declaration" ||
                     last_group_kind == "using_declaration" ||
                     last_group_kind == "declaration"
                 };
@@ -70,6 +104,12 @@ pub fn traverse_cpp_ast(ast: Tree, src: String, style: utils::Style) -> String {
                 result += format!("{}\n\n", return_statement).as_str();
                 last_group_kind = "return_statement".to_string();
             },
+            "comment" if is_trailing_comment(child) && last_group_kind == "namespace_definition" => {
+                // Regenerated by the namespace itself when end comments are on.
+                if utils::active_c_opts().namespace_end_comments { continue; }
+                let comment = handle_comment(child, src.clone());
+                result = format!("{} {}\n\n", result.trim_end(), comment.trim());
+            },
             "comment" => {
                 let comment = handle_comment(child, src.clone());
                 if last_group_kind.contains("preproc") { result += "\n"; }
@@ -88,7 +128,7 @@ pub fn traverse_cpp_ast(ast: Tree, src: String, style: utils::Style) -> String {
             },
             "struct_specifier" => {
                 let struct_specifier = handle_struct_specifier(child, src.clone());
-                result += format!("{}\n\n", struct_specifier).as_str();
+                result += format!("{};\n\n", struct_specifier.trim_end()).as_str();
                 last_group_kind = "struct_specifier".to_string();
             },
             "preproc_def" => {
@@ -144,6 +184,12 @@ pub fn traverse_cpp_ast(ast: Tree, src: String, style: utils::Style) -> String {
                 result += format!("{};\n\n", class_specifier.trim_end()).as_str();
                 last_group_kind = "class_specifier".to_string();
             },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(child, src.clone());
+                if last_group_kind.contains("preproc") { result += "\n"; }
+                result += format!("{};\n\n", union_specifier.trim_end()).as_str();
+                last_group_kind = "union_specifier".to_string();
+            },
             "template_declaration" => {
                 let template_declaration = handle_template_declaration(child, src.clone());
                 result += format!("{}\n\n", template_declaration).as_str();
@@ -158,8 +204,7 @@ pub fn traverse_cpp_ast(ast: Tree, src: String, style: utils::Style) -> String {
             "namespace_definition" => {
                 if last_group_kind != "namespace_definition" { result += "\n"; }
                 let namespace_definition = handle_namespace_definition(child, src.clone());
-                // the end semicolon is added at an unknown point
-                result += format!("{}", namespace_definition).as_str();
+                result += format!("{}\n\n", namespace_definition).as_str();
                 last_group_kind = "namespace_definition".to_string();
             },
             "alias_declaration" => {
@@ -176,16 +221,17 @@ pub fn traverse_cpp_ast(ast: Tree, src: String, style: utils::Style) -> String {
         panic!("Fatal: Unable to parse language grammar");
     }
     result = utils::sort_include_groups(result);
-    utils::format_else_lines(&mut result, &style);
     utils::close_empty_curly_brace_blocks(&mut result);
     result = utils::fix_stars(result);
-    utils::tidy_up_loose_ends(&mut result, style);
+    utils::tidy_up_loose_ends(&mut result, opts);
     result = result.trim_start().to_string();
+    if !rest_of_file.is_empty() { result = format!("{}\n\n{}", result.trim_end(), rest_of_file); }
     return result;
 }
 
 fn handle_compound_statement(root: Node, src: String) -> String {
     let mut result = String::new();
+    let mut pending_head = String::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "preproc_include" => {
@@ -206,6 +252,12 @@ fn handle_compound_statement(root: Node, src: String) -> String {
             },
             "compound_statement" => {
                 let compound_statement = handle_inner_compound_statement(node, src.clone());
+                if !pending_head.is_empty() {
+                    let block = utils::join_block(&pending_head, &compound_statement, utils::BraceKind::Control);
+                    result += format!("{}\n", utils::add_all_leading_tabs(block)).as_str();
+                    pending_head.clear();
+                    continue;
+                }
                 result += format!("\t{}\n", compound_statement).as_str();
             },
             "if_statement" => {
@@ -224,11 +276,11 @@ fn handle_compound_statement(root: Node, src: String) -> String {
                 result += format!("\t{}\n", case_statement).as_str();
             },
             "break_statement" => {
-                let break_statement = "break;\n".to_string();
+                let break_statement = "break;".to_string();
                 result += format!("\t{}\n", break_statement).as_str();
             },
             "continue_statement" => {
-                let continue_statement = "continue;\n".to_string();
+                let continue_statement = "continue;".to_string();
                 result += format!("\t{}\n", continue_statement).as_str();
             },
             "goto_statement" => {
@@ -247,6 +299,10 @@ fn handle_compound_statement(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += format!("\t{}\n", identifier).as_str();
             },
+            "co_return_statement" | "co_yield_statement" => {
+                let coroutine_statement = handle_coroutine_statement(node, src.clone());
+                result += format!("\t{}\n", coroutine_statement).as_str();
+            },
             "return_statement" => {
                 let return_statement = handle_return_statement(node, src.clone());
                 result += format!("\t{}\n", return_statement).as_str();
@@ -256,6 +312,15 @@ fn handle_compound_statement(root: Node, src: String) -> String {
                 if comment.starts_with(" ") { result += format!("{}\n", comment).as_str(); }
                 else { result += format!("\t{}\n", comment).as_str(); }
             },
+            "static_assert_declaration" => {
+                let static_assert_declaration = handle_static_assert_declaration(node, src.clone());
+                result += format!("\t{}\n", static_assert_declaration).as_str();
+            },
+            "attributed_statement" => {
+                let mut attributed_statement = handle_attributed_statement(node, src.clone());
+                attributed_statement = utils::add_all_leading_tabs(attributed_statement);
+                result += format!("{}\n", attributed_statement).as_str();
+            },
             "labeled_statement" => {
                 let labeled_statement = handle_labeled_statement(node, src.clone());
                 result += format!("\t{}\n", labeled_statement).as_str();
@@ -286,13 +351,27 @@ fn handle_compound_statement(root: Node, src: String) -> String {
             },
             "struct_specifier" => {
                 let struct_specifier = handle_struct_specifier(node, src.clone());
-                result += format!("\t{}\n", struct_specifier).as_str();
+                result += utils::add_all_leading_tabs(struct_specifier.trim_end().to_string()).as_str();
+            },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(node, src.clone());
+                result += utils::add_all_leading_tabs(union_specifier.trim_end().to_string()).as_str();
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                result += utils::add_all_leading_tabs(enum_specifier.trim_end().to_string()).as_str();
             },
             "init_declarator" => {
                 let init_declarator = handle_init_declarator(node, src.clone());
                 result += format!("\t{}\n", init_declarator).as_str();
             },
             "ERROR" => {
+                // The grammar doesn't know `if consteval { ... }` yet.
+                let text = node.utf8_text(src.as_bytes()).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ");
+                if text == "if consteval" || text == "if !consteval" {
+                    pending_head = text;
+                    continue;
+                }
                 let error = handle_error(node, src.clone());
                 result += format!("\t{}\n", error).as_str();
             },
@@ -337,7 +416,8 @@ fn handle_compound_statement(root: Node, src: String) -> String {
             "false" => {
                 dbg!(node.utf8_text(src.as_bytes()).unwrap());
             },
-            ";" => result += ";",
+            // only a struct, union or enum is followed by a bare `;`
+            ";" => result += ";\n",
             "{" => result += "{\n",
             "}" => result += "}",
             _ => println!("Unknown grammar name 2: {}\n", node.grammar_name()),
@@ -347,27 +427,40 @@ fn handle_compound_statement(root: Node, src: String) -> String {
 }
 
 fn handle_preproc_include(root: Node, src: String) -> String {
-    let result: String;
     let mut vec = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         vec.push(node.utf8_text(src.as_bytes()).unwrap().to_string());
     }
-    result = vec.join(" ");
+    let result = vec.join(" ");
     return result;
 }
 
 fn handle_declaration(root: Node, src: String) -> String {
     let mut result: String;
     let mut parts = Vec::<String>::new();
+    let mut attributes = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "ms_declspec_modifier" => {
+                let ms_declspec_modifier = handle_ms_declspec_modifier(node, src.clone());
+                parts.push(ms_declspec_modifier);
+            },
+            "ms_call_modifier" => parts.push(node.utf8_text(src.as_bytes()).unwrap_or("").to_string()),
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                attributes.push(attribute_declaration);
+            },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                parts.push(attribute_specifier);
+            },
             "compound_statement" => {
-                let compound_statement = handle_compound_statement(node.clone(), src.clone());
+                let compound_statement = handle_compound_statement(node, src.clone());
                 parts.push(compound_statement);
             },
             "init_declarator" => {
                 let init_declarator = handle_init_declarator(node, src.clone());
-                parts.push(init_declarator);
+                push_declarator(&mut parts, init_declarator);
             },
             "primitive_type" => {
                 let primitive_type = handle_primitive_type(node, src.clone());
@@ -405,6 +498,14 @@ fn handle_declaration(root: Node, src: String) -> String {
                 let struct_specifier = handle_struct_specifier(node, src.clone());
                 parts.push(struct_specifier);
             },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(node, src.clone());
+                parts.push(union_specifier);
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                parts.push(enum_specifier);
+            },
             "sized_type_specifier" => {
                 let sized_type_specifier = handle_sized_type_specifier(node, src.clone());
                 parts.push(sized_type_specifier);
@@ -417,7 +518,27 @@ fn handle_declaration(root: Node, src: String) -> String {
                 let qualified_identifier = handle_qualified_identifier(node, src.clone());
                 parts.push(qualified_identifier);
             },
-            "placeholder_type_specifier" => parts.push("auto".to_string()),
+            "virtual" => parts.push("virtual".to_string()),
+            "decltype" => {
+                let decltype = handle_decltype(node, src.clone());
+                parts.push(decltype);
+            },
+            "explicit_function_specifier" => {
+                let explicit_function_specifier = handle_explicit_function_specifier(node, src.clone());
+                parts.push(explicit_function_specifier);
+            },
+            "operator_cast" => {
+                let operator_cast = handle_operator_cast(node, src.clone());
+                parts.push(operator_cast);
+            },
+            "reference_declarator" => {
+                let reference_declarator = handle_reference_declarator(node, src.clone());
+                push_declarator(&mut parts, reference_declarator);
+            },
+            "placeholder_type_specifier" => {
+                let placeholder_type_specifier = handle_placeholder_type_specifier(node, src.clone());
+                parts.push(placeholder_type_specifier);
+            },
             ";" => parts.push(";".to_string()),
             "," => parts.push(",".to_string()),
             _ => println!("You shouldn't be here (declaration): {}\n", node.grammar_name()),
@@ -431,19 +552,56 @@ fn handle_declaration(root: Node, src: String) -> String {
         result.remove(idx+2);
     }
     if result.contains("**") { result = utils::remove_pointer_spaces(result); }
+    let is_function = root.children(&mut root.walk()).any(|x| x.grammar_name() == "function_declarator" || x.grammar_name() == "operator_cast");
+    result = utils::place_attributes(&attributes, &result, is_function);
     if result.contains("::") { result = utils::remove_object_constructor_space(result); }
     return result;
 }
 
 fn handle_function_definition(root: Node, src: String) -> String {
     let mut result = String::new();
+    let mut attributes = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "ms_declspec_modifier" => {
+                let ms_declspec_modifier = handle_ms_declspec_modifier(node, src.clone());
+                result += format!("{} ", ms_declspec_modifier).as_str();
+            },
+            "ms_call_modifier" => result += format!("{} ", node.utf8_text(src.as_bytes()).unwrap_or("")).as_str(),
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                attributes.push(attribute_declaration);
+            },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                result += format!("{} ", attribute_specifier).as_str();
+            },
+            "virtual" => result += "virtual ",
+            "decltype" => {
+                let decltype = handle_decltype(node, src.clone());
+                result += format!("{} ", decltype).as_str();
+            },
+            "placeholder_type_specifier" => {
+                let placeholder_type_specifier = handle_placeholder_type_specifier(node, src.clone());
+                result += format!("{} ", placeholder_type_specifier).as_str();
+            },
+            "explicit_function_specifier" => {
+                let explicit_function_specifier = handle_explicit_function_specifier(node, src.clone());
+                result += format!("{} ", explicit_function_specifier).as_str();
+            },
+            "operator_cast" => {
+                let operator_cast = handle_operator_cast(node, src.clone());
+                result += format!("{}\n", operator_cast).as_str();
+            },
+            "default_method_clause" | "delete_method_clause" => {
+                let method_clause = handle_method_clause(node, src.clone());
+                result = result.trim_end().to_string();
+                result += format!(" {}", method_clause).as_str();
+            },
             "compound_statement" => {
                 let compound_statement = handle_compound_statement(node, src.clone());
-                result = result.trim_end().to_string();
-                result = utils::remove_reference_spaces(result);
-                result += format!("\n{}\n", compound_statement).as_str();
+                result = utils::remove_reference_spaces(result.trim_end().to_string());
+                result = format!("{}\n", utils::join_block(&result, &compound_statement, utils::BraceKind::Function));
             },
             "primitive_type" => {
                 let primitive_type = handle_primitive_type(node, src.clone());
@@ -480,7 +638,7 @@ fn handle_function_definition(root: Node, src: String) -> String {
                 result += format!("{}\n", parenthesized_declarator).as_str();
             },
             "struct_specifier" => {
-                let struct_specifier = handle_struct_specifier(node.clone(), src.clone());
+                let struct_specifier = handle_struct_specifier(node, src.clone());
                 result += format!("{}\n", struct_specifier).as_str();
             },
             "storage_class_specifier" => {
@@ -497,20 +655,24 @@ fn handle_function_definition(root: Node, src: String) -> String {
             },
             "reference_declarator" => {
                 let reference_declarator = handle_reference_declarator(node, src.clone());
+                if reference_declarator.starts_with("&&") { result = result.trim_end().to_string(); }
                 result += format!("{} ", reference_declarator).as_str();
             },
             "field_initializer_list" => {
                 let field_initializer_list = handle_field_initializer_list(node, src.clone());
-                result += format!("{} ", field_initializer_list).as_str();
-                result = result.trim_end().to_string();
-                result += "\n";
+                if utils::active_c_opts().ctor_initializer_break == utils::CtorInitializerBreak::AfterColon {
+                    result = format!("{} :\n", result.trim_end());
+                }
+                result += format!("{}\n", field_initializer_list).as_str();
             },
             _ => println!("You shouldn't be here (function_definition): {}\n", node.grammar_name()),
         }
     }
     result = utils::remove_reference_spaces(result);
-    result = utils::ensure_space_after_char(&result, '=');
+    // Operator names like `operator==` must stay intact.
+    if !result.contains("operator") { result = utils::ensure_space_after_char(&result, '='); }
     result = result.trim_end().to_string();
+    result = utils::place_attributes(&attributes, &result, true);
     return result;
 }
 
@@ -518,6 +680,9 @@ fn handle_expression_statement(root: Node, src: String) -> String {
     let mut result = "".to_string();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            "co_await_expression" => {
+                result = handle_co_await_expression(node, src.clone());
+            },
             "assignment_expression" => {
                 result = handle_assignment_expression(node, src.clone());
             },
@@ -566,15 +731,16 @@ fn handle_assignment_expression(root: Node, src: String) -> String {
         match node.grammar_name() {
             ";" => parts.push(";".to_string()),
             "=" => parts.push("=".to_string()),
-            "+=" => parts.push("=".to_string()),
-            "-=" => parts.push("=".to_string()),
-            "*=" => parts.push("=".to_string()),
-            "/=" => parts.push("=".to_string()),
-            "%=" => parts.push("=".to_string()),
-            "<<=" => parts.push("=".to_string()),
-            ">>=" => parts.push("=".to_string()),
-            "&=" => parts.push("=".to_string()),
-            "|=" => parts.push("=".to_string()),
+            "+=" => parts.push("+=".to_string()),
+            "-=" => parts.push("-=".to_string()),
+            "*=" => parts.push("*=".to_string()),
+            "/=" => parts.push("/=".to_string()),
+            "%=" => parts.push("%=".to_string()),
+            "<<=" => parts.push("<<=".to_string()),
+            ">>=" => parts.push(">>=".to_string()),
+            "&=" => parts.push("&=".to_string()),
+            "|=" => parts.push("|=".to_string()),
+            "^=" => parts.push("^=".to_string()),
             "assignment_expression" => {
                 let assignment_expression = handle_inner_assignment_expression(node, src.clone());
                 parts.push(assignment_expression);
@@ -704,6 +870,7 @@ fn handle_inner_assignment_expression(root: Node, src: String) -> String {
             "/=" => parts.push("/=".to_string()),
             "%=" => parts.push("%=".to_string()),
             "|=" => parts.push("|=".to_string()),
+            "^=" => parts.push("^=".to_string()),
             "&=" => parts.push("&=".to_string()),
             ">>=" => parts.push(">>=".to_string()),
             "<<=" => parts.push("<<=".to_string()),
@@ -722,6 +889,10 @@ fn handle_inner_compound_statement(root: Node, src: String) -> String {
                 let expression_statement = handle_expression_statement(node, src.clone());
                 parts.push(format!("\t{}", expression_statement));
             },
+            "co_return_statement" | "co_yield_statement" => {
+                let coroutine_statement = handle_coroutine_statement(node, src.clone());
+                parts.push(format!("\t{}", coroutine_statement));
+            },
             "return_statement" => {
                 let return_statement = handle_return_statement(node, src.clone());
                 parts.push(format!("\t{}", return_statement));
@@ -790,6 +961,16 @@ fn handle_inner_compound_statement(root: Node, src: String) -> String {
                 for_range_loop = utils::add_all_leading_tabs(for_range_loop);
                 parts.push(for_range_loop);
             },
+            "static_assert_declaration" => {
+                let mut static_assert_declaration = handle_static_assert_declaration(node, src.clone());
+                static_assert_declaration = utils::add_all_leading_tabs(static_assert_declaration);
+                parts.push(static_assert_declaration);
+            },
+            "attributed_statement" => {
+                let mut attributed_statement = handle_attributed_statement(node, src.clone());
+                attributed_statement = utils::add_all_leading_tabs(attributed_statement);
+                parts.push(attributed_statement);
+            },
             "labeled_statement" => {
                 let labeled_statement = handle_labeled_statement(node, src.clone());
                 parts.push(labeled_statement);
@@ -818,6 +999,11 @@ fn handle_call_expression(root: Node, src: String) -> String {
     let mut result = "".to_string();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
+            // A nested requirement (`requires sizeof(T) > 1;`) parses as a call.
+            "requires_clause" => {
+                let requires_clause = handle_requires_clause(node, src.clone());
+                result += requires_clause.as_str();
+            },
             "call_expression" => {
                 let mut temp = String::new();
                 for subnode in node.children(&mut node.walk()) {
@@ -883,9 +1069,15 @@ fn handle_if_statement(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "if" => parts.push("if".to_string()),
+            "constexpr" => parts.push("constexpr".to_string()),
             "compound_statement" => {
                 let inner_compound_statement = handle_inner_compound_statement(node, src.clone());
-                parts.push(inner_compound_statement);
+                let head = parts.join(" ");
+                parts = vec![utils::join_block(&head, &inner_compound_statement, utils::BraceKind::Control)];
+            },
+            "co_return_statement" => {
+                let coroutine_statement = handle_coroutine_statement(node, src.clone());
+                parts.push(coroutine_statement);
             },
             "return_statement" => {
                 let return_statement = handle_return_statement(node, src.clone());
@@ -908,7 +1100,8 @@ fn handle_if_statement(root: Node, src: String) -> String {
             }
             "else_clause" => {
                 let else_clause = handle_else_clause(node, src.clone());
-                parts.push(else_clause);
+                let head = parts.join(" ");
+                parts = vec![utils::join_continuation(&head, &else_clause, utils::BraceKind::Else)];
             },
             "goto_statement" => {
                 let goto_statement = handle_goto_statement(node, src.clone());
@@ -942,7 +1135,8 @@ fn handle_else_clause(root: Node, src: String) -> String {
         match node.grammar_name() {
             "compound_statement" => {
                 let inner_compound_statement = handle_inner_compound_statement(node, src.clone());
-                pieces.push(inner_compound_statement);
+                let head = pieces.join(" ");
+                pieces = vec![utils::join_block(&head, &inner_compound_statement, utils::BraceKind::Control)];
             }
             "else" => {
                 pieces.push("else".to_string());
@@ -981,7 +1175,7 @@ fn handle_switch_statement(root: Node, src: String) -> String {
             "switch" => result += "switch ",
             "compound_statement" => {
                 let compound_statement = handle_inner_compound_statement(node, src.clone());
-                result += compound_statement.as_str();
+                result = utils::join_block(&result, &compound_statement, utils::BraceKind::Control);
             },
             "parenthesized_expression" => {
                 let parenthesized_expression = handle_parenthesized_expression(node, src.clone());
@@ -1009,6 +1203,22 @@ fn handle_case_statement(root: Node, src: String) -> String {
                 let compound_statement = handle_inner_compound_statement(node, src.clone());
                 result += format!("{}\n", compound_statement).as_str();
             },
+            // GNU case range: `case 1 ... 5:` reads as a pack expansion followed by an error.
+            "parameter_pack_expansion" => {
+                let range_start = node.utf8_text(src.as_bytes()).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ");
+                result += range_start.as_str();
+            },
+            // The other side of the range, or the whole `'a' ...` for char literals.
+            "ERROR" => {
+                let range = node.utf8_text(src.as_bytes()).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ");
+                if !result.ends_with(' ') {
+                    result += " ";
+                }
+                result += range.as_str();
+                if range.ends_with("...") {
+                    result += " ";
+                }
+            },
             "case" => {
                 result += "case ";
             },
@@ -1019,6 +1229,10 @@ fn handle_case_statement(root: Node, src: String) -> String {
                 let declaration = handle_declaration(node, src.clone());
                 result += format!("\t{}\n", declaration).as_str();
             },
+            "co_return_statement" | "co_yield_statement" => {
+                let coroutine_statement = handle_coroutine_statement(node, src.clone());
+                result += format!("\t{}\n", coroutine_statement).as_str();
+            },
             "return_statement" => {
                 let return_statement = handle_return_statement(node, src.clone());
                 result += format!("\t{}\n", return_statement).as_str();
@@ -1027,6 +1241,10 @@ fn handle_case_statement(root: Node, src: String) -> String {
                 let if_statement = handle_if_statement(node, src.clone());
                 result += format!("\t{}\n", if_statement).as_str();
             },
+            "attributed_statement" => {
+                let attributed_statement = handle_attributed_statement(node, src.clone());
+                result += format!("\t{}\n", attributed_statement).as_str();
+            },
             "goto_statement" => {
                 let goto_statement = handle_goto_statement(node, src.clone());
                 result += format!("\t{}\n", goto_statement).as_str();
@@ -1060,7 +1278,7 @@ fn handle_case_statement(root: Node, src: String) -> String {
             },
             ":" => {
                 result = result.trim_end().to_string();
-                result += ": \n"
+                result += ":\n"
             },
             _ => println!("You shouldn't be here (case_statement): {}\n", node.grammar_name()),
         }
@@ -1094,8 +1312,9 @@ fn handle_while_statement(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "compound_statement" => {
-                let compound_statement = handle_compound_statement(node.clone(), src.clone());
-                parts.push(compound_statement);
+                let compound_statement = handle_compound_statement(node, src.clone());
+                let head = parts.join(" ");
+                parts = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Control)];
             },
             "while" => {
                 parts.push("while".to_string());
@@ -1128,6 +1347,14 @@ fn handle_argument_list(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "," => result += ", ",
+            "parameter_pack_expansion" => {
+                let parameter_pack_expansion = handle_parameter_pack_expansion(node, src.clone());
+                result += parameter_pack_expansion.as_str();
+            },
+            "fold_expression" => {
+                let fold_expression = handle_fold_expression(node, src.clone());
+                result += fold_expression.as_str();
+            },
             "(" => result += "(",
             ")" => result += ")",
             "null" => result += "NULL",
@@ -1237,6 +1464,10 @@ fn handle_return_statement(root: Node, src: String) -> String {
                 let compound_statement = handle_compound_statement(node, src.clone());
                 parts.push(compound_statement);
             },
+            "co_await_expression" | "fold_expression" => {
+                let expression = handle_expression(node, src.clone());
+                parts.push(expression);
+            },
             "call_expression" => {
                 let call_expression = handle_call_expression(node, src.clone());
                 parts.push(call_expression);
@@ -1373,6 +1604,41 @@ fn handle_function_declarator(root: Node, src: String) -> String {
         if node.child_count() == 0 {
             result += node.utf8_text(src.as_bytes()).unwrap();
         }
+        else if node.grammar_name() == "destructor_name" {
+            let destructor_name = handle_destructor_name(node, src.clone());
+            result += destructor_name.as_str();
+        }
+        else if node.grammar_name() == "operator_name" {
+            let operator_name = handle_operator_name(node, src.clone());
+            result += operator_name.as_str();
+        }
+        else if node.grammar_name() == "qualified_identifier" {
+            let qualified_identifier = handle_qualified_identifier(node, src.clone());
+            result += qualified_identifier.as_str();
+        }
+        else if node.grammar_name() == "noexcept" {
+            let noexcept = handle_noexcept(node, src.clone());
+            result += format!(" {}", noexcept).as_str();
+        }
+        else if node.grammar_name() == "virtual_specifier" {
+            let virtual_specifier = handle_virtual_specifier(node, src.clone());
+            result += format!(" {}", virtual_specifier).as_str();
+        }
+        else if node.grammar_name() == "requires_clause" {
+            let requires_clause = handle_requires_clause(node, src.clone());
+            result += format!(" {}", requires_clause).as_str();
+        }
+        else if node.grammar_name() == "ref_qualifier" {
+            result += format!(" {}", node.utf8_text(src.as_bytes()).unwrap_or("")).as_str();
+        }
+        else if node.grammar_name() == "attribute_specifier" {
+            let attribute_specifier = handle_attribute_specifier(node, src.clone());
+            result += format!(" {}", attribute_specifier).as_str();
+        }
+        else if node.grammar_name() == "attribute_declaration" {
+            let attribute_declaration = handle_attribute_declaration(node, src.clone());
+            result += format!(" {}", attribute_declaration).as_str();
+        }
         else {
             let mut temp = String::new();
             for subnode in node.children(&mut node.walk()) {
@@ -1389,6 +1655,10 @@ fn handle_function_declarator(root: Node, src: String) -> String {
                         let pointer_declarator = handle_pointer_declarator(subnode, src.clone());
                         temp += pointer_declarator.as_str();
                     },
+                    "variadic_parameter_declaration" => {
+                        let variadic_parameter_declaration = handle_variadic_parameter_declaration(subnode, src.clone());
+                        temp += variadic_parameter_declaration.as_str();
+                    },
                     "parameter_declaration" => {
                         let mut parameter_declaration = handle_parameter_declaration(subnode, src.clone());
                         parameter_declaration = utils::remove_pointer_spaces(parameter_declaration);
@@ -1456,6 +1726,14 @@ fn handle_pointer_declarator(root: Node, src: String) -> String {
                 let reference_declarator = handle_reference_declarator(node, src.clone());
                 result += format!(" {}", reference_declarator).as_str();
             },
+            "type_qualifier" => {
+                let type_qualifier = handle_type_qualifier(node, src.clone());
+                result += type_qualifier.as_str();
+            },
+            "ms_pointer_modifier" => {
+                // `__restrict`, `__uptr` and `__sptr`
+                result += node.utf8_text(src.as_bytes()).unwrap();
+            },
             _ => println!("You shouldn't be here (pointer_declarator): {}\n", node.kind()),
         }
     }
@@ -1509,6 +1787,7 @@ fn handle_nested_inner_compound_statement(root: Node, src: String) -> String {
 
 fn handle_binary_expression(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
+    if is_kernel_launch(root) { return handle_kernel_launch(root, src.clone()); }
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "identifier" => {
@@ -1593,6 +1872,7 @@ fn handle_binary_expression(root: Node, src: String) -> String {
             "<=" => parts.push("<=".to_string()),
             "!=" => parts.push("!=".to_string()),
             "==" => parts.push("==".to_string()),
+            "<=>" => parts.push("<=>".to_string()),
             ">>" => parts.push(">>".to_string()),
             "<<" => parts.push("<<".to_string()),
             "|" => parts.push("|".to_string()),
@@ -1755,6 +2035,11 @@ fn handle_parenthesized_expression(root: Node, src: String) -> String {
                 let pointer_expression = handle_pointer_expression(node, src.clone());
                 result += pointer_expression.as_str();
             },
+            // GNU statement expression: `({ ... })`
+            "compound_statement" => {
+                let compound_statement = handle_compound_statement(node, src.clone());
+                result += compound_statement.as_str();
+            },
             "(" => result += "(",
             ")" => result += ")",
             _ => println!("You shouldn't be here (parenthesized_expression): {}\n", node.grammar_name()),
@@ -1799,6 +2084,7 @@ fn handle_pointer_expression(root: Node, src: String) -> String {
                 let cast_expression = handle_cast_expression(node, src.clone());
                 result += cast_expression.as_str();
             },
+            "this" => result += "this",
             "*" => result += "* ",
             "&" => result += "&",
             ";" => result += ";",
@@ -1847,6 +2133,10 @@ fn handle_parameter_declaration(root: Node, src: String) -> String {
                 let type_qualifier = handle_type_qualifier(node, src.clone());
                 parts.push(type_qualifier);
             },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                parts.push(attribute_declaration);
+            },
             "struct_specifier" => {
                 let struct_specifier = handle_struct_specifier(node, src.clone());
                 parts.push(struct_specifier);
@@ -1865,11 +2155,23 @@ fn handle_parameter_declaration(root: Node, src: String) -> String {
             },
             "abstract_reference_declarator" => {
                 let abstract_reference_declarator = handle_abstract_reference_declarator(node, src.clone());
-                parts.push(abstract_reference_declarator);
+                // Attach `&&` to the type, the same way `&` ends up as `const Base&`.
+                match parts.last_mut() {
+                    Some(last) => *last += abstract_reference_declarator.as_str(),
+                    None => parts.push(abstract_reference_declarator),
+                }
+            },
+            "decltype" => {
+                let decltype = handle_decltype(node, src.clone());
+                parts.push(decltype);
+            },
+            "placeholder_type_specifier" => {
+                let placeholder_type_specifier = handle_placeholder_type_specifier(node, src.clone());
+                parts.push(placeholder_type_specifier);
             },
             "reference_declarator" => {
                 let reference_declarator = handle_reference_declarator(node, src.clone());
-                parts.push(reference_declarator);
+                push_declarator(&mut parts, reference_declarator);
             },
             _ => println!("You shouldn't be here (parameter_declaration): {}\n", node.grammar_name()),
         }
@@ -2065,6 +2367,10 @@ fn handle_init_declarator(root: Node, src: String) -> String {
                 let call_expression = handle_call_expression(node, src.clone());
                 parts.push(call_expression);
             },
+            "compound_literal_expression" => {
+                let compound_literal_expression = handle_compound_literal_expression(node, src.clone());
+                parts.push(compound_literal_expression);
+            },
             "field_expression" => {
                 let field_expression = handle_field_expression(node, src.clone());
                 parts.push(field_expression);
@@ -2130,10 +2436,24 @@ fn handle_init_declarator(root: Node, src: String) -> String {
                 lambda_expression = utils::add_all_leading_tabs(lambda_expression);
                 parts.push(lambda_expression);
             },
+            "function_declarator" => {
+                // a defaulted or deleted free function: `auto operator<=>(const A&, const A&) = default;`
+                let function_declarator = handle_function_declarator(node, src.clone());
+                parts.push(function_declarator);
+            },
+            "delete_expression" if node.utf8_text(src.as_bytes()).unwrap_or("") == "delete" => parts.push("delete".to_string()),
+            "reference_declarator" => {
+                let reference_declarator = handle_reference_declarator(node, src.clone());
+                parts.push(reference_declarator);
+            },
             "=" => parts.push("=".to_string()),
             "false" => parts.push("false".to_string()),
             "true" => parts.push("true".to_string()),
             "null" => parts.push("NULL".to_string()),
+            "co_await_expression" | "fold_expression" | "decltype" => {
+                let expression = handle_expression(node, src.clone());
+                parts.push(expression);
+            },
             _ => println!("You shouldn't be here (init_declarator): {}\n", node.grammar_name()),
         }
     }
@@ -2179,7 +2499,7 @@ fn handle_type_descriptor(root: Node, src: String) -> String {
             },
             "abstract_reference_declarator" => {
                 let abstract_reference_declarator = handle_abstract_reference_declarator(node, src.clone());
-                result += format!("{} ", abstract_reference_declarator).as_str();
+                result = format!("{}{}", result.trim_end(), abstract_reference_declarator);
             },
             _ => println!("You shouldn't be here (type_descriptor): {}\n", node.grammar_name()),
         }
@@ -2234,6 +2554,14 @@ fn handle_initializer_list(root: Node, src: String) -> String {
             "{" => result += "{",
             "}" => result += "}",
             "," => result += ", ",
+            "initializer_pair" => {
+                let initializer_pair = handle_initializer_pair(node, src.clone());
+                result += initializer_pair.as_str();
+            },
+            "parameter_pack_expansion" => {
+                let parameter_pack_expansion = handle_parameter_pack_expansion(node, src.clone());
+                result += parameter_pack_expansion.as_str();
+            },
             _ => println!("You shouldn't be here (initializer_list): {}\n", node.grammar_name()),
         }
     }
@@ -2269,6 +2597,16 @@ fn handle_type_qualifier(root: Node, src: String) -> String {
             "const" => {
                 result = "const".to_string();
             },
+            "volatile" => result = "volatile".to_string(),
+            "restrict" => result = "restrict".to_string(),
+            "__restrict__" => result = "__restrict__".to_string(),
+            "mutable" => result = "mutable".to_string(),
+            "consteval" => result = "consteval".to_string(),
+            "constinit" => result = "constinit".to_string(),
+            "constexpr" => result = "constexpr".to_string(),
+            "alignas_qualifier" => {
+                result = handle_alignas_qualifier(node, src.clone());
+            },
             _ => println!("You shouldn't be here (type_qualifier): {}\n", node.grammar_name()),
         }
     }
@@ -2281,6 +2619,10 @@ fn handle_abstract_pointer_declarator(root: Node, src: String) -> String {
         match node.grammar_name() {
             "abstract_pointer_declarator" => result = "**".to_string(),
             "*" => result = "*".to_string(),
+            "abstract_function_declarator" => {
+                let abstract_function_declarator = handle_abstract_function_declarator(node, src.clone());
+                result += abstract_function_declarator.as_str();
+            },
             _ => println!("You shouldn't be here (abstract_pointer_declarator): {}\n", node.grammar_name()),
         }
     }
@@ -2504,7 +2846,8 @@ fn handle_for_statement(root: Node, src: String) -> String {
             "compound_statement" => {
                 reached_compound = true;
                 let compound_statement = handle_compound_statement(node, src.clone());
-                vec.push(compound_statement);
+                let head = vec.join(" ");
+                vec = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Control)];
             },
             "if_statement" => {
                 let if_statement = handle_if_statement(node, src.clone());
@@ -2556,15 +2899,30 @@ fn handle_struct_specifier(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += format!("{} ", identifier).as_str();
             },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                if result.ends_with("}") { result += " "; }
+                result += format!("{} ", attribute_specifier).as_str();
+            },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                result += format!("{} ", attribute_declaration).as_str();
+            },
+            "alignas_qualifier" => {
+                let alignas_qualifier = handle_alignas_qualifier(node, src.clone());
+                result += format!("{} ", alignas_qualifier).as_str();
+            },
             "field_declaration_list" => {
                 let field_declaration_list = handle_field_declaration_list(node, src.clone());
-                // This adds a newline before the curly brace.
-                if result.contains("\n") {
-                    result += field_declaration_list.as_str();
-                }
-                else {
-                    result += format!("\n{}", field_declaration_list).as_str();
-                }
+                result = utils::join_block(&result, &field_declaration_list, utils::BraceKind::Class);
+            },
+            "virtual_specifier" => {
+                let virtual_specifier = handle_virtual_specifier(node, src.clone());
+                result += format!("{} ", virtual_specifier).as_str();
+            },
+            "base_class_clause" => {
+                let base_class_clause = handle_base_class_clause(node, src.clone());
+                result += format!("{} ", base_class_clause).as_str();
             },
             "struct" => result += "struct ",
             _ => println!("You shouldn't be here (struct_specifier): {}\n", node.grammar_name()),
@@ -2620,20 +2978,53 @@ fn handle_type_definition(root: Node, src: String) -> String {
 fn handle_field_declaration_list(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
     let mut temp = String::new();
+    if let Some(order) = utils::active_c_opts().member_order {
+        for problem in check_member_order(root, order, src.clone()) { eprintln!("{}", problem); }
+    }
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "field_declaration" => {
                 let field_declaration = handle_field_declaration(node, src.clone());
                 parts.push(format!("\t{}", field_declaration));
             },
+            "static_assert_declaration" => {
+                let static_assert_declaration = handle_static_assert_declaration(node, src.clone());
+                parts.push(format!("\t{}", static_assert_declaration));
+            },
+            "declaration" => {
+                let declaration = handle_declaration(node, src.clone());
+                parts.push(format!("\t{}", declaration));
+            },
+            "friend_declaration" => {
+                let mut friend_declaration = handle_friend_declaration(node, src.clone());
+                friend_declaration = utils::add_all_leading_tabs(friend_declaration);
+                parts.push(friend_declaration);
+            },
             "comment" => {
                 let comment = handle_comment(node, src.clone());
                 parts.push(format!("\t{}", comment));
             },
             "access_specifier" => {
                 let access_specifier = handle_access_specifier(node, src.clone());
+                if parts.last().is_some_and(|x| x != "{") { parts.push(String::new()); }
                 parts.push(access_specifier);
             },
+            "alias_declaration" => {
+                let alias_declaration = handle_alias_declaration(node, src.clone());
+                parts.push(format!("\t{}", alias_declaration));
+            },
+            "using_declaration" => {
+                let using_declaration = handle_using_declaration(node, src.clone());
+                parts.push(format!("\t{}", using_declaration));
+            },
+            "type_definition" => {
+                let type_definition = handle_type_definition(node, src.clone());
+                parts.push(format!("\t{}", type_definition));
+            },
+            "template_declaration" => {
+                let template_declaration = handle_template_declaration(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(template_declaration));
+            },
             "function_definition" => {
                 let mut function_definition = handle_function_definition(node, src.clone());
                 function_definition = utils::add_all_leading_tabs(function_definition);
@@ -2687,12 +3078,62 @@ fn handle_field_declaration(root: Node, src: String) -> String {
                 let storage_class_specifier = handle_storage_class_specifier(node, src.clone());
                 result += format!("{} ", storage_class_specifier).as_str();
             },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                result += format!("{} ", attribute_declaration).as_str();
+            },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                result += format!("{} ", attribute_specifier).as_str();
+            },
+            "type_qualifier" => {
+                let type_qualifier = handle_type_qualifier(node, src.clone());
+                result += format!("{} ", type_qualifier).as_str();
+            },
+            "virtual" => result += "virtual ",
+            "reference_declarator" => {
+                let reference_declarator = handle_reference_declarator(node, src.clone());
+                if reference_declarator.starts_with("&&") { result = result.trim_end().to_string(); }
+                result += format!("{} ", reference_declarator).as_str();
+            },
+            // A nested type: `struct Inner { ... };`
+            "struct_specifier" => {
+                let struct_specifier = handle_struct_specifier(node, src.clone());
+                result += format!("{} ", struct_specifier.trim_end()).as_str();
+            },
+            "class_specifier" => {
+                let class_specifier = handle_class_specifier(node, src.clone());
+                result += format!("{} ", class_specifier.trim_end()).as_str();
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                result += format!("{} ", enum_specifier.trim_end()).as_str();
+            },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(node, src.clone());
+                result += format!("{} ", union_specifier.trim_end()).as_str();
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                result += format!("{} ", template_type).as_str();
+            },
+            "sized_type_specifier" => {
+                let sized_type_specifier = handle_sized_type_specifier(node, src.clone());
+                result += format!("{} ", sized_type_specifier).as_str();
+            },
+            // `= 0` on a pure virtual function, or a default member initializer.
+            "=" => result += "= ",
+            "number_literal" => {
+                let number_literal = handle_number_literal(node, src.clone());
+                result += format!("{} ", number_literal).as_str();
+            },
             ";" => result += ";",
             "," => result += ", ",
             _ => println!("You shouldn't be here (field_declaration): {}\n", node.grammar_name()),
         }
     }
     result = utils::remove_unnecessary_spaces(&result);
+    result = utils::remove_reference_spaces(result);
     return result;
 }
 
@@ -2757,17 +3198,11 @@ fn handle_enum_specifier(root: Node, src: String) -> String {
             },
             "enumerator_list" => {
                 let enumerator_list = handle_enumerator_list(node, src.clone());
-                result += format!("\n{}", enumerator_list).as_str();
+                result = utils::join_block(&result, &enumerator_list, utils::BraceKind::Enum);
             },
             "field_declaration_list" => {
                 let field_declaration_list = handle_field_declaration_list(node, src.clone());
-                // This adds a newline before the curly brace.
-                if result.contains("\n") {
-                    result += field_declaration_list.as_str();
-                }
-                else {
-                    result += format!("\n{}", field_declaration_list).as_str();
-                }
+                result = utils::join_block(&result, &field_declaration_list, utils::BraceKind::Enum);
             },
             "enum" => result += "enum ",
             "class" => result += "class ",
@@ -2785,15 +3220,21 @@ fn handle_union_specifier(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += format!("{} ", identifier).as_str();
             },
+            "attribute_specifier" => {
+                let attribute_specifier = handle_attribute_specifier(node, src.clone());
+                result += format!("{} ", attribute_specifier).as_str();
+            },
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                result += format!("{} ", attribute_declaration).as_str();
+            },
+            "alignas_qualifier" => {
+                let alignas_qualifier = handle_alignas_qualifier(node, src.clone());
+                result += format!("{} ", alignas_qualifier).as_str();
+            },
             "field_declaration_list" => {
                 let field_declaration_list = handle_field_declaration_list(node, src.clone());
-                // This adds a newline before the curly brace.
-                if result.contains("\n") {
-                    result += field_declaration_list.as_str();
-                }
-                else {
-                    result += format!("\n{}", field_declaration_list).as_str();
-                }
+                result = utils::join_block(&result, &field_declaration_list, utils::BraceKind::Union);
             },
             "union" => result += "union ",
             _ => println!("You shouldn't be here (union_specifier): {}\n", node.grammar_name()),
@@ -2835,6 +3276,8 @@ fn handle_storage_class_specifier(root: Node, src: String) -> String {
         match node.grammar_name() {
             "static" => result += "static ",
             "inline" => result += "inline ",
+            "extern" => result += "extern ",
+            "thread_local" => result += "thread_local ",
             _ => println!("You shouldn't be here (storage_class_specifier): {}\n", node.grammar_name()),
         }
     }
@@ -2845,6 +3288,7 @@ fn handle_storage_class_specifier(root: Node, src: String) -> String {
 
 fn handle_preproc_ifdef(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
+    let mut last_linkage = false;
     let mut temp = String::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
@@ -2854,6 +3298,15 @@ fn handle_preproc_ifdef(root: Node, src: String) -> String {
             "#ifdef" => {
                 temp += "#ifdef ";
             },
+            // A guard like `#ifdef __cplusplus` opening an `extern "C"` block has its
+            // `#endif` inside the block, so this one is missing.
+            "#endif" if node.is_missing() => (),
+            "linkage_specification" => {
+                let linkage_specification = handle_linkage_specification(node, src.clone());
+                parts.push(linkage_specification);
+                last_linkage = true;
+            },
+            "#endif" if last_linkage => parts.push("#endif".to_string()),
             "#endif" => {
                 parts.push("#endif".to_string());
             },
@@ -3065,11 +3518,11 @@ fn handle_do_statement(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "do" => result += "do ",
-            "while" => result += "while ",
+            "while" => result += " while ",
             ";" => result += ";",
             "compound_statement" => {
                 let compound_statement = handle_compound_statement(node, src.clone());
-                result += compound_statement.as_str();
+                result = utils::join_block(&result, &compound_statement, utils::BraceKind::Control);
             },
             "parenthesized_expression" => {
                 let parenthesized_expression = handle_parenthesized_expression(node, src.clone());
@@ -3153,7 +3606,8 @@ fn handle_for_range_loop(root: Node, src: String) -> String {
             "compound_statement" => {
                 reached_compound = true;
                 let compound_statement = handle_compound_statement(node, src.clone());
-                vec.push(compound_statement);
+                let head = vec.join(" ");
+                vec = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Control)];
             },
             "if_statement" => {
                 let if_statement = handle_if_statement(node, src.clone());
@@ -3214,7 +3668,10 @@ fn handle_for_range_loop(root: Node, src: String) -> String {
                 let pointer_declarator = handle_pointer_declarator(node, src.clone());
                 temp += format!("{} ", pointer_declarator).as_str();
             },
-            "placeholder_type_specifier" => temp += "auto",
+            "placeholder_type_specifier" => {
+                let placeholder_type_specifier = handle_placeholder_type_specifier(node, src.clone());
+                temp += placeholder_type_specifier.as_str();
+            },
             ":" => temp += ": ",
             ";" => temp += "; ",
             "(" => temp += "(",
@@ -3253,6 +3710,18 @@ fn handle_qualified_identifier(root: Node, src: String) -> String {
                 let template_function = handle_template_function(node, src.clone());
                 result += template_function.as_str();
             },
+            "destructor_name" => {
+                let destructor_name = handle_destructor_name(node, src.clone());
+                result += destructor_name.as_str();
+            },
+            "operator_name" => {
+                let operator_name = handle_operator_name(node, src.clone());
+                result += operator_name.as_str();
+            },
+            "operator_cast" => {
+                let operator_cast = handle_operator_cast(node, src.clone());
+                result += operator_cast.as_str();
+            },
             "::" => result += "::",
             _ => println!("You shouldn't be here (qualified_identifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
@@ -3309,8 +3778,18 @@ fn handle_template_argument_list(root: Node, src: String) -> String {
                 result += type_descriptor.as_str();
             },
             "<" => result += "<",
+            // `vector<vector<int>>` or, for C++03, `vector<vector<int> >`.
+            ">" if result.ends_with(">") && utils::active_c_opts().spaced_angle_brackets => result += " >",
             ">" => result += ">",
+            "number_literal" | "identifier" | "qualified_identifier" | "true" | "false" | "binary_expression" | "sizeof_expression" | "call_expression" | "template_function" => {
+                let expression = handle_expression(node, src.clone());
+                result += expression.as_str();
+            },
             "," => result += ", ",
+            "parameter_pack_expansion" => {
+                let parameter_pack_expansion = handle_parameter_pack_expansion(node, src.clone());
+                result += parameter_pack_expansion.as_str();
+            },
             _ => println!("You shouldn't be here (template_argument_list): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3387,18 +3866,19 @@ fn handle_subscript_argument_list(root: Node, src: String) -> String {
 }
 
 fn handle_field_initializer_list(root: Node, src: String) -> String {
-    let mut result = String::new();
+    let mut initializers = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "field_initializer" => {
                 let field_initializer = handle_field_initializer(node, src.clone());
-                result += field_initializer.as_str();
+                initializers.push(field_initializer);
             },
-            ":" => result += "\t: ",
-            "," => result += ", ",
+            ":" => (),
+            "," => (),
             _ => println!("You shouldn't be here (field_initializer_list): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
+    let result = utils::layout_ctor_initializers(&initializers);
     return result;
 }
 
@@ -3410,6 +3890,19 @@ fn handle_field_initializer(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += identifier.as_str();
             },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                result += qualified_identifier.as_str();
+            },
+            "template_method" | "template_type" => {
+                let template_type = node.utf8_text(src.as_bytes()).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ");
+                result += template_type.as_str();
+            },
+            "initializer_list" => {
+                let initializer_list = handle_initializer_list(node, src.clone());
+                result += initializer_list.as_str();
+            },
+            "..." => result += "...",
             "argument_list" => {
                 let argument_list = handle_argument_list(node, src.clone());
                 result += argument_list.as_str();
@@ -3420,6 +3913,15 @@ fn handle_field_initializer(root: Node, src: String) -> String {
     return result;
 }
 
+// `&&` is kept on the type (`auto&& x`, `T&& x`). A lone `&` is moved there later
+// by utils::remove_reference_spaces, which can't tell `&&` from a logical and.
+fn push_declarator(parts: &mut Vec<String>, declarator: String) {
+    match parts.last_mut() {
+        Some(last) if declarator.starts_with("&&") => *last += declarator.as_str(),
+        _ => parts.push(declarator),
+    }
+}
+
 fn handle_reference_declarator(root: Node, src: String) -> String {
     let mut result = String::new();
     for node in root.children(&mut root.walk()) {
@@ -3437,6 +3939,7 @@ fn handle_reference_declarator(root: Node, src: String) -> String {
                 result += structured_binding_declarator.as_str();
             },
             "&" => result += "& ",
+            "&&" => result += "&& ",
             _ => println!("You shouldn't be here (reference_declarator): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3448,6 +3951,7 @@ fn handle_abstract_reference_declarator(root: Node, src: String) -> String {
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
             "&" => result += "&",
+            "&&" => result += "&&",
             _ => println!("You shouldn't be here (abstract_reference_declarator): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3460,11 +3964,19 @@ fn handle_class_specifier(root: Node, src: String) -> String {
         match node.grammar_name() {
             "identifier" => {
                 let identifier = handle_identifier(node, src.clone());
-                result += format!("{}\n", identifier).as_str();
+                result += format!("{} ", identifier).as_str();
             },
             "field_declaration_list" => {
                 let field_declaration_list = handle_field_declaration_list(node, src.clone());
-                result += field_declaration_list.as_str();
+                result = utils::join_block(&result, &field_declaration_list, utils::BraceKind::Class);
+            },
+            "virtual_specifier" => {
+                let virtual_specifier = handle_virtual_specifier(node, src.clone());
+                result += format!("{} ", virtual_specifier).as_str();
+            },
+            "base_class_clause" => {
+                let base_class_clause = handle_base_class_clause(node, src.clone());
+                result += format!("{} ", base_class_clause).as_str();
             },
             "class" => result += "class ",
             _ => println!("You shouldn't be here (class_specifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
@@ -3479,13 +3991,43 @@ fn handle_template_declaration(root: Node, src: String) -> String {
         match node.grammar_name() {
             "template_parameter_list" => {
                 let template_parameter_list = handle_template_parameter_list(node, src.clone());
-                result += format!("{}\n", template_parameter_list).as_str();
+                result += template_parameter_list.as_str();
             },
             "function_definition" => {
                 let function_definition = handle_function_definition(node, src.clone());
-                result += function_definition.as_str();
+                result = utils::join_template_head(&result, &function_definition);
             }
-            "template" => result += "template ",
+            "requires_clause" => {
+                let requires_clause = handle_requires_clause(node, src.clone());
+                result = utils::join_requires_clause(&result, &requires_clause);
+            },
+            "concept_definition" => {
+                let concept_definition = handle_concept_definition(node, src.clone());
+                result = utils::join_template_head(&result, &concept_definition);
+            },
+            "declaration" => {
+                let declaration = handle_declaration(node, src.clone());
+                result = utils::join_template_head(&result, &declaration);
+            },
+            "struct_specifier" => {
+                let struct_specifier = handle_struct_specifier(node, src.clone());
+                result = utils::join_template_head(&result, &format!("{};", struct_specifier.trim_end()));
+            },
+            "class_specifier" => {
+                let class_specifier = handle_class_specifier(node, src.clone());
+                result = utils::join_template_head(&result, &format!("{};", class_specifier.trim_end()));
+            },
+            "alias_declaration" => {
+                let alias_declaration = handle_alias_declaration(node, src.clone());
+                result = utils::join_template_head(&result, &alias_declaration);
+            },
+            "template_declaration" => {
+                let template_declaration = handle_template_declaration(node, src.clone());
+                result = utils::join_template_head(&result, &template_declaration);
+            },
+            ";" => (),
+            "template" if utils::active_c_opts().space_after_template => result += "template ",
+            "template" => result += "template",
             _ => println!("You shouldn't be here (template_declaration): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3500,6 +4042,11 @@ fn handle_template_parameter_list(root: Node, src: String) -> String {
                 let type_parameter_declaration = handle_type_parameter_declaration(node, src.clone());
                 result += type_parameter_declaration.as_str();
             },
+            "parameter_declaration" | "optional_parameter_declaration" | "optional_type_parameter_declaration" | "variadic_type_parameter_declaration" | "variadic_parameter_declaration" => {
+                let template_parameter = handle_template_parameter(node, src.clone());
+                result += template_parameter.as_str();
+            },
+            "," => result += ", ",
             "<" => result += "<",
             ">" => result += ">",
             _ => println!("You shouldn't be here (template_parameter_list): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
@@ -3517,6 +4064,7 @@ fn handle_type_parameter_declaration(root: Node, src: String) -> String {
                 result += identifier.as_str();
             }
             "typename" => result += "typename ",
+            "class" => result += "class ",
             _ => println!("You should't be here (type_parameter_declaration): {} : {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3529,6 +4077,7 @@ fn handle_access_specifier(root: Node, src: String) -> String {
         match node.grammar_name() {
             "public" => result += "public:",
             "private" => result += "private:",
+            "protected" => result += "protected:",
             _ => println!("You shouldn't be here (access_specifier): {} : {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3539,8 +4088,23 @@ fn handle_gnu_asm_expression(root: Node, src: String) -> String {
     let mut result = String::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
-            "gnu_asm_expression" => {
-                result += node.utf8_text(src.as_bytes()).unwrap();
+            "asm" => result += "asm",
+            "__asm__" => result += "__asm__",
+            "__asm" => result += "__asm",
+            "gnu_asm_qualifier" => result += format!(" {}", node.utf8_text(src.as_bytes()).unwrap_or("")).as_str(),
+            "(" => result += "(",
+            ")" => result += ")",
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                result += string_literal.as_str();
+            },
+            "concatenated_string" => {
+                let concatenated_string = handle_concatenated_string(node, src.clone());
+                result += concatenated_string.as_str();
+            },
+            "gnu_asm_output_operand_list" | "gnu_asm_input_operand_list" | "gnu_asm_clobber_list" | "gnu_asm_goto_list" => {
+                let operand_list = handle_gnu_asm_operand_list(node, src.clone());
+                result += format!(" {}", operand_list).as_str();
             },
             ";" => result += ";",
             _ => println!("Gnu asm expression: {} : {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
@@ -3549,22 +4113,52 @@ fn handle_gnu_asm_expression(root: Node, src: String) -> String {
     return result;
 }
 
-fn handle_try_statement(root: Node, src: String) -> String {
-    let mut parts = Vec::<String>::new();
+// One `: ...` section of an asm statement: outputs, inputs, clobbers or goto labels.
+fn handle_gnu_asm_operand_list(root: Node, src: String) -> String {
+    let mut operands = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
-            "try" => parts.push("try".to_string()),
-            "compound_statement" => {
-                let compound_statement = handle_compound_statement(node, src.clone());
-                parts.push(compound_statement);
-            }
-            "catch_clause" => {
-                let catch_clause = handle_catch_clause(node, src.clone());
-                parts.push(catch_clause);
-            }
-            _ => println!("You shouldn't be here (try_statement): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
-        }
-    }
+            ":" | "," => (),
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                operands.push(string_literal);
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                operands.push(identifier);
+            },
+            "gnu_asm_output_operand" | "gnu_asm_input_operand" => {
+                // `[name] "constraint" (expression)`
+                let text = node.utf8_text(src.as_bytes()).unwrap_or("");
+                let operand = text.split_whitespace().collect::<Vec<&str>>().join(" ").replace("\" (", "\"(");
+                operands.push(operand);
+            },
+            _ => println!("Gnu asm operand list: {} : {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    if operands.is_empty() { return ":".to_string(); }
+    let result = format!(": {}", operands.join(", "));
+    return result;
+}
+
+fn handle_try_statement(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "try" => parts.push("try".to_string()),
+            "compound_statement" => {
+                let compound_statement = handle_compound_statement(node, src.clone());
+                let head = parts.join(" ");
+                parts = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Control)];
+            }
+            "catch_clause" => {
+                let catch_clause = handle_catch_clause(node, src.clone());
+                let head = parts.join(" ");
+                parts = vec![utils::join_continuation(&head, &catch_clause, utils::BraceKind::Catch)];
+            }
+            _ => println!("You shouldn't be here (try_statement): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
     let result = parts.join(" ");
     return result;
 }
@@ -3580,7 +4174,8 @@ fn handle_catch_clause(root: Node, src: String) -> String {
             },
             "compound_statement" => {
                 let compound_statement = handle_compound_statement(node, src.clone());
-                parts.push(compound_statement);
+                let head = parts.join(" ");
+                parts = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Control)];
             },
             _ => println!("You shouldn't be here (catch_clause): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
@@ -3599,11 +4194,15 @@ fn handle_lambda_expression(root: Node, src: String) -> String {
             },
             "abstract_function_declarator" => {
                 let abstract_function_declarator = handle_abstract_function_declarator(node, src.clone());
-                parts.push(abstract_function_declarator);
+                match parts.last_mut() {
+                    Some(capture) if capture.ends_with(']') => *capture += abstract_function_declarator.as_str(),
+                    _ => parts.push(abstract_function_declarator),
+                }
             },
             "compound_statement" => {
                 let compound_statement = handle_compound_statement(node, src.clone());
-                parts.push(compound_statement);
+                let head = parts.join(" ");
+                parts = vec![utils::join_block(&head, &compound_statement, utils::BraceKind::Lambda)];
             },
             "lambda_expression" => {
                 let mut lambda_expression = handle_lambda_expression(node, src.clone());
@@ -3613,9 +4212,7 @@ fn handle_lambda_expression(root: Node, src: String) -> String {
             _ => println!("You shouldn't be here (lambda_expression): {} : {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
-    let mut result = parts.join(" ");
-    // This function happens to work here too
-    result = utils::remove_object_constructor_space(result);
+    let result = parts.join(" ");
     return result;
 }
 
@@ -3627,7 +4224,17 @@ fn handle_lambda_capture_specifier(root: Node, src: String) -> String {
                 let identifier = handle_identifier(node, src.clone());
                 result += identifier.as_str();
             },
-            "lambda_default_capture" => result += "=",
+            "lambda_default_capture" => result += node.utf8_text(src.as_bytes()).unwrap_or("="),
+            "lambda_capture_initializer" => {
+                let lambda_capture_initializer = handle_lambda_capture_initializer(node, src.clone());
+                result += lambda_capture_initializer.as_str();
+            },
+            "parameter_pack_expansion" => {
+                let parameter_pack_expansion = handle_parameter_pack_expansion(node, src.clone());
+                result += parameter_pack_expansion.as_str();
+            },
+            // A pack capture after `this` (`[this, ...args]`) does not parse; keep it as written.
+            "ERROR" => result += node.utf8_text(src.as_bytes()).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ").as_str(),
             "this" => result += "this",
             "&" => result += "&",
             "*" => result += "*",
@@ -3640,6 +4247,27 @@ fn handle_lambda_capture_specifier(root: Node, src: String) -> String {
     return result;
 }
 
+// An init-capture: `x = std::move(y)`, `&r = z` or `...xs = std::move(args)`.
+fn handle_lambda_capture_initializer(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "&" => result += "&",
+            "..." => result += "...",
+            "=" => result += " = ",
+            _ => {
+                let expression = handle_expression(node, src.clone());
+                result += expression.as_str();
+            },
+        }
+    }
+    return result;
+}
+
 fn handle_abstract_function_declarator(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
     for node in root.children(&mut root.walk()) {
@@ -3648,6 +4276,19 @@ fn handle_abstract_function_declarator(root: Node, src: String) -> String {
                 let parameter_list = handle_parameter_list(node, src.clone());
                 parts.push(parameter_list);
             }
+            "type_qualifier" => {
+                let type_qualifier = handle_type_qualifier(node, src.clone());
+                parts.push(type_qualifier);
+            },
+            "noexcept" => {
+                let noexcept = handle_noexcept(node, src.clone());
+                parts.push(noexcept);
+            },
+            "trailing_return_type" => {
+                let trailing_return_type = handle_trailing_return_type(node, src.clone());
+                parts.push(trailing_return_type);
+            },
+            "ref_qualifier" => parts.push(node.utf8_text(src.as_bytes()).unwrap_or("").to_string()),
             _ => println!("You shouldn't be here (abstract_function_declarator): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3789,32 +4430,88 @@ fn handle_new_expression(root: Node, src: String) -> String {
 }
 
 fn handle_namespace_definition(root: Node, src: String) -> String {
-    let mut parts = Vec::<String>::new();
-    let mut temp = String::new();
+    let mut head = String::new();
+    let mut names = Vec::<String>::new();
+    let mut body = String::new();
     for node in root.children(&mut root.walk()) {
         match node.grammar_name() {
-            "namespace" => temp += "namespace ",
+            "inline" => head += "inline ",
+            "namespace" => head += "namespace",
             "identifier" => {
                 let identifier = handle_identifier(node, src.clone());
-                temp += identifier.as_str();
-                parts.push(temp);
-                temp = "".to_string();
+                names.push(identifier);
+            },
+            "nested_namespace_specifier" => {
+                let nested_namespace_specifier = handle_nested_namespace_specifier(node, src.clone());
+                names.push(nested_namespace_specifier);
             },
             "declaration_list" => {
-                let declaration_list = handle_declaration_list(node, src.clone());
-                parts.push(declaration_list);
-            }
+                let mut declaration_list = node;
+                // `namespace a { namespace b { ... } }` becomes `namespace a::b { ... }`.
+                while utils::active_c_opts().compact_namespaces && !head.starts_with("inline") && !names.is_empty() {
+                    let Some(inner) = get_compactable_namespace(declaration_list) else { break };
+                    let Some(inner_name) = inner.child_by_field_name("name") else { break };
+                    names.push(inner_name.utf8_text(src.as_bytes()).unwrap_or("").to_string());
+                    let Some(inner_body) = inner.child_by_field_name("body") else { break };
+                    declaration_list = inner_body;
+                }
+                body = handle_declaration_list(declaration_list, src.clone());
+            },
             _ => println!("Namespace definition: {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
-    let result = parts.join("\n");
+    let name = names.join("::");
+    if !name.is_empty() { head += format!(" {}", name).as_str(); }
+    let mut result = utils::join_block(&head, &body, utils::BraceKind::Namespace);
+    if utils::active_c_opts().namespace_end_comments {
+        if name.is_empty() { result += " // namespace"; }
+        else { result += format!(" // namespace {}", name).as_str(); }
+    }
     return result;
 }
 
+// The only thing inside a declaration list, if that is a plain named namespace.
+fn get_compactable_namespace(declaration_list: Node) -> Option<Node> {
+    let mut inner = None;
+    for node in declaration_list.named_children(&mut declaration_list.walk()) {
+        if inner.is_some() || node.grammar_name() != "namespace_definition" { return None; }
+        inner = Some(node);
+    }
+    let inner = inner?;
+    let is_plain = inner.child(0).is_some_and(|x| x.grammar_name() == "namespace");
+    let is_named = inner.child_by_field_name("name").is_some_and(|x| x.grammar_name() == "identifier");
+    if !is_plain || !is_named { return None; }
+    return Some(inner);
+}
+
+// A comment on the same line as the end of the previous node, e.g. `} // namespace foo`.
+fn is_trailing_comment(node: Node) -> bool {
+    let Some(prev) = node.prev_sibling() else { return false };
+    return node.grammar_name() == "comment" && prev.end_position().row == node.start_position().row;
+}
+
 fn handle_declaration_list(root: Node, src: String) -> String {
     let mut parts = Vec::<String>::new();
+    let mut last_kind = "";
     for node in root.children(&mut root.walk()) {
-        match node.grammar_name() {
+        let kind = node.grammar_name();
+        if kind == ";" { continue; }
+        if is_trailing_comment(node) {
+            let follows_namespace = node.prev_sibling().is_some_and(|x| x.grammar_name() == "namespace_definition");
+            // Regenerated by the namespace itself.
+            if follows_namespace && utils::active_c_opts().namespace_end_comments { continue; }
+            let comment = handle_comment(node, src.clone());
+            if let Some(last) = parts.last_mut() { *last += format!(" {}", comment.trim()).as_str(); }
+            continue;
+        }
+        // Like declarations stay grouped; everything else gets a blank line around it.
+        let is_grouped = {
+            kind == last_kind &&
+            ["declaration", "using_declaration", "alias_declaration", "namespace_alias_definition", "preproc_include", "comment"].contains(&kind)
+        };
+        let needs_blank_line = !["", "{", "comment"].contains(&last_kind) && kind != "}" && !is_grouped;
+        if needs_blank_line { parts.push(String::new()); }
+        match kind {
             "{" => parts.push("{".to_string()),
             "}" => parts.push("}".to_string()),
             "function_definition" => {
@@ -3822,8 +4519,86 @@ fn handle_declaration_list(root: Node, src: String) -> String {
                 function_definition = utils::add_all_leading_tabs(function_definition);
                 parts.push(function_definition);
             },
+            "declaration" => {
+                let declaration = handle_declaration(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(declaration));
+            },
+            "static_assert_declaration" => {
+                let mut static_assert_declaration = handle_static_assert_declaration(node, src.clone());
+                static_assert_declaration = utils::add_all_leading_tabs(static_assert_declaration);
+                parts.push(static_assert_declaration);
+            },
+            "template_declaration" => {
+                let template_declaration = handle_template_declaration(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(template_declaration));
+            },
+            "namespace_definition" => {
+                let namespace_definition = handle_namespace_definition(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(namespace_definition));
+            },
+            "namespace_alias_definition" => {
+                let namespace_alias_definition = handle_namespace_alias_definition(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(namespace_alias_definition));
+            },
+            "using_declaration" => {
+                let using_declaration = handle_using_declaration(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(using_declaration));
+            },
+            "alias_declaration" => {
+                let alias_declaration = handle_alias_declaration(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(alias_declaration));
+            },
+            "type_definition" => {
+                let type_definition = handle_type_definition(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(type_definition));
+            },
+            "class_specifier" => {
+                let class_specifier = handle_class_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", class_specifier.trim_end())));
+            },
+            "struct_specifier" => {
+                let struct_specifier = handle_struct_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", struct_specifier.trim_end())));
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", enum_specifier.trim_end())));
+            },
+            "linkage_specification" => {
+                let linkage_specification = handle_linkage_specification(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(linkage_specification));
+            },
+            "comment" => {
+                let comment = handle_comment(node, src.clone());
+                parts.push(format!("\t{}", comment.trim_start()));
+            },
+            "preproc_include" => {
+                let preproc_include = handle_preproc_include(node, src.clone());
+                parts.push(preproc_include);
+            },
+            "preproc_def" => {
+                let preproc_def = handle_preproc_def(node, src.clone());
+                parts.push(preproc_def.trim_end().to_string());
+            },
+            "preproc_function_def" => {
+                let preproc_function_def = handle_preproc_function_def(node, src.clone());
+                parts.push(preproc_function_def.trim_end().to_string());
+            },
+            "preproc_call" => {
+                let preproc_call = handle_preproc_call(node, src.clone());
+                parts.push(preproc_call.trim_end().to_string());
+            },
+            "preproc_ifdef" => {
+                let preproc_ifdef = handle_preproc_ifdef(node, src.clone());
+                parts.push(preproc_ifdef.trim().to_string());
+            },
+            "preproc_if" => {
+                let preproc_if = handle_preproc_if(node, src.clone());
+                parts.push(preproc_if.trim().to_string());
+            },
             _ => println!("You shouldn't be here (declaration_list): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
+        last_kind = kind;
     }
     let result = parts.join("\n");
     return result;
@@ -3859,7 +4634,11 @@ fn handle_initializer_pair(root: Node, src: String) -> String {
                 let string_literal = handle_string_literal(node, src.clone());
                 result += string_literal.as_str();
             },
-            "=" => result += format!("= ").as_str(),
+            "=" => result += "= ",
+            "number_literal" | "char_literal" | "call_expression" | "binary_expression" | "unary_expression" | "initializer_list" | "qualified_identifier" | "field_expression" | "true" | "false" => {
+                let expression = handle_expression(node, src.clone());
+                result += expression.as_str();
+            },
             _ => println!("Initializer Pair: {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
         }
     }
@@ -3881,3 +4660,2406 @@ fn handle_comma_expression(root: Node, src: String) -> String {
     return result;
 }
 
+fn handle_attribute_declaration(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "[[" => result += "[[",
+            "]]" => result += "]]",
+            "," => result += ", ",
+            "attribute" => {
+                let attribute = handle_attribute(node, src.clone());
+                result += attribute.as_str();
+            },
+            _ => println!("You shouldn't be here (attribute_declaration): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_attribute(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "argument_list" => {
+                let argument_list = handle_argument_list(node, src.clone());
+                result += argument_list.as_str();
+            },
+            "::" => result += "::",
+            "using" => result += "using ",
+            ":" => result += ": ",
+            _ => println!("You shouldn't be here (attribute): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_attribute_specifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "__attribute__" => result += "__attribute__",
+            "__attribute" => result += "__attribute",
+            "(" => result += "(",
+            ")" => result += ")",
+            "argument_list" => {
+                let argument_list = handle_argument_list(node, src.clone());
+                result += argument_list.as_str();
+            },
+            _ => println!("You shouldn't be here (attribute_specifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_alignas_qualifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "alignas" => result += "alignas",
+            "_Alignas" => result += "_Alignas",
+            "(" => result += "(",
+            ")" => result += ")",
+            "number_literal" => {
+                let number_literal = handle_number_literal(node, src.clone());
+                result += number_literal.as_str();
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.as_str();
+            },
+            "sizeof_expression" => {
+                let sizeof_expression = handle_sizeof_expression(node, src.clone());
+                result += sizeof_expression.as_str();
+            },
+            "binary_expression" => {
+                let binary_expression = handle_binary_expression(node, src.clone());
+                result += binary_expression.as_str();
+            },
+            _ => println!("You shouldn't be here (alignas_qualifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_attributed_statement(root: Node, src: String) -> String {
+    let mut attributes = Vec::<String>::new();
+    let mut statement = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "attribute_declaration" => {
+                let attribute_declaration = handle_attribute_declaration(node, src.clone());
+                attributes.push(attribute_declaration);
+            },
+            "expression_statement" => statement = handle_expression_statement(node, src.clone()),
+            "return_statement" => statement = handle_return_statement(node, src.clone()),
+            "if_statement" => statement = handle_if_statement(node, src.clone()),
+            "switch_statement" => statement = handle_switch_statement(node, src.clone()),
+            "while_statement" => statement = handle_while_statement(node, src.clone()),
+            "for_statement" => statement = handle_for_statement(node, src.clone()),
+            "do_statement" => statement = handle_do_statement(node, src.clone()),
+            "goto_statement" => statement = handle_goto_statement(node, src.clone()),
+            "labeled_statement" => statement = handle_labeled_statement(node, src.clone()).trim_start().to_string(),
+            "compound_statement" => statement = handle_inner_compound_statement(node, src.clone()),
+            "break_statement" => statement = "break;".to_string(),
+            "continue_statement" => statement = "continue;".to_string(),
+            _ => println!("You shouldn't be here (attributed_statement): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    // An empty statement like `[[fallthrough]];` keeps the semicolon attached.
+    if statement.trim() == ";" { return format!("{};", attributes.join(" ")); }
+    return format!("{} {}", attributes.join(" "), statement);
+}
+
+fn handle_static_assert_declaration(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "static_assert" => result += "static_assert",
+            "(" => result += "(",
+            ")" => result += ")",
+            "," => result += ", ",
+            ";" => result += ";",
+            "true" => result += "true",
+            "false" => result += "false",
+            "binary_expression" => {
+                let binary_expression = handle_binary_expression(node, src.clone());
+                result += binary_expression.as_str();
+            },
+            "unary_expression" => {
+                let unary_expression = handle_unary_expression(node, src.clone());
+                result += unary_expression.as_str();
+            },
+            "call_expression" => {
+                let call_expression = handle_call_expression(node, src.clone());
+                result += call_expression.as_str();
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                result += qualified_identifier.as_str();
+            },
+            "template_function" => {
+                let template_function = handle_template_function(node, src.clone());
+                result += template_function.as_str();
+            },
+            "sizeof_expression" => {
+                let sizeof_expression = handle_sizeof_expression(node, src.clone());
+                result += sizeof_expression.as_str();
+            },
+            "parenthesized_expression" => {
+                let parenthesized_expression = handle_parenthesized_expression(node, src.clone());
+                result += parenthesized_expression.as_str();
+            },
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                result += string_literal.as_str();
+            },
+            "concatenated_string" => {
+                let concatenated_string = handle_concatenated_string(node, src.clone());
+                result += concatenated_string.as_str();
+            },
+            "raw_string_literal" => {
+                let raw_string_literal = handle_raw_string_literal(node, src.clone());
+                result += raw_string_literal.as_str();
+            },
+            _ => println!("You shouldn't be here (static_assert_declaration): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_operator_name(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "operator" => result += "operator",
+            "new" => result += " new",
+            "delete" => result += " delete",
+            "co_await" => result += " co_await",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            _ => result += node.utf8_text(src.as_bytes()).unwrap_or(""),
+        }
+    }
+    return result;
+}
+
+fn handle_destructor_name(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "~" => result += "~",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            _ => println!("You shouldn't be here (destructor_name): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_operator_cast(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    let mut declarator = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "operator" => parts.push("operator".to_string()),
+            "type_qualifier" => {
+                let type_qualifier = handle_type_qualifier(node, src.clone());
+                parts.push(type_qualifier);
+            },
+            "primitive_type" => {
+                let primitive_type = handle_primitive_type(node, src.clone());
+                parts.push(primitive_type);
+            },
+            "sized_type_specifier" => {
+                let sized_type_specifier = handle_sized_type_specifier(node, src.clone());
+                parts.push(sized_type_specifier);
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                parts.push(identifier);
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                parts.push(qualified_identifier);
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                parts.push(template_type);
+            },
+            "abstract_function_declarator" => declarator = handle_abstract_function_declarator(node, src.clone()),
+            "abstract_pointer_declarator" => declarator = handle_abstract_pointer_declarator(node, src.clone()),
+            "abstract_reference_declarator" => declarator = handle_abstract_reference_declarator(node, src.clone()),
+            _ => println!("You shouldn't be here (operator_cast): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    let result = format!("{}{}", parts.join(" "), declarator);
+    return result;
+}
+
+fn handle_noexcept(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "noexcept" => result += "noexcept",
+            "(" => result += "(",
+            ")" => result += ")",
+            "true" => result += "true",
+            "false" => result += "false",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "call_expression" => {
+                let call_expression = handle_call_expression(node, src.clone());
+                result += call_expression.as_str();
+            },
+            "binary_expression" => {
+                let binary_expression = handle_binary_expression(node, src.clone());
+                result += binary_expression.as_str();
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                result += qualified_identifier.as_str();
+            },
+            _ => println!("You shouldn't be here (noexcept): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_virtual_specifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "override" => result += "override",
+            "final" => result += "final",
+            _ => println!("You shouldn't be here (virtual_specifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_explicit_function_specifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "explicit" => result += "explicit",
+            "(" => result += "(",
+            ")" => result += ")",
+            "true" => result += "true",
+            "false" => result += "false",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            _ => println!("You shouldn't be here (explicit_function_specifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_method_clause(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "=" => result += "= ",
+            "default" => result += "default",
+            "delete" => result += "delete",
+            ";" => result += ";",
+            _ => println!("You shouldn't be here (method_clause): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_friend_declaration(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "friend" => parts.push("friend".to_string()),
+            "class" => parts.push("class".to_string()),
+            "struct" => parts.push("struct".to_string()),
+            "union" => parts.push("union".to_string()),
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                parts.push(identifier);
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                parts.push(qualified_identifier);
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                parts.push(template_type);
+            },
+            "declaration" => {
+                let declaration = handle_declaration(node, src.clone());
+                parts.push(declaration);
+            },
+            "function_definition" => {
+                let function_definition = handle_function_definition(node, src.clone());
+                parts.push(function_definition);
+            },
+            ";" => parts.push(";".to_string()),
+            _ => println!("You shouldn't be here (friend_declaration): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    let mut result = parts.join(" ");
+    if result.ends_with(" ;") { result = format!("{};", result.trim_end_matches(" ;")); }
+    return result;
+}
+
+fn handle_base_class_clause(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            ":" => result += ": ",
+            "," => result += ", ",
+            "virtual" => result += "virtual ",
+            "access_specifier" => {
+                let access_specifier = handle_access_specifier(node, src.clone());
+                result += format!("{} ", access_specifier.trim_end_matches(':')).as_str();
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                result += qualified_identifier.as_str();
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                result += template_type.as_str();
+            },
+            _ => println!("You shouldn't be here (base_class_clause): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_concept_definition(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "concept" => result += "concept ",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += format!("{} ", identifier).as_str();
+            },
+            "=" => result += "= ",
+            ";" => result += ";",
+            _ => {
+                let constraint = handle_constraint(node, src.clone());
+                result += constraint.as_str();
+            },
+        }
+    }
+    return result;
+}
+
+fn handle_requires_clause(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "requires" => result += "requires ",
+            "(" => result += "(",
+            ")" => result += ")",
+            _ => {
+                let constraint = handle_constraint(node, src.clone());
+                result += constraint.as_str();
+            },
+        }
+    }
+    return result;
+}
+
+// The right-hand side of a concept or a requires clause.
+fn handle_constraint(root: Node, src: String) -> String {
+    let mut result = String::new();
+    match root.grammar_name() {
+        "constraint_conjunction" | "constraint_disjunction" => {
+            let mut parts = Vec::<String>::new();
+            // `(` sticks to the constraint after it and `)` to the one before it
+            let mut open_parens = String::new();
+            for node in root.children(&mut root.walk()) {
+                match node.grammar_name() {
+                    "&&" | "||" | "and" | "or" => parts.push(node.grammar_name().to_string()),
+                    "(" => open_parens += "(",
+                    ")" => if let Some(last) = parts.last_mut() { last.push(')'); },
+                    _ => {
+                        let constraint = handle_constraint(node, src.clone());
+                        parts.push(format!("{}{}", open_parens, constraint));
+                        open_parens.clear();
+                    },
+                }
+            }
+            result = parts.join(" ");
+        },
+        "requires_expression" => result = handle_requires_expression(root, src.clone()),
+        "template_type" => result = handle_template_type(root, src.clone()),
+        "qualified_identifier" => result = handle_qualified_identifier(root, src.clone()),
+        "identifier" => result = handle_identifier(root, src.clone()),
+        "parenthesized_expression" => result = handle_parenthesized_expression(root, src.clone()),
+        "binary_expression" => result = handle_binary_expression(root, src.clone()),
+        "unary_expression" => result = handle_unary_expression(root, src.clone()),
+        "call_expression" => result = handle_call_expression(root, src.clone()),
+        "template_function" => result = handle_template_function(root, src.clone()),
+        "true" => result = "true".to_string(),
+        "false" => result = "false".to_string(),
+        _ => println!("You shouldn't be here (constraint): {}: {}\n", root.grammar_name(), root.utf8_text(src.as_bytes()).unwrap_or("")),
+    }
+    return result;
+}
+
+fn handle_requires_expression(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "requires" => result += "requires",
+            "parameter_list" => {
+                let parameter_list = handle_parameter_list(node, src.clone());
+                result += parameter_list.as_str();
+            },
+            "requirement_seq" => {
+                let requirement_seq = handle_requirement_seq(node, src.clone());
+                result += format!(" {}", requirement_seq).as_str();
+            },
+            _ => println!("You shouldn't be here (requires_expression): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_requirement_seq(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "{" => parts.push("{".to_string()),
+            "}" => parts.push("}".to_string()),
+            "compound_requirement" => {
+                let compound_requirement = handle_compound_requirement(node, src.clone());
+                parts.push(format!("\t{}", compound_requirement));
+            },
+            "type_requirement" => {
+                let type_requirement = handle_type_requirement(node, src.clone());
+                parts.push(format!("\t{};", type_requirement));
+            },
+            "expression_statement" => {
+                let expression_statement = handle_expression_statement(node, src.clone());
+                // A lone `;` is the leftover of a requirement the grammar split in two.
+                if expression_statement.trim() == ";" { continue; }
+                parts.push(format!("\t{}", expression_statement));
+            },
+            "comment" => {
+                let comment = handle_comment(node, src.clone());
+                parts.push(format!("\t{}", comment));
+            },
+            ";" => (),
+            _ => println!("You shouldn't be here (requirement_seq): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    let result = parts.join("\n");
+    return result;
+}
+
+fn handle_compound_requirement(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "{" => result += "{ ",
+            "}" => result += " }",
+            ";" => result += ";",
+            "noexcept" => result += " noexcept",
+            "trailing_return_type" => {
+                let trailing_return_type = handle_trailing_return_type(node, src.clone());
+                result += format!(" {}", trailing_return_type).as_str();
+            },
+            "field_expression" => {
+                let field_expression = handle_field_expression(node, src.clone());
+                result += field_expression.as_str();
+            },
+            "subscript_expression" => {
+                let subscript_expression = handle_subscript_expression(node, src.clone());
+                result += subscript_expression.as_str();
+            },
+            "update_expression" => {
+                let update_expression = handle_update_expression(node, src.clone());
+                result += update_expression.as_str();
+            },
+            "assignment_expression" => {
+                let assignment_expression = handle_assignment_expression(node, src.clone());
+                result += assignment_expression.as_str();
+            },
+            "pointer_expression" => {
+                let pointer_expression = handle_pointer_expression(node, src.clone());
+                result += pointer_expression.as_str();
+            },
+            _ => {
+                let constraint = handle_constraint(node, src.clone());
+                result += constraint.as_str();
+            },
+        }
+    }
+    return result;
+}
+
+fn handle_type_requirement(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "typename" => result += "typename ",
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                result += qualified_identifier.as_str();
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                result += template_type.as_str();
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            _ => println!("You shouldn't be here (type_requirement): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_trailing_return_type(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "->" => result += "-> ",
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                result += type_descriptor.as_str();
+            },
+            _ => println!("You shouldn't be here (trailing_return_type): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_placeholder_type_specifier(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "auto" => parts.push("auto".to_string()),
+            "decltype" => {
+                let decltype = handle_decltype(node, src.clone());
+                parts.push(decltype);
+            },
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                parts.push(identifier);
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                parts.push(qualified_identifier);
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                parts.push(template_type);
+            },
+            _ => println!("You shouldn't be here (placeholder_type_specifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    let result = parts.join(" ");
+    return result;
+}
+
+fn handle_template_parameter(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "typename" => parts.push("typename".to_string()),
+            "class" => parts.push("class".to_string()),
+            "..." => parts.push("...".to_string()),
+            "=" => parts.push("=".to_string()),
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                parts.push(identifier);
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                parts.push(qualified_identifier);
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                parts.push(template_type);
+            },
+            "primitive_type" => {
+                let primitive_type = handle_primitive_type(node, src.clone());
+                parts.push(primitive_type);
+            },
+            "placeholder_type_specifier" => {
+                let placeholder_type_specifier = handle_placeholder_type_specifier(node, src.clone());
+                parts.push(placeholder_type_specifier);
+            },
+            "type_descriptor" => {
+                let type_descriptor = handle_type_descriptor(node, src.clone());
+                parts.push(type_descriptor);
+            },
+            "number_literal" => {
+                let number_literal = handle_number_literal(node, src.clone());
+                parts.push(number_literal);
+            },
+            "true" => parts.push("true".to_string()),
+            "false" => parts.push("false".to_string()),
+            _ => println!("You shouldn't be here (template_parameter): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    let result = parts.join(" ").replace(" ...", "...");
+    return result;
+}
+
+// The grammar predates C++20 modules, so `module`, `import` and `export` declarations
+// come out as a mix of identifiers and ERROR nodes. They are recognized from their text instead.
+// `module m;`, `export module m;`, `import <vector>;`, `module;` and `export { ... }`.
+fn is_module_declaration(text: &str) -> bool {
+    let text = text.trim();
+    let starts_with_keyword = |text: &str, keyword: &str| {
+        return text.strip_prefix(keyword).is_some_and(|x| x.starts_with(char::is_whitespace));
+    };
+    let is_exported = starts_with_keyword(text, "export") || text.starts_with("export{");
+    let rest = if is_exported { text["export".len()..].trim_start() } else { text };
+    if is_exported && rest.starts_with("{") { return true; }
+    if !text.ends_with(";") { return false; }
+    if rest == "module;" { return true; }
+    let is_keyword = starts_with_keyword(rest, "module") || starts_with_keyword(rest, "import");
+    return is_keyword && !rest.contains("=") && !rest.contains("(");
+}
+
+// The byte offsets of the braces of an `export { ... }` block starting at `start`.
+// Strings, character literals and comments are skipped.
+fn find_export_block(src: &str, start: usize) -> Option<(usize, usize)> {
+    let text = &src[start..];
+    if !text.starts_with("export") || !text["export".len()..].trim_start().starts_with("{") { return None; }
+    let open = start + text.find("{")?;
+    let bytes = src.as_bytes();
+    let mut depth = 0;
+    let mut i = open;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => {
+                let quote = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' { i += 1; }
+                    i += 1;
+                }
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' { i += 1; }
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i + 1 < bytes.len() && !(bytes[i] == b'*' && bytes[i + 1] == b'/') { i += 1; }
+                i += 1;
+            },
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 { return Some((open, i)); }
+            },
+            _ => (),
+        }
+        i += 1;
+    }
+    return None;
+}
+
+// The declarations of an `export { ... }` block are formatted like a file of their own.
+// GNU and Whitesmiths braces are moved once the whole file is put together.
+fn handle_export_block(inner: &str, opts: options::COpts) -> String {
+    let mut body = String::new();
+    if !inner.trim().is_empty() {
+        let mut inner_opts = opts;
+        if [utils::Style::Gnu, utils::Style::Whitesmiths].contains(&opts.style) { inner_opts.style = utils::Style::Allman; }
+        let ast = cpp_format::parse_existing_cpp_file(inner.to_string());
+        body = traverse_cpp_ast(ast, inner.to_string(), inner_opts);
+        utils::set_active_c_opts(opts);
+    }
+    let lines = body.trim_end().lines()
+        .map(|x| if x.is_empty() { String::new() } else { format!("\t{}", x) })
+        .collect::<Vec<String>>();
+    let block = if lines.is_empty() { "{\n}".to_string() } else { format!("{{\n{}\n}}", lines.join("\n")) };
+    return utils::join_block("export", &block, utils::BraceKind::Namespace);
+}
+
+fn handle_module_declaration(root: Node, src: String) -> String {
+    let text = root.utf8_text(src.as_bytes()).unwrap_or("").trim().to_string();
+    let result = text.split_whitespace().collect::<Vec<&str>>().join(" ").replace(" ;", ";");
+    return result;
+}
+
+fn handle_expression(root: Node, src: String) -> String {
+    let result = match root.grammar_name() {
+        "identifier" => handle_identifier(root, src.clone()),
+        "qualified_identifier" => handle_qualified_identifier(root, src.clone()),
+        "number_literal" => handle_number_literal(root, src.clone()),
+        "string_literal" => handle_string_literal(root, src.clone()),
+        "char_literal" => handle_char_literal(root, src.clone()),
+        "call_expression" => handle_call_expression(root, src.clone()),
+        "binary_expression" => handle_binary_expression(root, src.clone()),
+        "unary_expression" => handle_unary_expression(root, src.clone()),
+        "update_expression" => handle_update_expression(root, src.clone()),
+        "field_expression" => handle_field_expression(root, src.clone()),
+        "subscript_expression" => handle_subscript_expression(root, src.clone()),
+        "pointer_expression" => handle_pointer_expression(root, src.clone()),
+        "cast_expression" => handle_cast_expression(root, src.clone()),
+        "parenthesized_expression" => handle_parenthesized_expression(root, src.clone()),
+        "conditional_expression" => handle_conditional_expression(root, src.clone()),
+        "assignment_expression" => handle_assignment_expression(root, src.clone()),
+        "initializer_list" => handle_initializer_list(root, src.clone()),
+        "lambda_expression" => handle_lambda_expression(root, src.clone()),
+        "compound_literal_expression" => handle_compound_literal_expression(root, src.clone()),
+        "new_expression" => handle_new_expression(root, src.clone()),
+        "sizeof_expression" => handle_sizeof_expression(root, src.clone()),
+        "template_function" => handle_template_function(root, src.clone()),
+        "co_await_expression" => handle_co_await_expression(root, src.clone()),
+        "fold_expression" => handle_fold_expression(root, src.clone()),
+        "parameter_pack_expansion" => handle_parameter_pack_expansion(root, src.clone()),
+        "type_descriptor" => handle_type_descriptor(root, src.clone()),
+        "true" | "false" | "this" | "nullptr" => root.grammar_name().to_string(),
+        _ => {
+            println!("You shouldn't be here (expression): {}: {}\n", root.grammar_name(), root.utf8_text(src.as_bytes()).unwrap_or(""));
+            String::new()
+        },
+    };
+    return result;
+}
+
+// A functional cast with braces: `Point{1, 2}` or `std::string{"a"}`.
+fn handle_compound_literal_expression(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                result += qualified_identifier.as_str();
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                result += template_type.as_str();
+            },
+            "primitive_type" => {
+                let primitive_type = handle_primitive_type(node, src.clone());
+                result += primitive_type.as_str();
+            },
+            "initializer_list" => {
+                let initializer_list = handle_initializer_list(node, src.clone());
+                result += initializer_list.as_str();
+            },
+            _ => {
+                let expression = handle_expression(node, src.clone());
+                result += expression.as_str();
+            },
+        }
+    }
+    return result;
+}
+
+fn handle_co_await_expression(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "co_await" => result += "co_await ",
+            _ => {
+                let expression = handle_expression(node, src.clone());
+                result += expression.as_str();
+            },
+        }
+    }
+    return result;
+}
+
+// Covers both `co_return` and `co_yield` statements.
+fn handle_coroutine_statement(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "co_return" => parts.push("co_return".to_string()),
+            "co_yield" => parts.push("co_yield".to_string()),
+            ";" => (),
+            _ => {
+                let expression = handle_expression(node, src.clone());
+                parts.push(expression);
+            },
+        }
+    }
+    let result = format!("{};", parts.join(" "));
+    return result;
+}
+
+fn handle_fold_expression(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "(" | ")" => (),
+            "..." => parts.push("...".to_string()),
+            _ if node.child_count() == 0 && !node.is_named() => parts.push(node.grammar_name().to_string()),
+            _ => {
+                let expression = handle_expression(node, src.clone());
+                parts.push(expression);
+            },
+        }
+    }
+    let result = format!("({})", parts.join(" "));
+    return result;
+}
+
+fn handle_parameter_pack_expansion(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "..." => result += "...",
+            _ => {
+                let expression = handle_expression(node, src.clone());
+                result += expression.as_str();
+            },
+        }
+    }
+    return result;
+}
+
+fn handle_decltype(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "decltype" => result += "decltype",
+            "(" => result += "(",
+            ")" => result += ")",
+            "auto" => result += "auto",
+            _ => {
+                let expression = handle_expression(node, src.clone());
+                result += expression.as_str();
+            },
+        }
+    }
+    return result;
+}
+
+fn handle_variadic_parameter_declaration(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "qualified_identifier" => {
+                let qualified_identifier = handle_qualified_identifier(node, src.clone());
+                result += qualified_identifier.as_str();
+            },
+            "template_type" => {
+                let template_type = handle_template_type(node, src.clone());
+                result += template_type.as_str();
+            },
+            "primitive_type" => {
+                let primitive_type = handle_primitive_type(node, src.clone());
+                result += primitive_type.as_str();
+            },
+            "type_qualifier" => {
+                let type_qualifier = handle_type_qualifier(node, src.clone());
+                result += format!("{} ", type_qualifier).as_str();
+            },
+            "variadic_declarator" => {
+                let variadic_declarator = handle_variadic_declarator(node, src.clone());
+                result += variadic_declarator.as_str();
+            },
+            "placeholder_type_specifier" => {
+                let placeholder_type_specifier = handle_placeholder_type_specifier(node, src.clone());
+                result += placeholder_type_specifier.as_str();
+            },
+            "reference_declarator" => {
+                for subnode in node.children(&mut node.walk()) {
+                    match subnode.grammar_name() {
+                        "&" => result += "&",
+                        "&&" => result += "&&",
+                        "variadic_declarator" => {
+                            let variadic_declarator = handle_variadic_declarator(subnode, src.clone());
+                            result += variadic_declarator.as_str();
+                        },
+                        _ => println!("You shouldn't be here (variadic_parameter_declaration): {}\n", subnode.grammar_name()),
+                    }
+                }
+            },
+            _ => println!("You shouldn't be here (variadic_parameter_declaration): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_variadic_declarator(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "..." => result += "...",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += format!(" {}", identifier).as_str();
+            },
+            _ => println!("You shouldn't be here (variadic_declarator): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_linkage_specification(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "extern" => result += "extern ",
+            "string_literal" => {
+                let string_literal = handle_string_literal(node, src.clone());
+                result += string_literal.as_str();
+            },
+            "declaration_list" => {
+                let declaration_list = handle_linkage_declaration_list(node, src.clone());
+                result = utils::join_block(&result, &declaration_list, utils::BraceKind::Extern);
+            },
+            "declaration" => {
+                let declaration = handle_declaration(node, src.clone());
+                result += format!(" {}", declaration).as_str();
+            },
+            "function_definition" => {
+                let function_definition = handle_function_definition(node, src.clone());
+                result += format!(" {}", function_definition).as_str();
+            },
+            _ => println!("You shouldn't be here (linkage_specification): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+fn handle_linkage_declaration_list(root: Node, src: String) -> String {
+    let mut parts = Vec::<String>::new();
+    let mut last_kind = "";
+    for node in root.children(&mut root.walk()) {
+        let kind = node.grammar_name();
+        // The `;` after a struct body, already added below.
+        if kind == ";" { continue; }
+        // Keep declarations grouped, but give everything else some room.
+        let needs_space = {
+            !last_kind.is_empty() && last_kind != "{" &&
+            (kind != last_kind || kind == "function_definition")
+        };
+        if needs_space && kind != "}" { parts.push(String::new()); }
+        match kind {
+            "{" => parts.push("{".to_string()),
+            "}" => parts.push("}".to_string()),
+            "declaration" => {
+                let mut declaration = handle_declaration(node, src.clone());
+                if declaration.contains("*") { declaration = utils::remove_pointer_spaces(declaration); }
+                parts.push(utils::add_all_leading_tabs(declaration));
+            },
+            "function_definition" => {
+                let function_definition = handle_function_definition(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(function_definition));
+            },
+            "struct_specifier" => {
+                let struct_specifier = handle_struct_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", struct_specifier.trim_end())));
+            },
+            "enum_specifier" => {
+                let enum_specifier = handle_enum_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", enum_specifier.trim_end())));
+            },
+            "union_specifier" => {
+                let union_specifier = handle_union_specifier(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(format!("{};", union_specifier.trim_end())));
+            },
+            // `_Static_assert(...)` is parsed as a call.
+            "expression_statement" => {
+                let expression_statement = handle_expression_statement(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(expression_statement));
+            },
+            "static_assert_declaration" => {
+                let static_assert_declaration = handle_static_assert_declaration(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(static_assert_declaration));
+            },
+            "type_definition" => {
+                let type_definition = handle_type_definition(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(type_definition));
+            },
+            "comment" => {
+                let comment = handle_comment(node, src.clone());
+                parts.push(format!("\t{}", comment));
+            },
+            "preproc_include" => {
+                let preproc_include = handle_preproc_include(node, src.clone());
+                parts.push(preproc_include);
+            },
+            "preproc_def" => {
+                let preproc_def = handle_preproc_def(node, src.clone());
+                parts.push(preproc_def);
+            },
+            "preproc_function_def" => {
+                let preproc_function_def = handle_preproc_function_def(node, src.clone());
+                parts.push(preproc_function_def);
+            },
+            "preproc_call" => {
+                let preproc_call = handle_preproc_call(node, src.clone());
+                parts.push(preproc_call.trim_end().to_string());
+            },
+            "preproc_ifdef" => {
+                let preproc_ifdef = handle_preproc_ifdef(node, src.clone());
+                parts.push(preproc_ifdef.trim().to_string());
+            },
+            "preproc_if" => {
+                let preproc_if = handle_preproc_if(node, src.clone());
+                parts.push(preproc_if.trim().to_string());
+            },
+            "linkage_specification" => {
+                let linkage_specification = handle_linkage_specification(node, src.clone());
+                parts.push(utils::add_all_leading_tabs(linkage_specification));
+            },
+            _ => println!("You shouldn't be here (linkage_declaration_list): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+        last_kind = kind;
+    }
+    let result = parts.join("\n");
+    return result;
+}
+
+fn handle_ms_declspec_modifier(root: Node, src: String) -> String {
+    let mut result = String::new();
+    for node in root.children(&mut root.walk()) {
+        match node.grammar_name() {
+            "__declspec" => result += "__declspec",
+            "(" => result += "(",
+            ")" => result += ")",
+            "identifier" => {
+                let identifier = handle_identifier(node, src.clone());
+                result += identifier.as_str();
+            },
+            "call_expression" => {
+                let call_expression = handle_call_expression(node, src.clone());
+                result += call_expression.as_str();
+            },
+            _ => println!("You shouldn't be here (ms_declspec_modifier): {}: {}\n", node.grammar_name(), node.utf8_text(src.as_bytes()).unwrap_or("")),
+        }
+    }
+    return result;
+}
+
+// Reports (without reordering anything) members that come before a group they should follow,
+// checked separately for each access section.
+fn check_member_order(root: Node, order: [utils::MemberKind; 4], src: String) -> Vec<String> {
+    let mut problems = Vec::<String>::new();
+    let class_name = root.parent()
+        .and_then(|x| x.child_by_field_name("name"))
+        .map(|x| x.utf8_text(src.as_bytes()).unwrap_or("").to_string())
+        .unwrap_or_default();
+    let rank = |kind: utils::MemberKind| order.iter().position(|x| *x == kind).unwrap_or(0);
+    let mut furthest: Option<utils::MemberKind> = None;
+    for node in root.children(&mut root.walk()) {
+        if node.grammar_name() == "access_specifier" {
+            furthest = None;
+            continue;
+        }
+        let Some(kind) = get_member_kind(node, &class_name, src.clone()) else { continue };
+        match furthest {
+            Some(previous) if rank(kind) < rank(previous) => {
+                let member = node.utf8_text(src.as_bytes()).unwrap_or("").lines().next().unwrap_or("").trim().to_string();
+                problems.push(format!(
+                    "Member order: line {}: `{}` in `{}` belongs with the {}, which should come before the {}.",
+                    node.start_position().row + 1, member, class_name, kind.name(), previous.name()
+                ));
+            },
+            _ => furthest = Some(kind),
+        }
+    }
+    return problems;
+}
+
+fn get_member_kind(node: Node, class_name: &str, src: String) -> Option<utils::MemberKind> {
+    match node.grammar_name() {
+        "alias_declaration" | "type_definition" | "using_declaration" | "class_specifier" | "struct_specifier" | "enum_specifier" | "union_specifier" => {
+            return Some(utils::MemberKind::Type);
+        },
+        "template_declaration" => {
+            let last = node.named_child(node.named_child_count().saturating_sub(1))?;
+            return get_member_kind(last, class_name, src);
+        },
+        "field_declaration" | "declaration" | "function_definition" => (),
+        _ => return None,
+    }
+    let Some(mut declarator) = node.child_by_field_name("declarator") else {
+        // `struct Inner { ... };` declares a type, anything else without a declarator is a field.
+        let is_type = node.child_by_field_name("type").is_some_and(|x| x.grammar_name().ends_with("_specifier"));
+        if is_type { return Some(utils::MemberKind::Type); }
+        return Some(utils::MemberKind::Field);
+    };
+    while ["pointer_declarator", "reference_declarator", "init_declarator"].contains(&declarator.grammar_name()) {
+        let inner = declarator.child_by_field_name("declarator").or(declarator.named_child(0));
+        match inner {
+            Some(inner) => declarator = inner,
+            None => break,
+        }
+    }
+    if declarator.grammar_name() == "operator_cast" { return Some(utils::MemberKind::Method); }
+    if declarator.grammar_name() != "function_declarator" { return Some(utils::MemberKind::Field); }
+    let name = declarator.child_by_field_name("declarator")
+        .map(|x| x.utf8_text(src.as_bytes()).unwrap_or(""))
+        .unwrap_or("");
+    if name == class_name || name.starts_with("~") { return Some(utils::MemberKind::Constructor); }
+    return Some(utils::MemberKind::Method);
+}
+
+// A CUDA launch, `kernel<<<grid, block>>>(args)`, reads as `(kernel << <grid, block>) >> (args)`.
+fn is_kernel_launch(root: Node) -> bool {
+    let (Some(left), Some(operator), Some(right)) = (root.child(0), root.child(1), root.child(2)) else { return false };
+    let is_shift_right = operator.grammar_name() == ">>" && right.grammar_name() == "parenthesized_expression";
+    let is_launch_config = {
+        left.grammar_name() == "binary_expression" &&
+        left.child(1).is_some_and(|x| x.grammar_name() == "<<") &&
+        left.child(2).is_some_and(|x| x.grammar_name() == "template_function")
+    };
+    return is_shift_right && is_launch_config;
+}
+
+fn handle_kernel_launch(root: Node, src: String) -> String {
+    let mut result = String::new();
+    let normalize = |node: Node| node.utf8_text(src.as_bytes()).unwrap_or("").split_whitespace().collect::<Vec<&str>>().join(" ");
+    let Some(left) = root.child(0) else { return result };
+    if let Some(kernel) = left.child(0) {
+        let kernel = handle_expression(kernel, src.clone());
+        result += kernel.as_str();
+    }
+    let mut config = Vec::<String>::new();
+    if let Some(arguments) = left.child(2).and_then(|x| x.child_by_field_name("arguments")) {
+        for node in arguments.named_children(&mut arguments.walk()) {
+            config.push(normalize(node));
+        }
+    }
+    result += format!("<<<{}>>>", config.join(", ")).as_str();
+    let mut arguments = Vec::<String>::new();
+    if let Some(parenthesized) = root.child(2) {
+        let mut stack = parenthesized.named_children(&mut parenthesized.walk()).collect::<Vec<Node>>();
+        // `(a, b, n)` is a chain of comma expressions.
+        while let Some(node) = stack.pop() {
+            if node.grammar_name() == "comma_expression" {
+                let mut children = node.named_children(&mut node.walk()).collect::<Vec<Node>>();
+                children.reverse();
+                stack.extend(children);
+                continue;
+            }
+            arguments.push(handle_expression(node, src.clone()));
+        }
+    }
+    result += format!("({})", arguments.join(", ")).as_str();
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(src: &str) -> String {
+        let ast = cpp_format::parse_existing_cpp_file(src.to_string());
+        return traverse_cpp_ast(ast, src.to_string(), options::COpts::default());
+    }
+
+    #[test]
+    fn class_hierarchy() {
+        let src = "\
+class Shape {
+public:
+    Shape() = default;
+    Shape(const Shape&) = delete;
+    virtual ~Shape() noexcept = default;
+    virtual double area() const = 0;
+    bool operator==(const Shape& other) const noexcept;
+    double operator[](int i) const;
+    explicit operator bool() const;
+    friend class Canvas;
+    friend bool operator<(const Shape& a, const Shape& b);
+};
+class Circle final : public Shape {
+public:
+    double area() const override;
+    void draw() const final;
+};
+";
+        let expected = "\
+class Shape
+{
+public:
+\tShape() = default;
+\tShape(const Shape&) = delete;
+\tvirtual ~Shape() noexcept = default;
+\tvirtual double area() const = 0;
+\tbool operator==(const Shape& other) const noexcept;
+\tdouble operator[](int i) const;
+\texplicit operator bool() const;
+\tfriend class Canvas;
+\tfriend bool operator<(const Shape& a, const Shape& b);
+};
+
+class Circle final : public Shape
+{
+public:
+\tdouble area() const override;
+\tvoid draw() const final;
+};";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn derived_struct_with_inline_members() {
+        let src = "\
+struct Square final : Shape {
+    explicit Square(double s) noexcept : side(s) {}
+    double area() const override { return side * side; }
+    operator double() const { return side; }
+    Square& operator=(const Square&) = default;
+    double side;
+};
+";
+        let expected = "\
+struct Square final : Shape
+{
+\texplicit Square(double s) noexcept
+\t\t: side(s)
+\t{}
+\tdouble area() const override
+\t{
+\t\treturn side * side;
+\t}
+\toperator double() const
+\t{
+\t\treturn side;
+\t}
+\tSquare& operator=(const Square&) = default;
+\tdouble side;
+};";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn requires_clause_keeps_parentheses() {
+        let src = "\
+template <typename T>
+requires std::integral<T> && (sizeof(T) > 2)
+void g(T x);
+";
+        let expected = "\
+template <typename T>
+\trequires std::integral<T> && (sizeof(T) > 2)
+void g(T x);";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn forwarding_references() {
+        let src = "\
+void h(std::integral auto&& y, auto&& z, const auto& w);
+T&& get(T&& x);
+";
+        let expected = "\
+void h(std::integral auto&& y, auto&& z, const auto& w);
+T&& get(T&& x);";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn export_block_is_formatted() {
+        let src = "\
+export module shapes;
+export {
+  struct S { int a; };
+  int f(int x) { return x; }
+}
+module_t m;
+";
+        let expected = "\
+export module shapes;
+
+export
+{
+\tstruct S
+\t{
+\t\tint a;
+\t};
+
+\tint f(int x)
+\t{
+\t\treturn x;
+\t}
+}
+
+module_t m;";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn co_await_statement() {
+        let src = "task f() {\n    co_await something();\n}\n";
+        assert_eq!(format(src).trim_end(), "task f()\n{\n\tco_await something();\n}");
+    }
+
+    #[test]
+    fn defaulted_and_deleted_free_operators() {
+        let src = "\
+auto operator<=>(const Circle&, const Circle&) = default;
+bool operator!=(const Circle& a, const Circle& b) = delete;
+";
+        let expected = "\
+auto operator<=>(const Circle&, const Circle&) = default;
+bool operator!=(const Circle& a, const Circle& b) = delete;";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn lambda_init_captures() {
+        let src = "\
+void f() {
+	auto a = [x = std::move(y), &r = z, this]() { return x; };
+	auto b = [s = std::string{\"a\"}, &xs...]() {};
+}
+";
+        let expected = "\
+void f()
+{
+	auto a = [x = std::move(y), &r = z, this]() {
+		return x;
+	};
+	auto b = [s = std::string{\"a\"}, &xs...]() {
+	};
+}";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn cuda_restrict_parameters() {
+        let src = "\
+__global__ void add(const float* __restrict__ a, const float * __restrict__ b, float *c) {
+	c[0] = a[0] + b[0];
+}
+";
+        let expected = "\
+__global__ void add(const float* __restrict__ a, const float* __restrict__ b, float* c)
+{
+	c[0] = a[0] + b[0];
+}";
+        let masked = utils::mask_gpu_qualifiers(src, false);
+        let result = utils::unmask_gpu_qualifiers(&format(&masked));
+        assert_eq!(result.trim_end(), expected);
+    }
+
+    fn format_with(src: &str, args: &[&str]) -> String {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        let ast = cpp_format::parse_existing_cpp_file(src.to_string());
+        return traverse_cpp_ast(ast, src.to_string(), options::get_c_opts(&args));
+    }
+
+    #[test]
+    fn constructor_initializers() {
+        let src = "\
+class Widget {
+public:
+    Widget(int a) : a_(a), b_(2) {}
+    Widget() : first_member_(1), second_member_(2), third_member_(3), fourth_member_(4), fifth_member_(5), sixth_member_(6) { go(); }
+    int a_;
+};
+";
+        let cases = [
+            (vec!["--ctor-init-break", "before-colon"], "\
+class Widget
+{
+public:
+	Widget(int a)
+		: a_(a), b_(2)
+	{}
+	Widget()
+		: first_member_(1), second_member_(2), third_member_(3), fourth_member_(4), fifth_member_(5), sixth_member_(6)
+	{
+		go();
+	}
+	int a_;
+};"),
+            (vec!["--ctor-init-break", "before-comma", "--ctor-init-pack", "one-per-line"], "\
+class Widget
+{
+public:
+	Widget(int a)
+		: a_(a)
+		, b_(2)
+	{}
+	Widget()
+		: first_member_(1)
+		, second_member_(2)
+		, third_member_(3)
+		, fourth_member_(4)
+		, fifth_member_(5)
+		, sixth_member_(6)
+	{
+		go();
+	}
+	int a_;
+};"),
+            (vec!["--ctor-init-break", "after-colon"], "\
+class Widget
+{
+public:
+	Widget(int a) :
+		a_(a), b_(2)
+	{}
+	Widget() :
+		first_member_(1), second_member_(2), third_member_(3), fourth_member_(4), fifth_member_(5), sixth_member_(6)
+	{
+		go();
+	}
+	int a_;
+};"),
+            (vec!["--ctor-init-pack", "all"], "\
+class Widget
+{
+public:
+	Widget(int a)
+		: a_(a), b_(2)
+	{}
+	Widget()
+		: first_member_(1), second_member_(2), third_member_(3), fourth_member_(4), fifth_member_(5), sixth_member_(6)
+	{
+		go();
+	}
+	int a_;
+};"),
+            (vec!["--ctor-init-pack", "bin-pack"], "\
+class Widget
+{
+public:
+	Widget(int a)
+		: a_(a), b_(2)
+	{}
+	Widget()
+		: first_member_(1), second_member_(2), third_member_(3), fourth_member_(4), fifth_member_(5),
+		  sixth_member_(6)
+	{
+		go();
+	}
+	int a_;
+};"),
+            (vec!["--ctor-init-pack", "fit"], "\
+class Widget
+{
+public:
+	Widget(int a)
+		: a_(a), b_(2)
+	{}
+	Widget()
+		: first_member_(1),
+		  second_member_(2),
+		  third_member_(3),
+		  fourth_member_(4),
+		  fifth_member_(5),
+		  sixth_member_(6)
+	{
+		go();
+	}
+	int a_;
+};"),
+            (vec!["--ctor-init-pack", "one-per-line"], "\
+class Widget
+{
+public:
+	Widget(int a)
+		: a_(a),
+		  b_(2)
+	{}
+	Widget()
+		: first_member_(1),
+		  second_member_(2),
+		  third_member_(3),
+		  fourth_member_(4),
+		  fifth_member_(5),
+		  sixth_member_(6)
+	{
+		go();
+	}
+	int a_;
+};"),
+            (vec!["-s", "1tbs"], "\
+class Widget {
+public:
+	Widget(int a)
+		: a_(a), b_(2) {}
+	Widget()
+		: first_member_(1), second_member_(2), third_member_(3), fourth_member_(4), fifth_member_(5), sixth_member_(6) {
+		go();
+	}
+	int a_;
+};"),
+            (vec!["-b", "function=attach", "--ctor-init-break", "after-colon"], "\
+class Widget
+{
+public:
+	Widget(int a) :
+		a_(a), b_(2) {}
+	Widget() :
+		first_member_(1), second_member_(2), third_member_(3), fourth_member_(4), fifth_member_(5), sixth_member_(6) {
+		go();
+	}
+	int a_;
+};"),
+            (vec!["-s", "1tbs", "--ctor-init-break", "before-comma", "--ctor-init-pack", "one-per-line"], "\
+class Widget {
+public:
+	Widget(int a)
+		: a_(a)
+		, b_(2) {}
+	Widget()
+		: first_member_(1)
+		, second_member_(2)
+		, third_member_(3)
+		, fourth_member_(4)
+		, fifth_member_(5)
+		, sixth_member_(6) {
+		go();
+	}
+	int a_;
+};"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn namespaces() {
+        let src = "\
+namespace a {
+namespace b {
+int f(int x) { return x; }
+} // namespace b
+int y;
+}
+";
+        let cases = [
+            (vec![], "\
+namespace a
+{
+	namespace b
+	{
+		int f(int x)
+		{
+			return x;
+		}
+	} // namespace b
+
+	int y;
+}"),
+            (vec!["--namespace-indent", "none"], "\
+namespace a
+{
+namespace b
+{
+int f(int x)
+{
+	return x;
+}
+} // namespace b
+
+int y;
+}"),
+            (vec!["--namespace-indent", "inner"], "\
+namespace a
+{
+namespace b
+{
+	int f(int x)
+	{
+		return x;
+	}
+} // namespace b
+
+int y;
+}"),
+            (vec!["--namespace-comments", "add"], "\
+namespace a
+{
+	namespace b
+	{
+		int f(int x)
+		{
+			return x;
+		}
+	} // namespace b
+
+	int y;
+} // namespace a"),
+            (vec!["--namespace-indent", "none", "--namespace-comments", "add"], "\
+namespace a
+{
+namespace b
+{
+int f(int x)
+{
+	return x;
+}
+} // namespace b
+
+int y;
+} // namespace a"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn compact_namespaces() {
+        let src = "\
+namespace a { namespace b { int x; } }
+";
+        let cases = [
+            (vec![], "\
+namespace a
+{
+	namespace b
+	{
+		int x;
+	}
+}"),
+            (vec!["--nested-namespaces", "compact"], "\
+namespace a::b
+{
+	int x;
+}"),
+            (vec!["--nested-namespaces", "compact", "--namespace-comments", "add", "--namespace-indent", "none"], "\
+namespace a::b
+{
+int x;
+} // namespace a::b"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn extern_c_blocks() {
+        let src = "\
+extern \"C\" {
+_Static_assert(sizeof(int) == 4, \"int\");
+static_assert(sizeof(long) == 8, \"long\");
+union u { int a; float b; };
+}
+";
+        let expected = "\
+extern \"C\"
+{
+	_Static_assert(sizeof(int) == 4, \"int\");
+	static_assert(sizeof(long) == 8, \"long\");
+	union u
+	{
+		int a;
+		float b;
+	};
+}";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn access_offsets() {
+        let src = "\
+namespace n {
+class A {
+public:
+    int x;
+private:
+    int y;
+};
+}
+";
+        let cases = [
+            (vec![], "\
+namespace n
+{
+	class A
+	{
+	public:
+		int x;
+
+	private:
+		int y;
+	};
+}"),
+            (vec!["--access-offset", "half"], "\
+namespace n
+{
+	class A
+	{
+	  public:
+		int x;
+
+	  private:
+		int y;
+	};
+}"),
+            (vec!["--access-offset", "none"], "\
+namespace n
+{
+	class A
+	{
+		public:
+		int x;
+
+		private:
+		int y;
+	};
+}"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+
+    fn find_node<'a>(node: Node<'a>, kind: &str) -> Option<Node<'a>> {
+        if node.grammar_name() == kind { return Some(node); }
+        return node.children(&mut node.walk()).find_map(|child| find_node(child, kind));
+    }
+
+    #[test]
+    fn member_order_reports() {
+        let src = "\
+class A {
+public:
+    int f();
+    A();
+    using T = int;
+private:
+    int x;
+};
+";
+        let ast = cpp_format::parse_existing_cpp_file(src.to_string());
+        let members = find_node(ast.root_node(), "field_declaration_list").unwrap();
+        let order = |value: &str| options::get_c_opts(&vec!["--member-order".to_string(), value.to_string()]).member_order.unwrap();
+        assert_eq!(check_member_order(members, order("default"), src.to_string()), vec![
+            "Member order: line 4: `A();` in `A` belongs with the constructors, which should come before the methods.",
+            "Member order: line 5: `using T = int;` in `A` belongs with the types, which should come before the methods.",
+        ]);
+        assert_eq!(check_member_order(members, order("methods,constructors,types,fields"), src.to_string()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unions_and_enums() {
+        let src = "\
+struct point { int x; int y; };
+
+union value {
+    int i;
+    float f;
+};
+
+enum color { RED, GREEN };
+
+int main(void) {
+    union value v;
+    union local { int a; float b; };
+    enum dir { UP, DOWN };
+    return 0;
+}
+";
+        let cases = [
+            (vec![], "\
+struct point
+{
+	int x;
+	int y;
+};
+
+union value
+{
+	int i;
+	float f;
+};
+
+enum color
+{
+	RED,
+	GREEN
+};
+
+int main(void)
+{
+	union value v;
+	union local
+	{
+		int a;
+		float b;
+	};
+	enum dir
+	{
+		UP,
+		DOWN
+	};
+	return 0;
+}"),
+            (vec!["-b", "union=attach", "enum=attach"], "\
+struct point
+{
+	int x;
+	int y;
+};
+
+union value {
+	int i;
+	float f;
+};
+
+enum color {
+	RED,
+	GREEN
+};
+
+int main(void)
+{
+	union value v;
+	union local {
+		int a;
+		float b;
+	};
+	enum dir {
+		UP,
+		DOWN
+	};
+	return 0;
+}"),
+            (vec!["-s", "k&r", "-b", "union=newline"], "\
+struct point {
+	int x;
+	int y;
+};
+
+union value
+{
+	int i;
+	float f;
+};
+
+enum color {
+	RED,
+	GREEN
+};
+
+int main(void)
+{
+	union value v;
+	union local
+	{
+		int a;
+		float b;
+	};
+	enum dir {
+		UP,
+		DOWN
+	};
+	return 0;
+}"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+
+    // Shared by the tests for the named brace styles.
+    const STYLE_SRC: &str = "\
+namespace app {
+struct Point { int x; int y; };
+class Shape {
+public:
+    virtual ~Shape() {}
+};
+int area(int w, int h) {
+    if (w > 0) {
+        return w * h;
+    } else if (h > 0) {
+        return h;
+    } else {}
+    for (int i = 0; i < w; i++) {
+        h += i;
+    }
+    auto f = [](int a) { return a; };
+    return f(0);
+}
+}
+";
+
+    #[test]
+    fn allman_style() {
+        let expected = "\
+namespace app
+{
+	struct Point
+	{
+		int x;
+		int y;
+	};
+
+	class Shape
+	{
+	public:
+		virtual ~Shape()
+		{}
+	};
+
+	int area(int w, int h)
+	{
+		if (w > 0)
+		{
+			return w * h;
+		}
+		else if (h > 0)
+		{
+			return h;
+		}
+		else
+		{}
+		for (int i = 0; i < w; i++)
+		{
+			h += i;
+		}
+		auto f = [](int a)
+		{
+			return a;
+		};
+		return f(0);
+	}
+}";
+        assert_eq!(format_with(STYLE_SRC, &["-s", "allman"]).trim_end(), expected);
+    }
+
+    #[test]
+    fn knr_style() {
+        let expected = "\
+namespace app {
+	struct Point {
+		int x;
+		int y;
+	};
+
+	class Shape {
+	public:
+		virtual ~Shape()
+		{}
+	};
+
+	int area(int w, int h)
+	{
+		if (w > 0) {
+			return w * h;
+		} else if (h > 0) {
+			return h;
+		} else {}
+		for (int i = 0; i < w; i++) {
+			h += i;
+		}
+		auto f = [](int a) {
+			return a;
+		};
+		return f(0);
+	}
+}";
+        assert_eq!(format_with(STYLE_SRC, &["-s", "knr"]).trim_end(), expected);
+    }
+
+    #[test]
+    fn stroustrup_style() {
+        let expected = "\
+namespace app
+{
+	struct Point
+	{
+		int x;
+		int y;
+	};
+
+	class Shape
+	{
+	public:
+		virtual ~Shape()
+		{}
+	};
+
+	int area(int w, int h)
+	{
+		if (w > 0) {
+			return w * h;
+		}
+		else if (h > 0) {
+			return h;
+		}
+		else {}
+		for (int i = 0; i < w; i++) {
+			h += i;
+		}
+		auto f = [](int a) {
+			return a;
+		};
+		return f(0);
+	}
+}";
+        assert_eq!(format_with(STYLE_SRC, &["-s", "stroustrup"]).trim_end(), expected);
+    }
+
+    #[test]
+    fn gnu_style() {
+        let expected = "\
+namespace app
+{
+  struct Point
+  {
+    int x;
+    int y;
+  };
+
+  class Shape
+  {
+  public:
+    virtual ~Shape()
+    {}
+  };
+
+  int area(int w, int h)
+  {
+    if (w > 0)
+      {
+        return w * h;
+      }
+    else if (h > 0)
+      {
+        return h;
+      }
+    else
+      {}
+    for (int i = 0; i < w; i++)
+      {
+        h += i;
+      }
+    auto f = [](int a)
+    {
+      return a;
+    };
+    return f(0);
+  }
+}";
+        assert_eq!(format_with(STYLE_SRC, &["-s", "gnu"]).trim_end(), expected);
+    }
+
+    #[test]
+    fn whitesmiths_style() {
+        let expected = "\
+namespace app
+	{
+	struct Point
+		{
+		int x;
+		int y;
+		};
+
+	class Shape
+		{
+	public:
+		virtual ~Shape()
+			{}
+		};
+
+	int area(int w, int h)
+		{
+		if (w > 0)
+			{
+			return w * h;
+			}
+		else if (h > 0)
+			{
+			return h;
+			}
+		else
+			{}
+		for (int i = 0; i < w; i++)
+			{
+			h += i;
+			}
+		auto f = [](int a)
+			{
+			return a;
+			};
+		return f(0);
+		}
+	}";
+        assert_eq!(format_with(STYLE_SRC, &["-s", "whitesmiths"]).trim_end(), expected);
+    }
+
+    #[test]
+    fn linux_style() {
+        let expected = "\
+namespace app {
+	struct Point {
+		int x;
+		int y;
+	};
+
+	class Shape {
+	public:
+		virtual ~Shape()
+		{}
+	};
+
+	int area(int w, int h)
+	{
+		if (w > 0) {
+			return w * h;
+		} else if (h > 0) {
+			return h;
+		} else {}
+		for (int i = 0; i < w; i++) {
+			h += i;
+		}
+		auto f = [](int a) {
+			return a;
+		};
+		return f(0);
+	}
+}";
+        assert_eq!(format_with(STYLE_SRC, &["-s", "linux"]).trim_end(), expected);
+        // The initializers fit in 100 columns, but not in Linux's 80.
+        let src = "\
+struct Window {
+    Window() : width(640), height(480), title(\"untitled window\"), visible(false), border(2) {}
+};
+";
+        let expected = "\
+struct Window {
+	Window()
+		: width(640),
+		  height(480),
+		  title(\"untitled window\"),
+		  visible(false),
+		  border(2)
+	{}
+};";
+        assert_eq!(format_with(src, &["-s", "linux", "--ctor-init-pack", "fit"]).trim_end(), expected);
+        assert!(format_with(src, &["-s", "knr", "--ctor-init-pack", "fit"]).contains("width(640), height(480)"));
+    }
+
+    #[test]
+    fn one_true_brace_style() {
+        let expected = "\
+namespace app {
+	struct Point {
+		int x;
+		int y;
+	};
+
+	class Shape {
+	public:
+		virtual ~Shape() {}
+	};
+
+	int area(int w, int h) {
+		if (w > 0) {
+			return w * h;
+		} else if (h > 0) {
+			return h;
+		} else {}
+		for (int i = 0; i < w; i++) {
+			h += i;
+		}
+		auto f = [](int a) {
+			return a;
+		};
+		return f(0);
+	}
+}";
+        assert_eq!(format_with(STYLE_SRC, &["-s", "1tbs"]).trim_end(), expected);
+    }
+
+    #[test]
+    fn attribute_placement() {
+        let src = "\
+[[nodiscard]] int compute(int x);
+[[deprecated(\"use compute\")]] int old_compute(int x) {
+    [[maybe_unused]] int y = x;
+    return x;
+}
+[[maybe_unused]] static int counter = 0;
+struct [[gnu::packed]] Header { int tag; };
+";
+        let cases = [
+            (vec![], "\
+[[nodiscard]]
+int compute(int x);
+
+[[deprecated(\"use compute\")]]
+int old_compute(int x)
+{
+	[[maybe_unused]] int y = x;
+	return x;
+}
+
+[[maybe_unused]] static int counter = 0;
+struct [[gnu::packed]] Header
+{
+	int tag;
+};"),
+            (vec!["--attribute-break", "always"], "\
+[[nodiscard]]
+int compute(int x);
+
+[[deprecated(\"use compute\")]]
+int old_compute(int x)
+{
+	[[maybe_unused]]
+	int y = x;
+	return x;
+}
+
+[[maybe_unused]]
+static int counter = 0;
+struct [[gnu::packed]] Header
+{
+	int tag;
+};"),
+            (vec!["--attribute-break", "functions"], "\
+[[nodiscard]]
+int compute(int x);
+
+[[deprecated(\"use compute\")]]
+int old_compute(int x)
+{
+	[[maybe_unused]] int y = x;
+	return x;
+}
+
+[[maybe_unused]] static int counter = 0;
+struct [[gnu::packed]] Header
+{
+	int tag;
+};"),
+            (vec!["--attribute-break", "never"], "\
+[[nodiscard]] int compute(int x);
+
+[[deprecated(\"use compute\")]] int old_compute(int x)
+{
+	[[maybe_unused]] int y = x;
+	return x;
+}
+
+[[maybe_unused]] static int counter = 0;
+struct [[gnu::packed]] Header
+{
+	int tag;
+};"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn template_layout() {
+        let src = "\
+template <typename T> T identity(T x) { return x; }
+template <typename Key, typename Value, typename Allocator> std::map<Key, std::vector<Value>> group_values_by_key(const std::vector<Value>& values);
+template <typename T> struct Box { std::vector<std::vector<T>> rows; };
+";
+        let cases = [
+            (vec![], "\
+template <typename T>
+T identity(T x)
+{
+	return x;
+}
+
+template <typename Key, typename Value, typename Allocator>
+std::map<Key, std::vector<Value>> group_values_by_key(const std::vector<Value>& values);
+
+template <typename T>
+struct Box
+{
+	std::vector<std::vector<T>> rows;
+};"),
+            (vec!["--template-break", "over-limit"], "\
+template <typename T> T identity(T x)
+{
+	return x;
+}
+
+template <typename Key, typename Value, typename Allocator>
+std::map<Key, std::vector<Value>> group_values_by_key(const std::vector<Value>& values);
+
+template <typename T> struct Box
+{
+	std::vector<std::vector<T>> rows;
+};"),
+            (vec!["--template-break", "never"], "\
+template <typename T> T identity(T x)
+{
+	return x;
+}
+
+template <typename Key, typename Value, typename Allocator> std::map<Key, std::vector<Value>> group_values_by_key(const std::vector<Value>& values);
+
+template <typename T> struct Box
+{
+	std::vector<std::vector<T>> rows;
+};"),
+            (vec!["--template-space", "none"], "\
+template<typename T>
+T identity(T x)
+{
+	return x;
+}
+
+template<typename Key, typename Value, typename Allocator>
+std::map<Key, std::vector<Value>> group_values_by_key(const std::vector<Value>& values);
+
+template<typename T>
+struct Box
+{
+	std::vector<std::vector<T>> rows;
+};"),
+            (vec!["--angle-brackets", "spaced"], "\
+template <typename T>
+T identity(T x)
+{
+	return x;
+}
+
+template <typename Key, typename Value, typename Allocator>
+std::map<Key, std::vector<Value> > group_values_by_key(const std::vector<Value>& values);
+
+template <typename T>
+struct Box
+{
+	std::vector<std::vector<T> > rows;
+};"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args).trim_end(), expected, "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use crate::{options, utils};
+
+// Runs an external formatter on `contents` and returns what it produced.
+// The program is started directly with its arguments, so nothing in the path
+// or the template is ever interpreted by a shell. Returns None (after printing
+// why) when the program can't be run or exits with an unexpected code.
+pub fn run_external_formatter(opts: &options::ExternalOpts, path: &String, contents: &String) -> Option<String> {
+    return match opts.mode {
+        utils::ExternalMode::Pipe => run_piped(opts, path, contents),
+        utils::ExternalMode::InPlace => run_in_place(opts, path, contents),
+    };
+}
+
+fn fill_template(args: &[String], path: &str) -> Vec<String> {
+    return args.iter().map(|arg| arg.replace("{path}", path)).collect();
+}
+
+fn run_piped(opts: &options::ExternalOpts, path: &String, contents: &String) -> Option<String> {
+    let child = Command::new(&opts.program)
+        .args(fill_template(&opts.args, path))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Error running '{}': {}", opts.program, e);
+            return None;
+        },
+    };
+    // Written from another thread so a program that answers before reading
+    // all of its input can't fill the stdout pipe and deadlock.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = contents.clone();
+    let writer = std::thread::spawn(move || { _ = stdin.write_all(input.as_bytes()); });
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Error running '{}': {}", opts.program, e);
+            return None;
+        },
+    };
+    _ = writer.join();
+    if !exited_as_expected(opts, output.status.code(), &output.stderr) { return None; }
+    return match String::from_utf8(output.stdout) {
+        Ok(result) => Some(result),
+        Err(_) => {
+            eprintln!("'{}' did not produce valid UTF-8.", opts.program);
+            None
+        },
+    };
+}
+
+// The program rewrites a temporary copy of the file, so the original is only
+// touched by `-w`, the same as with the built-in formatters.
+fn run_in_place(opts: &options::ExternalOpts, path: &String, contents: &String) -> Option<String> {
+    let file_name = std::path::Path::new(path).file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+    let temp_path = std::env::temp_dir().join(format!("blfmt-{}-{}", std::process::id(), file_name));
+    let temp_path = temp_path.to_string_lossy().to_string();
+    if let Err(e) = utils::write_file(&temp_path, contents.as_bytes()) {
+        eprintln!("Error creating '{}': {}", temp_path, e);
+        return None;
+    }
+    let mut args = fill_template(&opts.args, &temp_path);
+    if !opts.args.iter().any(|arg| arg.contains("{path}")) { args.push(temp_path.clone()); }
+    let output = Command::new(&opts.program).args(args).stdin(Stdio::null()).output();
+    let mut result = None;
+    match output {
+        Ok(output) => {
+            if exited_as_expected(opts, output.status.code(), &output.stderr) {
+                let mut text = String::new();
+                match std::fs::File::open(&temp_path).and_then(|mut file| file.read_to_string(&mut text)) {
+                    Ok(_) => result = Some(text),
+                    Err(e) => eprintln!("Error reading '{}': {}", temp_path, e),
+                }
+            }
+        },
+        Err(e) => eprintln!("Error running '{}': {}", opts.program, e),
+    }
+    _ = std::fs::remove_file(&temp_path);
+    return result;
+}
+
+fn exited_as_expected(opts: &options::ExternalOpts, code: Option<i32>, stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr);
+    let expected = code.map(|code| opts.exit_codes.contains(&code)).unwrap_or(false);
+    if !expected {
+        match code {
+            Some(code) => eprintln!("'{}' exited with code {}:\n{}", opts.program, code, stderr.trim_end()),
+            None => eprintln!("'{}' was terminated by a signal:\n{}", opts.program, stderr.trim_end()),
+        }
+    }
+    else if stderr.trim() != "" {
+        eprintln!("{}", stderr.trim_end());
+    }
+    return expected;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn external(template: &str, mode: utils::ExternalMode, exit_codes: Vec<i32>) -> options::ExternalOpts {
+        let mut words = template.split_whitespace().map(|x| x.to_string());
+        let program = words.next().unwrap();
+        return options::ExternalOpts { program, args: words.collect(), mode, exit_codes };
+    }
+
+    #[test]
+    fn piped_programs() {
+        let opts = external("tr a-z A-Z", utils::ExternalMode::Pipe, vec![0]);
+        let result = run_external_formatter(&opts, &"a.lua".to_string(), &"local x = 1\n".to_string());
+        assert_eq!(result, Some("LOCAL X = 1\n".to_string()));
+    }
+
+    #[test]
+    fn in_place_programs() {
+        // the path of the temporary copy is appended when the template has no `{path}`
+        let opts = external("sed -i s/x/y/", utils::ExternalMode::InPlace, vec![0]);
+        let result = run_external_formatter(&opts, &"a.lua".to_string(), &"local x = 1\n".to_string());
+        assert_eq!(result, Some("local y = 1\n".to_string()));
+        let opts = external("sed -i s/1/2/ {path}", utils::ExternalMode::InPlace, vec![0]);
+        let result = run_external_formatter(&opts, &"a.lua".to_string(), &"local x = 1\n".to_string());
+        assert_eq!(result, Some("local x = 2\n".to_string()));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let contents = "x\n".to_string();
+        assert_eq!(run_external_formatter(&external("false", utils::ExternalMode::Pipe, vec![0]), &"a.js".to_string(), &contents), None);
+        assert_eq!(run_external_formatter(&external("false", utils::ExternalMode::Pipe, vec![0, 1]), &"a.js".to_string(), &contents), Some(String::new()));
+        assert_eq!(run_external_formatter(&external("blfmt-no-such-program", utils::ExternalMode::Pipe, vec![0]), &"a.js".to_string(), &contents), None);
+    }
+}
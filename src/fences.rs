@@ -0,0 +1,161 @@
+use regex::Regex;
+use crate::{options, utils, c_format, cpp_format, c_ast, cpp_ast};
+
+// Fenced code blocks in Markdown and txt files that are tagged with a language
+// the C/C++ formatters handle get formatted with the configured style.
+
+// The file extension a fence's info string stands for, or None when the block isn't C/C++.
+fn fence_language(info: &str) -> Option<&'static str> {
+    let language = info.split_whitespace().next().unwrap_or("").trim_start_matches('{').trim_start_matches('.').to_lowercase();
+    return match language.as_str() {
+        "c" | "h" => Some(".c"),
+        "cpp" | "c++" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(".cpp"),
+        "cuda" | "cu" | "cuh" => Some(".cu"),
+        "opencl" | "cl" => Some(".cl"),
+        _ => None,
+    };
+}
+
+// Formats the code of a fenced block, or returns None when the block isn't C/C++.
+// A block that doesn't parse is kept as it is, and the error is the report to print.
+pub fn format_code_block(info: &str, code: &str, opts: options::COpts, line: usize) -> Result<Option<String>, String> {
+    let ext = match fence_language(info) {
+        Some(ext) => ext,
+        None => return Ok(None),
+    };
+    if code.trim().is_empty() { return Ok(None); }
+    let is_c = ext == ".c" || ext == ".cl";
+    let mut masked = code.to_string();
+    if utils::is_gpu_source(ext) { masked = utils::mask_gpu_qualifiers(&masked, is_c); }
+    let ast = if is_c { c_format::parse_existing_c_file(masked.clone()) }
+              else { cpp_format::parse_existing_cpp_file(masked.clone()) };
+    if ast.root_node().has_error() {
+        return Err(format!("The '{}' code block at line {} has syntax errors, so it was left as it is.", info.trim(), line));
+    }
+    let mut result = if is_c { c_ast::traverse_c_ast(ast, masked, opts) }
+                     else { cpp_ast::traverse_cpp_ast(ast, masked, opts) };
+    if utils::is_gpu_source(ext) { result = utils::unmask_gpu_qualifiers(&result); }
+    return Ok(Some(result.trim_matches('\n').to_string()));
+}
+
+// Splits text into (is_code, text) pieces, where the code pieces are whole
+// fenced blocks (opening and closing fence included).
+pub fn split_fenced_blocks(text: &str) -> Vec<(bool, String)> {
+    let opening = Regex::new(r"^(\s*)(`{3,}|~{3,})").unwrap();
+    let mut result = Vec::<(bool, String)>::new();
+    let mut current = String::new();
+    let mut fence: Option<String> = None;
+    for line in text.split_inclusive('\n') {
+        match &fence {
+            None => {
+                if let Some(captures) = opening.captures(line) {
+                    if current.trim() != "" { result.push((false, current.clone())); }
+                    current = String::new();
+                    fence = Some(captures[2].to_string());
+                }
+                current += line;
+            },
+            Some(marker) => {
+                current += line;
+                let trimmed = line.trim();
+                let closes = trimmed.starts_with(marker.as_str()) && trimmed.chars().all(|c| c == marker.chars().next().unwrap());
+                if closes {
+                    result.push((true, current.clone()));
+                    current = String::new();
+                    fence = None;
+                }
+            },
+        }
+    }
+    if current.trim() != "" { result.push((fence.is_some(), current)); }
+    return result;
+}
+
+// Formats a whole fenced block found by `split_fenced_blocks`, keeping the
+// indentation of its fence. Blocks that aren't C/C++ come back unchanged.
+pub fn format_fenced_block(block: &str, opts: options::COpts, line: usize) -> String {
+    let lines = block.trim_end().split('\n').collect::<Vec<&str>>();
+    let indent = &lines[0][..lines[0].len() - lines[0].trim_start().len()];
+    let info = lines[0].trim_start().trim_start_matches(['`', '~']);
+    let is_closed = lines.len() > 1 && opening_marker(lines[lines.len() - 1]) == opening_marker(lines[0]);
+    if !is_closed { return block.trim_end().to_string(); }
+    let code = lines[1..lines.len() - 1].iter().map(|x| x.strip_prefix(indent).unwrap_or(x.trim_start()))
+        .collect::<Vec<&str>>().join("\n");
+    let formatted = match format_code_block(info, &code, opts, line + 1) {
+        Ok(Some(formatted)) => formatted,
+        Ok(None) => return block.trim_end().to_string(),
+        Err(report) => {
+            eprintln!("{}", report);
+            return block.trim_end().to_string();
+        },
+    };
+    let mut result = vec![lines[0].to_string()];
+    for code_line in formatted.split('\n') {
+        if code_line.is_empty() { result.push(String::new()); }
+        else { result.push(format!("{}{}", indent, code_line)); }
+    }
+    result.push(lines[lines.len() - 1].to_string());
+    return result.join("\n");
+}
+
+fn opening_marker(line: &str) -> char {
+    return line.trim_start().chars().next().unwrap_or(' ');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Formats every fenced block of a txt file, the way `format_txt_file` does.
+    fn format_blocks(text: &str) -> Vec<String> {
+        let mut result = Vec::<String>::new();
+        let mut line = 0;
+        for (is_code, segment) in split_fenced_blocks(text) {
+            if is_code { result.push(format_fenced_block(&segment, options::COpts::default(), line)); }
+            line += segment.matches('\n').count();
+        }
+        return result;
+    }
+
+    #[test]
+    fn fenced_blocks_in_text() {
+        let text = "\
+Design notes.
+
+```c
+int add(int a,int b){return a+b;}
+```
+
+    ```cpp
+    namespace n { int f() { return 1; } }
+    ```
+
+```c
+int broken( {
+```
+
+~~~python
+x = [1,2]
+~~~
+";
+        let expected = [
+            "```c\nint add(int a, int b)\n{\n\treturn a + b;\n}\n```",
+            "    ```cpp\n    namespace n\n    {\n    \tint f()\n    \t{\n    \t\treturn 1;\n    \t}\n    }\n    ```",
+            "```c\nint broken( {\n```",
+            "~~~python\nx = [1,2]\n~~~",
+        ];
+        assert_eq!(format_blocks(text), expected);
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let opts = options::COpts::default();
+        assert_eq!(
+            format_code_block("c", "int broken( {\n", opts, 9),
+            Err("The 'c' code block at line 9 has syntax errors, so it was left as it is.".to_string()),
+        );
+        assert_eq!(format_code_block("cpp", "int f() { return 1; }\n", opts, 1), Ok(Some("int f()\n{\n\treturn 1;\n}".to_string())));
+        assert_eq!(format_code_block("python", "x = [1,2]\n", opts, 1), Ok(None));
+        assert_eq!(format_code_block("c", "\n", opts, 1), Ok(None));
+    }
+}
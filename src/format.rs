@@ -1,70 +1,197 @@
-use crate::{group, options, utils, utils::StringUtils, c_format, cpp_format, txt_format, c_ast, cpp_ast};
+use crate::{group, options, utils, utils::StringUtils, c_format, cpp_format, txt_format, c_ast, cpp_ast, go_format, go_ast, py_format, py_ast, md_format, md_ast, json_format, json_ast, toml_format, toml_ast, yaml_format, yaml_ast, sh_format, sh_ast, rs_format, rs_ast, js_format, js_ast, cmake_format, make_format, fences, external};
 use regex::Regex;
 use std::fs::{self, File};
 use std::io::Write;
-use std::process::Command;
 
-pub fn format_c_file(path: String, style: utils::Style, write_arg: bool, text: String) {
-    let ast = if text.len() > 0 { c_format::parse_existing_c_file(text.clone()) }
-              else { c_format::parse_c_file(path.clone()) };
+pub fn format_c_file(path: String, opts: options::COpts, mode: utils::OutputMode, text: String) {
     let contents = std::fs::read_to_string(&path).unwrap_or(text);
-    if contents == "".to_string() {
+    if contents.is_empty() {
         println!("'{}' not found.", path);
         return;
     }
-    let result = c_ast::traverse_c_ast(ast, contents, style);
-    if write_arg { utils::write_results(&path, result); }
-    else { utils::print_results(&result); }
+    let is_gpu_source = utils::is_gpu_source(&path);
+    let mut masked = contents.clone();
+    if is_gpu_source { masked = utils::mask_gpu_qualifiers(&masked, true); }
+    let ast = c_format::parse_existing_c_file(masked.clone());
+    let mut result = c_ast::traverse_c_ast(ast, masked, opts);
+    if is_gpu_source { result = utils::unmask_gpu_qualifiers(&result); }
+    utils::emit_results(&path, &contents, result, mode);
 }
 
-pub fn format_cpp_file(path: String, style: utils::Style, write_arg: bool, text: String) {
-    let ast = if text.len() > 0 { cpp_format::parse_existing_cpp_file(text.clone()) }
-              else { cpp_format::parse_cpp_file(path.clone()) };
+pub fn format_cpp_file(path: String, opts: options::COpts, mode: utils::OutputMode, text: String) {
     let contents = std::fs::read_to_string(&path).unwrap_or(text);
-    if contents == "".to_string() {
+    if contents.is_empty() {
         println!("'{}' not found.", path);
         return;
     }
-    let result = cpp_ast::traverse_cpp_ast(ast, contents, style);
-    if write_arg { utils::write_results(&path, result); }
-    else { utils::print_results(&result); }
+    let is_gpu_source = utils::is_gpu_source(&path);
+    let mut masked = contents.clone();
+    if is_gpu_source { masked = utils::mask_gpu_qualifiers(&masked, false); }
+    let ast = cpp_format::parse_existing_cpp_file(masked.clone());
+    let mut result = cpp_ast::traverse_cpp_ast(ast, masked, opts);
+    if is_gpu_source { result = utils::unmask_gpu_qualifiers(&result); }
+    utils::emit_results(&path, &contents, result, mode);
 }
 
-pub fn format_py_file(path: String) {
-    let command = utils::sanitize(format!("black {}", path));
-    let res = Command::new("sh").arg("-c").arg(command).output().unwrap();
-    let res = String::from_utf8(res.stderr).unwrap_or_default();
-    println!("{}", res);
+pub fn format_py_file(path: String, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let ast = py_format::parse_existing_py_file(contents.clone());
+    let result = py_ast::traverse_py_ast(ast, contents.clone());
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_go_file(path: String, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let ast = go_format::parse_existing_go_file(contents.clone());
+    let result = go_ast::traverse_go_ast(ast, contents.clone());
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_json_file(path: String, opts: options::JsonOpts, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let ast = json_format::parse_existing_json_file(contents.clone());
+    let result = json_ast::traverse_json_ast(ast, contents.clone(), opts);
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_toml_file(path: String, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let ast = toml_format::parse_existing_toml_file(contents.clone());
+    let result = toml_ast::traverse_toml_ast(ast, contents.clone());
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_yaml_file(path: String, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let ast = yaml_format::parse_existing_yaml_file(contents.clone());
+    let result = yaml_ast::traverse_yaml_ast(ast, contents.clone());
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_sh_file(path: String, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let ast = sh_format::parse_existing_sh_file(contents.clone());
+    let result = sh_ast::traverse_sh_ast(ast, contents.clone());
+    utils::emit_results(&path, &contents, result, mode);
 }
 
-pub fn format_go_file(path: String) {
-    let command = utils::sanitize(format!("gofmt -w {}", path));
-    let res = Command::new("sh").arg("-c").arg(command).output().unwrap();
-    let err = String::from_utf8(res.stderr).unwrap_or_default();
-    if err.as_str() != "" {
-        eprintln!("Error running gofmt:\n{}", err);
+pub fn format_rs_file(path: String, opts: options::COpts, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
     }
-    else {
-        println!("Go file formatted successfully!");
+    let ast = rs_format::parse_existing_rs_file(contents.clone());
+    let result = rs_ast::traverse_rs_ast(ast, contents.clone(), opts);
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_js_file(path: String, file_type: String, opts: options::JsOpts, c_opts: options::COpts, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let ast = js_format::parse_existing_js_file(contents.clone(), &file_type);
+    let result = js_ast::traverse_js_ast(ast, contents.clone(), opts, c_opts);
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_cmake_file(path: String, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let result = cmake_format::format_cmake(contents.clone());
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_makefile(path: String, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let result = make_format::format_makefile(contents.clone());
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_external_file(path: String, opts: options::ExternalOpts, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
+    }
+    let result = match external::run_external_formatter(&opts, &path, &contents) {
+        Some(result) => result,
+        None => std::process::exit(1),
+    };
+    utils::emit_results(&path, &contents, result, mode);
+}
+
+pub fn format_md_file(path: String, opts: options::TxtOpts, c_opts: options::COpts, mode: utils::OutputMode, text: String) {
+    let contents = std::fs::read_to_string(&path).unwrap_or(text);
+    if contents.is_empty() {
+        println!("'{}' not found.", path);
+        return;
     }
+    let ast = md_format::parse_existing_md_file(contents.clone());
+    let result = md_ast::traverse_md_ast(ast, contents.clone(), opts, c_opts);
+    utils::emit_results(&path, &contents, result, mode);
 }
 
-pub fn format_txt_file(path: String, opts: options::TxtOpts, opt_titles: &[String], write_arg: bool) {
+pub fn format_txt_file(path: String, opts: options::TxtOpts, c_opts: options::COpts, opt_titles: &[String], mode: utils::OutputMode) {
     let path_clone = path.clone();
     let file_contents = fs::read_to_string(&path).unwrap();
     let result = String::new();
     let (cols, spacing) = (opts.columns, opts.spacing);
-    let paragraphs = group::group_paragraphs(&file_contents, opt_titles);
     let mut result = Vec::<String>::new();
-    for paragraph in paragraphs {
-        let temp_para = txt_format::format_paragraph(paragraph, opts);
-        result.push(temp_para);
+    // fenced code blocks are kept out of the paragraphs, and formatted when they are C/C++
+    let mut line = 0;
+    let segments = fences::split_fenced_blocks(&file_contents);
+    let has_code = segments.iter().any(|(is_code, _)| *is_code);
+    for (is_code, segment) in segments {
+        if is_code { result.push(fences::format_fenced_block(&segment, c_opts, line)); }
+        else {
+            // the blank lines around a code block are the separator's job
+            let text = if has_code { segment.trim_matches('\n').to_string() } else { segment.clone() };
+            let paragraphs = group::group_paragraphs(&text, opt_titles);
+            for paragraph in paragraphs {
+                let temp_para = txt_format::format_paragraph(paragraph, opts);
+                result.push(temp_para);
+            }
+        }
+        line += segment.matches('\n').count();
     }
     let mut sep = "\n".to_string();
     for _ in 0..opts.spacing {
         sep += "\n";
     }
     let paragraphs = result.join(sep.as_str());
-    if write_arg { utils::write_results(&path, paragraphs); }
-    else { utils::print_results(&paragraphs); }
+    utils::emit_results(&path, &file_contents, paragraphs, mode);
 }
@@ -0,0 +1,722 @@
+use tree_sitter::{Tree, Node};
+use std::collections::HashSet;
+
+// gofmt keeps the line structure of the source, so instead of rebuilding every
+// statement the Go formatter walks the tokens of the tree: it normalizes the
+// spacing between the tokens of a line, recomputes the indentation of every
+// line and aligns the columns gofmt aligns (struct fields, grouped const/var
+// specs, keyed elements and trailing comments).
+
+// Separates the cells of a line that get aligned with the lines around it.
+const CELL: char = '\u{b}';
+const CELLS: [&str; 4] = ["", "\u{b}", "\u{b}\u{b}", "\u{b}\u{b}\u{b}"];
+
+// Nodes printed as they are, without looking at their children.
+const ATOMIC_NODES: [&str; 4] = ["interpreted_string_literal", "raw_string_literal", "rune_literal", "comment"];
+
+// Nodes whose children are statements, so a ';' in them only separates statements.
+const STATEMENT_CONTAINERS: [&str; 6] = ["source_file", "block", "expression_case", "default_case", "type_case", "communication_case"];
+
+const CASE_NODES: [&str; 4] = ["expression_case", "default_case", "type_case", "communication_case"];
+
+const FUNC_NODES: [&str; 3] = ["function_declaration", "method_declaration", "func_literal"];
+
+struct GoLine<'a> {
+    tokens: Vec<Node<'a>>,
+    blank_before: bool,
+}
+
+pub fn traverse_go_ast(ast: Tree, src: String) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the Go file was left as it is.");
+        return src;
+    }
+    let mut tokens = Vec::<Node>::new();
+    collect_tokens(root, &src, &mut tokens);
+    let mut forced_breaks = HashSet::<usize>::new();
+    find_forced_breaks(root, &mut forced_breaks);
+    let mut tight = HashSet::<usize>::new();
+    mark_binary_spacing(root, 1, &mut tight);
+    let lines = split_into_lines(&tokens, &src, &forced_breaks);
+    let indents = compute_indentation(&lines, &src);
+    let mut result = Vec::<String>::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.blank_before && result.len() > 0 { result.push(String::new()); }
+        let mut text = "\t".repeat(indents[i]);
+        for (j, token) in line.tokens.iter().enumerate() {
+            if j > 0 { text += get_separator(line.tokens[j - 1], *token, &src, &tight); }
+            text += get_text(*token, &src);
+        }
+        result.push(text);
+    }
+    let result = sort_imports(result);
+    let result = align_cells(result);
+    let mut result = result.join("\n");
+    result = result.trim_end().to_string();
+    result.push('\n');
+    return result;
+}
+
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    return node.utf8_text(src.as_bytes()).unwrap_or("");
+}
+
+fn parent_kind(node: Node) -> &'static str {
+    return match node.parent() {
+        Some(parent) => parent.kind(),
+        None => "",
+    };
+}
+
+fn collect_tokens<'a>(node: Node<'a>, src: &str, tokens: &mut Vec<Node<'a>>) {
+    if node.child_count() == 0 || ATOMIC_NODES.contains(&node.kind()) {
+        let text = get_text(node, src);
+        if text.trim() != "" { tokens.push(node); }
+        return;
+    }
+    for child in node.children(&mut node.walk()) {
+        collect_tokens(child, src, tokens);
+    }
+}
+
+// gofmt expands one-line blocks and case clauses, except for function bodies.
+fn find_forced_breaks(node: Node, forced_breaks: &mut HashSet<usize>) {
+    match node.kind() {
+        "block" => {
+            let keeps_one_line = FUNC_NODES.contains(&parent_kind(node));
+            let count = node.child_count();
+            if !keeps_one_line && count >= 2 {
+                let open = node.child(0).unwrap();
+                let close = node.child(count - 1).unwrap();
+                if count > 2 { forced_breaks.insert(node.child(1).unwrap().id()); }
+                if open.start_position().row == close.start_position().row {
+                    forced_breaks.insert(close.id());
+                }
+            }
+        },
+        kind if CASE_NODES.contains(&kind) => {
+            let mut after_colon = false;
+            for child in node.children(&mut node.walk()) {
+                if after_colon {
+                    forced_breaks.insert(child.id());
+                    break;
+                }
+                if child.kind() == ":" { after_colon = true; }
+            }
+        },
+        _ => {},
+    }
+    for child in node.children(&mut node.walk()) {
+        find_forced_breaks(child, forced_breaks);
+    }
+}
+
+fn first_token(node: Node) -> Node {
+    let mut node = node;
+    while node.child_count() > 0 && !ATOMIC_NODES.contains(&node.kind()) {
+        node = node.child(0).unwrap();
+    }
+    return node;
+}
+
+// Function bodies written on one line stay on one line, so the semicolons
+// between their statements are kept.
+fn is_one_line_func_body(node: Node) -> bool {
+    return match node.parent() {
+        Some(block) => block.kind() == "block" && !is_multiline(block) && FUNC_NODES.contains(&parent_kind(block)),
+        None => false,
+    };
+}
+
+fn split_into_lines<'a>(tokens: &[Node<'a>], src: &str, forced_breaks: &HashSet<usize>) -> Vec<GoLine<'a>> {
+    let mut lines = Vec::<GoLine>::new();
+    let mut current = GoLine { tokens: Vec::new(), blank_before: false };
+    let mut last_row: Option<usize> = None;
+    let mut break_next = false;
+    for token in tokens {
+        let text = get_text(*token, src);
+        // gofmt drops the semicolons that only end statements and puts every statement on its own line.
+        if text == ";" && STATEMENT_CONTAINERS.contains(&parent_kind(*token)) {
+            if !is_one_line_func_body(*token) { break_next = true; }
+            else if current.tokens.len() > 0 { current.tokens.push(*token); }
+            continue;
+        }
+        let row = token.start_position().row;
+        let mut new_line = break_next || last_row.is_none() || row > last_row.unwrap();
+        if forced_breaks.contains(&token.id()) {
+            new_line = true;
+        }
+        else {
+            let mut ancestor = Some(*token);
+            while ancestor.is_some() && !new_line {
+                let node = ancestor.unwrap();
+                if node.start_byte() != token.start_byte() { break; }
+                if forced_breaks.contains(&node.id()) { new_line = true; }
+                ancestor = node.parent();
+            }
+        }
+        if new_line && current.tokens.len() > 0 {
+            lines.push(current);
+            let blank = last_row.is_some() && row > last_row.unwrap() + 1;
+            current = GoLine { tokens: Vec::new(), blank_before: blank };
+        }
+        current.tokens.push(*token);
+        last_row = Some(token.end_position().row);
+        break_next = false;
+    }
+    if current.tokens.len() > 0 { lines.push(current); }
+    return lines;
+}
+
+fn is_opener(text: &str) -> bool {
+    return text == "(" || text == "[" || text == "{";
+}
+
+fn is_closer(text: &str) -> bool {
+    return text == ")" || text == "]" || text == "}";
+}
+
+fn ends_with_continuation(token: Node, src: &str) -> bool {
+    if token.is_named() { return false; }
+    let text = get_text(token, src);
+    return match parent_kind(token) {
+        "binary_expression" => true,
+        "assignment_statement" | "short_var_declaration" | "var_spec" | "const_spec" => text != ",",
+        "selector_expression" => text == ".",
+        _ => false,
+    };
+}
+
+fn compute_indentation(lines: &[GoLine], src: &str) -> Vec<usize> {
+    let mut indents = Vec::<usize>::new();
+    // the line on which each unclosed bracket was opened
+    let mut open_lines = Vec::<usize>::new();
+    for (i, line) in lines.iter().enumerate() {
+        let mut k = 0;
+        while k < line.tokens.len() && is_closer(get_text(line.tokens[k], src)) {
+            open_lines.pop();
+            k += 1;
+        }
+        let mut distinct = open_lines.clone();
+        distinct.dedup();
+        let mut indent = distinct.len();
+        let first = line.tokens[0];
+        let is_case = CASE_NODES.contains(&parent_kind(first)) && first.prev_sibling().is_none();
+        let is_label = parent_kind(first) == "labeled_statement" && first.prev_sibling().is_none();
+        if (is_case || is_label) && indent > 0 { indent -= 1; }
+        if i > 0 {
+            let previous = &lines[i - 1];
+            let last = previous.tokens[previous.tokens.len() - 1];
+            let opened_on_previous = open_lines.last() == Some(&(i - 1));
+            if ends_with_continuation(last, src) && !opened_on_previous { indent += 1; }
+        }
+        indents.push(indent);
+        for token in &line.tokens[k..] {
+            let text = get_text(*token, src);
+            if is_opener(text) { open_lines.push(i); }
+            else if is_closer(text) { open_lines.pop(); }
+        }
+    }
+    return indents;
+}
+
+fn starts_with_word(text: &str) -> bool {
+    return match text.chars().next() {
+        Some(c) => c.is_alphanumeric() || c == '_' || c == '"' || c == '`' || c == '\'',
+        None => false,
+    };
+}
+
+fn is_keyword(node: Node, src: &str) -> bool {
+    return !node.is_named() && starts_with_word(get_text(node, src));
+}
+
+fn is_unary_operator(node: Node) -> bool {
+    if node.is_named() { return false; }
+    let parent = match node.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    return match parent.kind() {
+        "unary_expression" | "pointer_type" => parent.child(0) == Some(node),
+        _ => false,
+    };
+}
+
+fn is_multiline(node: Node) -> bool {
+    return node.start_position().row != node.end_position().row;
+}
+
+// Whether a keyed element starts its own line inside a multi-line literal,
+// which is when gofmt aligns its value with the elements around it.
+fn keyed_element_is_alone(element: Node) -> bool {
+    let literal = match element.parent() {
+        Some(literal) => literal,
+        None => return false,
+    };
+    if !is_multiline(literal) || is_multiline(element) { return false; }
+    let row = element.start_position().row;
+    let previous_on_row = match element.prev_named_sibling() {
+        Some(previous) => previous.end_position().row == row,
+        None => literal.start_position().row == row,
+    };
+    let next_on_row = match element.next_named_sibling() {
+        Some(next) => next.start_position().row == row,
+        None => false,
+    };
+    return !previous_on_row && !next_on_row;
+}
+
+// Whether the spec is part of a parenthesized group, in which gofmt aligns
+// the types and values of consecutive specs.
+fn spec_is_grouped(spec: Node) -> bool {
+    let parent = match spec.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    if parent.kind() == "var_spec_list" { return true; }
+    for child in parent.children(&mut parent.walk()) {
+        if child.kind() == "(" { return true; }
+    }
+    return false;
+}
+
+fn starts_field(token: Node, parent: Node, field: &str) -> bool {
+    return match parent.child_by_field_name(field) {
+        Some(node) => node.start_byte() == token.start_byte(),
+        None => false,
+    };
+}
+
+fn ancestor_of_kind<'a>(node: Node<'a>, kinds: &[&str]) -> Option<Node<'a>> {
+    let mut current = node.parent();
+    while current.is_some() {
+        let parent = current.unwrap();
+        if kinds.contains(&parent.kind()) { return Some(parent); }
+        current = parent.parent();
+    }
+    return None;
+}
+
+// Mirrors gofmt's keepTypeColumn: in a run of grouped specs with values, the
+// type column is kept for all of them when one of them has a type.
+fn keep_type_column(spec: Node) -> bool {
+    let parent = match spec.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let specs = parent.named_children(&mut parent.walk()).filter(|x| x.kind() == spec.kind()).collect::<Vec<Node>>();
+    let index = match specs.iter().position(|x| x.id() == spec.id()) {
+        Some(index) => index,
+        None => return false,
+    };
+    if !spec_columns(spec).1 { return false; }
+    let (mut start, mut end) = (index, index + 1);
+    while start > 0 && spec_columns(specs[start - 1]).1 { start -= 1; }
+    while end < specs.len() && spec_columns(specs[end]).1 { end += 1; }
+    return specs[start..end].iter().any(|x| spec_columns(*x).0);
+}
+
+// Whether a const/var spec has a type and a value.
+fn spec_columns(spec: Node) -> (bool, bool) {
+    let has_type = spec.child_by_field_name("type").is_some();
+    let has_value = spec.children(&mut spec.walk()).any(|x| x.kind() == "=");
+    return (has_type, has_value);
+}
+
+// gofmt writes three cell breaks before the trailing comment of a grouped spec
+// whatever columns it has, so the comments of specs without a type or a value
+// line up with the others; the empty columns take no room.
+fn comment_cells(a: Node) -> usize {
+    let spec = match ancestor_of_kind(a, &["const_spec", "var_spec"]) {
+        Some(spec) => spec,
+        None => return 1,
+    };
+    if !spec_is_grouped(spec) || is_multiline(spec) { return 1; }
+    let (has_type, has_value) = spec_columns(spec);
+    let type_column = has_type || keep_type_column(spec);
+    return 3 - type_column as usize - has_value as usize;
+}
+
+// Cells before the type and the tag of a field and before the type and the
+// value of a grouped const/var spec.
+fn starts_aligned_cell(a: Node, b: Node, src: &str) -> bool {
+    if let Some(field) = ancestor_of_kind(b, &["field_declaration"]) {
+        let list = field.parent().unwrap();
+        if !is_multiline(list) || is_multiline(field) || field.child_by_field_name("name").is_none() { return false; }
+        let a_in_field = a.start_byte() >= field.start_byte();
+        return a_in_field && (starts_field(b, field, "type") || starts_field(b, field, "tag"));
+    }
+    if let Some(spec) = ancestor_of_kind(b, &["const_spec", "var_spec"]) {
+        if !spec_is_grouped(spec) || is_multiline(spec) { return false; }
+        let a_in_spec = a.start_byte() >= spec.start_byte();
+        let is_value = get_text(b, src) == "=" && b.parent() == Some(spec);
+        return a_in_spec && (is_value || starts_field(b, spec, "type"));
+    }
+    return false;
+}
+
+fn get_separator(a: Node, b: Node, src: &str, tight: &HashSet<usize>) -> &'static str {
+    let (ta, tb) = (get_text(a, src), get_text(b, src));
+    let (pa, pb) = (parent_kind(a), parent_kind(b));
+    if b.kind() == "comment" { return CELLS[comment_cells(a)]; }
+    if a.kind() == "comment" || ta == "," || ta == ";" { return " "; }
+    if tb == "," || tb == ";" || tb == ")" || tb == "]" { return ""; }
+    if tb == ":" { return ""; }
+    if ta == ":" {
+        if pa == "slice_expression" { return ""; }
+        if pa == "keyed_element" && keyed_element_is_alone(a.parent().unwrap()) { return "\u{b}"; }
+        return " ";
+    }
+    if ta == "(" || ta == "[" { return ""; }
+    if ta == "{" {
+        if tb == "}" || pa == "literal_value" { return ""; }
+        return " ";
+    }
+    if tb == "}" {
+        if pb == "literal_value" { return ""; }
+        return " ";
+    }
+    if tb == "{" {
+        if pb == "literal_value" { return ""; }
+        // one-line struct and interface types keep the brace on the keyword: struct{ a int }
+        if (ta == "struct" || ta == "interface") && !is_multiline(b.parent().unwrap()) { return ""; }
+        return " ";
+    }
+    if ta == "." || tb == "." { return ""; }
+    if tb == "..." { return if pb == "variadic_parameter_declaration" { " " } else { "" }; }
+    if ta == "..." { return ""; }
+    if tb == "++" || tb == "--" { return ""; }
+    if is_unary_operator(a) { return ""; }
+    if pa == "channel_type" || pb == "channel_type" {
+        if (ta == "chan" && tb == "<-") || (ta == "<-" && tb == "chan") { return ""; }
+        return " ";
+    }
+    if !a.is_named() && pa == "binary_expression" {
+        return if tight.contains(&a.parent().unwrap().id()) { "" } else { " " };
+    }
+    if !b.is_named() && pb == "binary_expression" {
+        return if tight.contains(&b.parent().unwrap().id()) { "" } else { " " };
+    }
+    if tb == "(" {
+        if ta == "func" { return if pb == "parameter_list" && pa == "method_declaration" { " " } else { "" }; }
+        if is_keyword(a, src) { return " "; }
+        if ta == ")" { return if pb == "parameter_list" { " " } else { "" }; }
+        if a.is_named() || ta == "]" || ta == "}" { return ""; }
+        return " ";
+    }
+    if tb == "[" {
+        if ["index_expression", "slice_expression", "type_arguments", "type_parameter_list", "generic_type"].contains(&pb) {
+            return "";
+        }
+        if ta == "]" || ta == "map" || ta == "*" { return ""; }
+        if is_keyword(a, src) || ta == ")" || a.is_named() { return " "; }
+        return " ";
+    }
+    if ta == "]" {
+        if ["slice_type", "array_type", "map_type", "implicit_length_array_type"].contains(&pa) { return ""; }
+        if pa == "type_arguments" { return ""; }
+        return " ";
+    }
+    if starts_aligned_cell(a, b, src) {
+        // an empty type cell keeps the values of a run lined up with a typed spec
+        let empty_type = get_text(b, src) == "=" && b.parent().is_some_and(|spec| !spec_columns(spec).0 && keep_type_column(spec));
+        return if empty_type { CELLS[2] } else { CELLS[1] };
+    }
+    return " ";
+}
+
+fn precedence(op: &str) -> usize {
+    return match op {
+        "*" | "/" | "%" | "<<" | ">>" | "&" | "&^" => 5,
+        "+" | "-" | "|" | "^" => 4,
+        "==" | "!=" | "<" | "<=" | ">" | ">=" => 3,
+        "&&" => 2,
+        "||" => 1,
+        _ => 0,
+    };
+}
+
+fn binary_precedence(node: Node) -> usize {
+    if node.kind() != "binary_expression" { return 0; }
+    return match node.child_by_field_name("operator") {
+        Some(op) => precedence(op.kind()),
+        None => 0,
+    };
+}
+
+// Mirrors gofmt's walkBinary: which precedences appear in the expression and
+// whether an operator pair would read wrong without spaces (like "/ *p").
+fn walk_binary(node: Node) -> (bool, bool, usize) {
+    let prec = binary_precedence(node);
+    let (mut has4, mut has5, mut max_problem) = (prec == 4, prec == 5, 0);
+    let op = node.child_by_field_name("operator").map(|op| op.kind()).unwrap_or("");
+    if let Some(left) = node.child_by_field_name("left") {
+        if left.kind() == "binary_expression" && binary_precedence(left) >= prec {
+            let (h4, h5, problem) = walk_binary(left);
+            has4 = has4 || h4;
+            has5 = has5 || h5;
+            max_problem = max_problem.max(problem);
+        }
+    }
+    if let Some(right) = node.child_by_field_name("right") {
+        if right.kind() == "binary_expression" && binary_precedence(right) > prec {
+            let (h4, h5, problem) = walk_binary(right);
+            has4 = has4 || h4;
+            has5 = has5 || h5;
+            max_problem = max_problem.max(problem);
+        }
+        else if right.kind() == "unary_expression" {
+            let right_op = right.child(0).map(|op| op.kind()).unwrap_or("");
+            match format!("{}{}", op, right_op).as_str() {
+                "/*" | "&&" | "&^" => max_problem = 5,
+                "++" | "--" => max_problem = max_problem.max(4),
+                _ => {},
+            }
+        }
+    }
+    return (has4, has5, max_problem);
+}
+
+fn get_cutoff(node: Node, depth: usize) -> usize {
+    let (has4, has5, max_problem) = walk_binary(node);
+    if max_problem > 0 { return max_problem + 1; }
+    if has4 && has5 {
+        if depth == 1 { return 5; }
+        return 4;
+    }
+    if depth == 1 { return 6; }
+    return 4;
+}
+
+fn diff_precedence(node: Node, prec: usize) -> usize {
+    if node.kind() != "binary_expression" || binary_precedence(node) != prec { return 1; }
+    return 0;
+}
+
+fn count_arguments(node: Node) -> usize {
+    return node.named_children(&mut node.walk()).filter(|child| child.kind() != "comment").count();
+}
+
+// Collects the binary expressions gofmt prints without spaces around the
+// operator, following the precedence cutoffs of its printer.
+fn mark_binary_spacing(node: Node, depth: usize, tight: &mut HashSet<usize>) {
+    match node.kind() {
+        "binary_expression" => {
+            let prec = binary_precedence(node);
+            if prec >= get_cutoff(node, depth) { tight.insert(node.id()); }
+            if let Some(left) = node.child_by_field_name("left") {
+                mark_binary_spacing(left, depth + diff_precedence(left, prec), tight);
+            }
+            if let Some(right) = node.child_by_field_name("right") {
+                mark_binary_spacing(right, depth + 1, tight);
+            }
+        },
+        "parenthesized_expression" | "unary_expression" | "selector_expression" | "type_assertion_expression" => {
+            for child in node.named_children(&mut node.walk()) {
+                let child_depth = if node.kind() == "parenthesized_expression" { 1.max(depth.saturating_sub(1)) } else { depth };
+                mark_binary_spacing(child, child_depth, tight);
+            }
+        },
+        "call_expression" => {
+            for child in node.named_children(&mut node.walk()) {
+                if child.kind() == "argument_list" {
+                    let arg_depth = if count_arguments(child) > 1 { depth + 1 } else { depth };
+                    for arg in child.named_children(&mut child.walk()) {
+                        mark_binary_spacing(arg, arg_depth, tight);
+                    }
+                }
+                else {
+                    mark_binary_spacing(child, depth, tight);
+                }
+            }
+        },
+        "index_expression" | "slice_expression" => {
+            let operand = node.child_by_field_name("operand");
+            for child in node.named_children(&mut node.walk()) {
+                let child_depth = if Some(child) == operand { depth } else { depth + 1 };
+                mark_binary_spacing(child, child_depth, tight);
+            }
+        },
+        "assignment_statement" | "short_var_declaration" => {
+            let left = node.child_by_field_name("left").map(count_arguments).unwrap_or(0);
+            let right = node.child_by_field_name("right").map(count_arguments).unwrap_or(0);
+            let child_depth = if left > 1 && right > 1 { 2 } else { 1 };
+            for child in node.named_children(&mut node.walk()) {
+                for expr in child.named_children(&mut child.walk()) {
+                    mark_binary_spacing(expr, child_depth, tight);
+                }
+            }
+        },
+        _ => {
+            for child in node.named_children(&mut node.walk()) {
+                mark_binary_spacing(child, 1, tight);
+            }
+        },
+    }
+}
+
+fn import_path(line: &str) -> String {
+    let code = match line.find(CELL) {
+        Some(i) => &line[..i],
+        None => line,
+    };
+    let code = code.trim();
+    return match code.rfind(' ') {
+        Some(i) => code[i + 1..].to_string(),
+        None => code.to_string(),
+    };
+}
+
+// Sorts the specs of every import group by path, keeping blank lines as
+// group boundaries and leaving groups with comments alone.
+fn sort_imports(lines: Vec<String>) -> Vec<String> {
+    let mut result = Vec::<String>::new();
+    let mut group = Vec::<String>::new();
+    let mut in_imports = false;
+    for line in lines {
+        if !in_imports {
+            in_imports = line == "import (";
+            result.push(line);
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed == ")" || trimmed.is_empty() {
+            let has_comments = group.iter().any(|spec| spec.trim_start().starts_with("//") || spec.trim_start().starts_with("/*"));
+            if !has_comments {
+                group.sort_by_key(|spec| import_path(spec));
+                group.dedup();
+            }
+            result.append(&mut group);
+            in_imports = trimmed != ")";
+            result.push(line);
+            continue;
+        }
+        group.push(line);
+    }
+    result.append(&mut group);
+    return result;
+}
+
+fn split_cells(line: &str) -> Vec<String> {
+    return line.split(CELL).map(|cell| cell.to_string()).collect();
+}
+
+fn line_indent(line: &str) -> usize {
+    return line.len() - line.trim_start_matches('\t').len();
+}
+
+// Pads column `column` of consecutive lines that have it, the way a
+// tabwriter does, then aligns the next column within the same runs.
+fn align_column(rows: &mut [Vec<String>], column: usize) {
+    let mut start = 0;
+    while start < rows.len() {
+        if rows[start].len() <= column + 1 {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < rows.len() && rows[end].len() > column + 1 {
+            end += 1;
+        }
+        let width = rows[start..end].iter().map(|row| row[column].chars().count()).max().unwrap_or(0);
+        // like gofmt's tabwriter, columns that are empty on every line are dropped
+        for row in rows[start..end].iter_mut().filter(|_| width > 0) {
+            let padding = width - row[column].chars().count() + 1;
+            row[column] += &" ".repeat(padding);
+        }
+        align_column(&mut rows[start..end], column + 1);
+        start = end;
+    }
+}
+
+fn align_cells(lines: Vec<String>) -> Vec<String> {
+    let mut result = Vec::<String>::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if !lines[i].contains(CELL) {
+            result.push(lines[i].clone());
+            i += 1;
+            continue;
+        }
+        let indent = line_indent(&lines[i]);
+        let mut rows = Vec::<Vec<String>>::new();
+        while i < lines.len() && lines[i].contains(CELL) && line_indent(&lines[i]) == indent {
+            rows.push(split_cells(&lines[i]));
+            i += 1;
+        }
+        align_column(&mut rows, 0);
+        for row in rows {
+            result.push(row.concat());
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::go_format;
+
+    fn format(src: &str) -> String {
+        let ast = go_format::parse_existing_go_file(src.to_string());
+        return traverse_go_ast(ast, src.to_string());
+    }
+
+    #[test]
+    fn one_line_struct_types() {
+        let src = "\
+package main
+
+var v struct { a, b int }
+
+type I interface { M() }
+
+func f(s struct { a int }) {}
+";
+        let expected = "\
+package main
+
+var v struct{ a, b int }
+
+type I interface{ M() }
+
+func f(s struct{ a int }) {}";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+
+    #[test]
+    fn spec_comments_are_aligned() {
+        let src = "\
+package main
+
+const (
+	A = 1 // first
+	LongerName // second
+	B = 10 // third
+)
+
+var (
+	a int = 1 // x
+	bb = 2 // y
+	c string // z
+)
+";
+        let expected = "\
+package main
+
+const (
+	A          = 1  // first
+	LongerName      // second
+	B          = 10 // third
+)
+
+var (
+	a  int    = 1 // x
+	bb        = 2 // y
+	c  string     // z
+)";
+        assert_eq!(format(src).trim_end(), expected);
+    }
+}
@@ -0,0 +1,739 @@
+use tree_sitter::{Tree, Node};
+use std::collections::{HashMap, HashSet};
+use crate::{options, utils};
+
+// JavaScript and TypeScript (with JSX) are formatted like Rust: the line
+// structure of the source is kept, the spacing between the tokens of a line
+// and the indentation of every line are recomputed, and the braces of blocks
+// spanning several lines follow the C brace options. Statements can get their
+// semicolons added or dropped, and string quotes can be normalized.
+
+// Nodes printed as they are, without looking at their children.
+const ATOMIC_NODES: [&str; 6] = ["string", "template_string", "template_literal_type", "regex", "comment", "jsx_text"];
+
+// Nodes with a `<...>` list of types.
+const ANGLE_LISTS: [&str; 2] = ["type_arguments", "type_parameters"];
+
+// Nodes starting with an operator that applies to what comes right after it.
+const PREFIX_NODES: [&str; 3] = ["unary_expression", "update_expression", "spread_element"];
+
+// Statements that end with a semicolon (or a line break, when it can be left out).
+const SEMICOLON_STATEMENTS: [&str; 17] = [
+    "expression_statement", "lexical_declaration", "variable_declaration", "return_statement",
+    "throw_statement", "break_statement", "continue_statement", "debugger_statement", "do_statement",
+    "import_statement", "export_statement", "type_alias_declaration", "public_field_definition",
+    "field_definition", "function_signature", "abstract_method_signature", "import_alias",
+];
+
+// A statement starting with one of these would continue the one before it if
+// that one didn't end with a semicolon, and an empty statement would end it.
+const CONTINUING_STARTS: [&str; 9] = ["(", "[", "`", "+", "-", "/", "*", "<", ";"];
+
+// Parents of a `{` and the construct they open, for brace placement.
+const BRACE_OWNERS: [(&str, &str, utils::BraceKind); 24] = [
+    ("statement_block", "function_declaration", utils::BraceKind::Function),
+    ("statement_block", "function_expression", utils::BraceKind::Function),
+    ("statement_block", "generator_function_declaration", utils::BraceKind::Function),
+    ("statement_block", "generator_function", utils::BraceKind::Function),
+    ("statement_block", "method_definition", utils::BraceKind::Function),
+    ("statement_block", "arrow_function", utils::BraceKind::Lambda),
+    ("statement_block", "if_statement", utils::BraceKind::Control),
+    ("statement_block", "else_clause", utils::BraceKind::Control),
+    ("statement_block", "for_statement", utils::BraceKind::Control),
+    ("statement_block", "for_in_statement", utils::BraceKind::Control),
+    ("statement_block", "while_statement", utils::BraceKind::Control),
+    ("statement_block", "do_statement", utils::BraceKind::Control),
+    ("statement_block", "try_statement", utils::BraceKind::Control),
+    ("statement_block", "catch_clause", utils::BraceKind::Control),
+    ("statement_block", "finally_clause", utils::BraceKind::Control),
+    ("switch_body", "switch_statement", utils::BraceKind::Control),
+    ("class_body", "class_declaration", utils::BraceKind::Class),
+    ("class_body", "class", utils::BraceKind::Class),
+    ("class_body", "abstract_class_declaration", utils::BraceKind::Class),
+    ("interface_body", "interface_declaration", utils::BraceKind::Class),
+    ("object_type", "interface_declaration", utils::BraceKind::Class),
+    ("enum_body", "enum_declaration", utils::BraceKind::Enum),
+    ("statement_block", "internal_module", utils::BraceKind::Namespace),
+    ("statement_block", "module", utils::BraceKind::Namespace),
+];
+
+struct JsLine<'a> {
+    tokens: Vec<Node<'a>>,
+    blank_before: bool,
+}
+
+pub fn traverse_js_ast(ast: Tree, src: String, opts: options::JsOpts, c_opts: options::COpts) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the JavaScript/TypeScript file was left as it is.");
+        return src;
+    }
+    let mut tokens = Vec::<Node>::new();
+    collect_tokens(root, &src, &mut tokens);
+    // semicolons added after a token and the ones dropped
+    let mut semicolons = HashSet::<usize>::new();
+    match opts.semicolons {
+        utils::Semicolons::Always => add_semicolons(root, &mut semicolons),
+        utils::Semicolons::Never => tokens = drop_semicolons(&tokens, &src),
+        utils::Semicolons::Keep => (),
+    }
+    let mut quoted = HashMap::<usize, String>::new();
+    if opts.quotes != utils::QuoteStyle::Keep {
+        for token in &tokens {
+            if let Some(text) = normalize_quotes(*token, &src, opts.quotes) { quoted.insert(token.id(), text); }
+        }
+    }
+    let mut breaks = HashSet::<usize>::new();
+    let mut joins = HashSet::<usize>::new();
+    place_braces(&tokens, &src, c_opts, &mut breaks, &mut joins);
+    if opts.semicolons == utils::Semicolons::Always {
+        // `let c = a` followed by `;[1, 2].forEach(f)` gets its semicolon back
+        for i in 1..tokens.len() {
+            let moved = first_row(tokens[i], &src) > last_row(tokens[i - 1], &src);
+            if !moved || !ends_statement(tokens[i]) || tokens[i - 1].kind() == "comment" { continue; }
+            joins.insert(tokens[i].id());
+            if let Some(next) = tokens.get(i + 1).filter(|x| x.kind() != "comment" && first_row(**x, &src) == last_row(tokens[i], &src)) { breaks.insert(next.id()); }
+        }
+    }
+    let lines = split_into_lines(&tokens, &src, &breaks, &joins);
+    let (indents, text_indents) = compute_indentation(&lines, &src);
+    // the physical lines of every line, more than one when it holds a multi-line string or comment
+    let mut result = Vec::<Vec<String>>::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.blank_before && result.len() > 0 { result.push(vec![String::new()]); }
+        let mut text = "\t".repeat(indents[i]);
+        for (j, token) in line.tokens.iter().enumerate() {
+            if j > 0 { text += get_separator(line.tokens[j - 1], *token, &src); }
+            match quoted.get(&token.id()) {
+                Some(quoted) => text += quoted,
+                None if token.kind() == "jsx_text" => {
+                    // whitespace around line breaks in JSX text doesn't count, so it's reindented
+                    let parts = get_text(*token, &src).split('\n').collect::<Vec<&str>>();
+                    for (k, mut words) in parts.iter().copied().enumerate() {
+                        if k > 0 { words = words.trim_start(); }
+                        if k + 1 < parts.len() { words = words.trim_end(); }
+                        if k > 0 { text += "\n"; }
+                        if k > 0 && !words.is_empty() { text += &"\t".repeat(text_indents[&token.id()]); }
+                        text += words;
+                    }
+                },
+                None => text += get_text(*token, &src),
+            }
+            if semicolons.contains(&token.id()) { text += ";"; }
+        }
+        result.push(text.trim_end().split('\n').map(|x| x.to_string()).collect());
+    }
+    // GNU and Whitesmiths move the lines with braces, which mustn't touch the
+    // inside of multi-line strings and comments.
+    let mut first_lines = result.iter().map(|x| x[0].clone()).collect::<Vec<String>>().join("\n");
+    utils::indent_braces(&mut first_lines, c_opts.style);
+    for (physical, first) in result.iter_mut().zip(first_lines.split('\n')) {
+        physical[0] = first.to_string();
+    }
+    let mut result = result.concat().join("\n");
+    result = result.trim_end().to_string();
+    result.push('\n');
+    return result;
+}
+
+// JSX text loses the whitespace around it that holds a line break, which
+// doesn't count. The whitespace on its line is part of the text.
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    let mut text = node.utf8_text(src.as_bytes()).unwrap_or("");
+    if node.kind() != "jsx_text" { return text; }
+    let leading = &text[..text.len() - text.trim_start().len()];
+    if leading.contains('\n') { text = text.trim_start(); }
+    let trailing = &text[text.trim_end().len()..];
+    if trailing.contains('\n') { text = text.trim_end(); }
+    return text;
+}
+
+// The rows of the first and the last character of the token that gets printed.
+fn first_row(node: Node, src: &str) -> usize {
+    let text = node.utf8_text(src.as_bytes()).unwrap_or("");
+    let leading = &text[..text.len() - text.trim_start().len()];
+    return node.start_position().row + leading.matches('\n').count();
+}
+
+fn last_row(node: Node, src: &str) -> usize {
+    return first_row(node, src) + get_text(node, src).matches('\n').count();
+}
+
+fn parent_kind(node: Node) -> &'static str {
+    return match node.parent() {
+        Some(parent) => parent.kind(),
+        None => "",
+    };
+}
+
+fn is_first_child(node: Node) -> bool {
+    return node.prev_sibling().is_none();
+}
+
+fn collect_tokens<'a>(node: Node<'a>, src: &str, tokens: &mut Vec<Node<'a>>) {
+    if node.child_count() == 0 || (node.is_named() && ATOMIC_NODES.contains(&node.kind())) {
+        if get_text(node, src).trim() != "" || (node.kind() == "jsx_text" && !get_text(node, src).is_empty()) { tokens.push(node); }
+        return;
+    }
+    for child in node.children(&mut node.walk()) {
+        collect_tokens(child, src, tokens);
+    }
+}
+
+// The last token of the node that isn't a comment.
+fn last_token(node: Node) -> Node {
+    let mut node = node;
+    loop {
+        if node.is_named() && ATOMIC_NODES.contains(&node.kind()) { return node; }
+        let children = node.children(&mut node.walk()).filter(|x| x.kind() != "comment").collect::<Vec<Node>>();
+        match children.last() {
+            Some(child) => node = *child,
+            None => return node,
+        }
+    }
+}
+
+// Whether the statement would end with a semicolon in a file written with them.
+fn takes_semicolon(node: Node) -> bool {
+    if !SEMICOLON_STATEMENTS.contains(&node.kind()) { return false; }
+    // the declaration in `for (let i = 0; ...)` has its own
+    if let Some(parent) = node.parent().filter(|x| ["for_statement", "for_in_statement"].contains(&x.kind())) {
+        if parent.child_by_field_name("body") != Some(node) { return false; }
+    }
+    let last = match node.named_child(node.named_child_count().saturating_sub(1)) {
+        Some(last) => last,
+        None => return true,
+    };
+    return match node.kind() {
+        "expression_statement" => !["internal_module", "module"].contains(&last.kind()),
+        // `export function f() {}` and `export default class {}` end with their body
+        "export_statement" => {
+            let is_declaration = node.child_by_field_name("declaration").is_some();
+            let is_body = ["function_expression", "function", "generator_function", "class"].contains(&last.kind());
+            !is_declaration && !is_body
+        },
+        _ => true,
+    };
+}
+
+// TypeScript puts the semicolon of a class field next to it instead of in it.
+fn ends_statement(token: Node) -> bool {
+    if token.kind() != ";" { return false; }
+    let parent = match token.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    if parent.kind() == "class_body" { return token.prev_sibling().is_some_and(|x| takes_semicolon(x)); }
+    return takes_semicolon(parent) && parent.child(parent.child_count() - 1) == Some(token);
+}
+
+fn add_semicolons(node: Node, semicolons: &mut HashSet<usize>) {
+    let has_semicolon = last_token(node).kind() == ";" || node.next_sibling().is_some_and(|x| ends_statement(x));
+    if takes_semicolon(node) && !has_semicolon {
+        semicolons.insert(last_token(node).id());
+    }
+    for child in node.named_children(&mut node.walk()) {
+        add_semicolons(child, semicolons);
+    }
+}
+
+// Drops the semicolons ending a line, unless the next statement starts with
+// something that would continue the one before it.
+fn drop_semicolons<'a>(tokens: &[Node<'a>], src: &str) -> Vec<Node<'a>> {
+    let mut result = Vec::<Node>::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if ends_statement(*token) {
+            let next = tokens[i + 1..].iter().find(|x| x.kind() != "comment");
+            let droppable = match next {
+                Some(next) => {
+                    let text = get_text(*next, src);
+                    let on_next_line = first_row(*next, src) > last_row(*token, src);
+                    let continues = CONTINUING_STARTS.iter().any(|x| text.starts_with(x));
+                    (on_next_line || text == "}") && !continues
+                },
+                None => true,
+            };
+            if droppable { continue; }
+        }
+        result.push(*token);
+    }
+    return result;
+}
+
+// 'text' becomes "text" (or the other way around) when that needs no more escapes.
+// JSX attributes can't have escapes, so they're left alone.
+fn normalize_quotes(node: Node, src: &str, style: utils::QuoteStyle) -> Option<String> {
+    if !node.is_named() || node.kind() != "string" || parent_kind(node) == "jsx_attribute" { return None; }
+    let (from, to) = match style {
+        utils::QuoteStyle::Double => ('\'', '"'),
+        utils::QuoteStyle::Single => ('"', '\''),
+        utils::QuoteStyle::Keep => return None,
+    };
+    let text = get_text(node, src);
+    if !text.starts_with(from) || text.len() < 2 { return None; }
+    let inner = &text[1..text.len() - 1];
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == to { return None; }
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == from => result.push(next),
+                Some(next) => { result.push(c); result.push(next); },
+                None => result.push(c),
+            }
+            continue;
+        }
+        result.push(c);
+    }
+    return Some(format!("{}{}{}", to, result, to));
+}
+
+fn brace_kind(open: Node) -> Option<utils::BraceKind> {
+    let list = open.parent()?;
+    let owner = list.parent()?;
+    for (list_kind, owner_kind, kind) in BRACE_OWNERS {
+        if list.kind() == list_kind && owner.kind() == owner_kind { return Some(kind); }
+    }
+    return None;
+}
+
+// The `{` of a block is attached to the line before it or moved to a line of
+// its own, and the `else`, `catch` and `finally` after a block go on the `}`
+// line or on the next one, as the brace options say. A block written on one
+// line stays that way when its brace is attached, and is spread over several
+// lines, one item per line, when its brace goes on a new line.
+fn place_braces(tokens: &[Node], src: &str, opts: options::COpts, breaks: &mut HashSet<usize>, joins: &mut HashSet<usize>) {
+    let mut expanded = HashSet::<usize>::new();
+    for i in 1..tokens.len() {
+        let (previous, token) = (tokens[i - 1], tokens[i]);
+        let on_new_line = first_row(token, src) > last_row(previous, src);
+        let placement;
+        let block;
+        if token.kind() == "{" {
+            let kind = match brace_kind(token) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            placement = opts.braces.get(kind);
+            block = token.parent().unwrap();
+        }
+        else if previous.kind() == "}" && ["else", "catch", "finally"].contains(&token.kind()) {
+            let kind = if token.kind() == "else" { utils::BraceKind::Else } else { utils::BraceKind::Catch };
+            placement = opts.braces.get(kind);
+            block = previous.parent().unwrap();
+            // `if (a) { f() } else { g() }` stays on one line unless its first block was spread out
+            if is_one_line(block) && !expanded.contains(&block.id()) { continue; }
+        }
+        else {
+            continue;
+        }
+        match placement {
+            utils::BracePlacement::Attach => {
+                if is_one_line(block) && token.kind() == "{" { continue; }
+                if on_new_line && previous.kind() != "comment" { joins.insert(token.id()); }
+            },
+            utils::BracePlacement::NewLine => {
+                // a `{` with the start of the body after it stays where it is
+                let body_follows = tokens.get(i + 1).is_some_and(|x| token.kind() == "{" && !is_one_line(block) && first_row(*x, src) == last_row(token, src));
+                if !on_new_line && !body_follows { breaks.insert(token.id()); }
+                if is_one_line(block) && token.kind() == "{" {
+                    // the one-line blocks around it are spread out with it
+                    let mut current = Some(block);
+                    while let Some(node) = current {
+                        let is_block = node.child(0).is_some_and(|x| x.kind() == "{" && brace_kind(x).is_some());
+                        if is_block && is_one_line(node) && expanded.insert(node.id()) { expand_block(node, breaks); }
+                        current = node.parent();
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn is_one_line(node: Node) -> bool {
+    return node.start_position().row == node.end_position().row;
+}
+
+// Breaks the line before every item of a block and before its `}`, which an empty block keeps.
+fn expand_block(block: Node, breaks: &mut HashSet<usize>) {
+    let is_empty = block.named_child_count() == 0;
+    for child in block.children(&mut block.walk()) {
+        if child.kind() == "}" && !is_empty { breaks.insert(child.id()); }
+        else if child.is_named() { breaks.insert(first_token(child).id()); }
+    }
+}
+
+fn first_token(node: Node) -> Node {
+    let mut current = node;
+    while current.child_count() > 0 && !ATOMIC_NODES.contains(&current.kind()) {
+        current = current.child(0).unwrap();
+    }
+    return current;
+}
+
+fn split_into_lines<'a>(tokens: &[Node<'a>], src: &str, breaks: &HashSet<usize>, joins: &HashSet<usize>) -> Vec<JsLine<'a>> {
+    let mut lines = Vec::<JsLine>::new();
+    let mut current = JsLine { tokens: Vec::new(), blank_before: false };
+    let mut previous_row: Option<usize> = None;
+    for token in tokens {
+        let row = first_row(*token, src);
+        let mut new_line = previous_row.is_none() || row > previous_row.unwrap();
+        // a `//` comment goes on until the end of the line
+        if current.tokens.last().is_some_and(|x| get_text(*x, src).starts_with("//")) { new_line = true; }
+        if breaks.contains(&token.id()) { new_line = true; }
+        if joins.contains(&token.id()) { new_line = false; }
+        if new_line && current.tokens.len() > 0 {
+            lines.push(current);
+            let blank = previous_row.is_some() && row > previous_row.unwrap() + 1;
+            current = JsLine { tokens: Vec::new(), blank_before: blank };
+        }
+        current.tokens.push(*token);
+        previous_row = Some(last_row(*token, src));
+    }
+    if current.tokens.len() > 0 { lines.push(current); }
+    return lines;
+}
+
+// Brackets, and JSX tags: an opening tag opens at its `<` and its `>`
+// closes that and opens the element's children, which its closing tag closes.
+fn is_opener(token: Node) -> bool {
+    return match token.kind() {
+        "(" | "[" | "{" => true,
+        "<" => ["jsx_opening_element", "jsx_self_closing_element"].contains(&parent_kind(token)),
+        ">" => parent_kind(token) == "jsx_opening_element",
+        _ => false,
+    };
+}
+
+fn is_closer(token: Node) -> bool {
+    return match token.kind() {
+        ")" | "]" | "}" | "/>" | "</" => true,
+        ">" => parent_kind(token) == "jsx_opening_element",
+        _ => false,
+    };
+}
+
+fn is_binary_operator(token: Node) -> bool {
+    return !token.is_named() && parent_kind(token) == "binary_expression" && !is_first_child(token);
+}
+
+// Lines after one ending with an operator, `=` or `=>` are indented once more.
+fn ends_with_continuation(token: Node, src: &str) -> bool {
+    if token.is_named() { return false; }
+    let text = get_text(token, src);
+    if is_binary_operator(token) { return true; }
+    return match parent_kind(token) {
+        "variable_declarator" | "assignment_expression" | "augmented_assignment_expression" | "type_alias_declaration" => text == "=" || text.ends_with("="),
+        "public_field_definition" | "field_definition" => text == "=",
+        "arrow_function" => text == "=>",
+        "ternary_expression" | "conditional_type" => text == "?" || text == ":",
+        _ => false,
+    };
+}
+
+// Method chains, ternaries and operators carried over to the next line are indented once more.
+fn starts_with_continuation(token: Node, src: &str) -> bool {
+    let text = get_text(token, src);
+    if text == "." || text == "?." { return true; }
+    if ["ternary_expression", "conditional_type"].contains(&parent_kind(token)) && (text == "?" || text == ":") { return true; }
+    return is_binary_operator(token);
+}
+
+// Whether the token starts a statement of a `case` or `default` clause, which
+// goes one level deeper than the clause.
+fn in_case_body(token: Node) -> bool {
+    let mut current = token;
+    while let Some(parent) = current.parent() {
+        if ["switch_case", "switch_default"].contains(&parent.kind()) {
+            return current.start_byte() != parent.start_byte();
+        }
+        if parent.start_byte() != token.start_byte() { return false; }
+        current = parent;
+    }
+    return false;
+}
+
+// Also returns the indentation of the later lines of each multi-line JSX text,
+// which belong inside the element holding it.
+fn compute_indentation(lines: &[JsLine], src: &str) -> (Vec<usize>, HashMap<usize, usize>) {
+    let mut indents = Vec::<usize>::new();
+    let mut text_indents = HashMap::<usize, usize>::new();
+    // the line on which each unclosed bracket was opened
+    let mut open_lines = Vec::<usize>::new();
+    for (i, line) in lines.iter().enumerate() {
+        let mut k = 0;
+        let mut closed: Option<usize> = None;
+        let mut reopens = false;
+        while k < line.tokens.len() && is_closer(line.tokens[k]) {
+            closed = open_lines.pop();
+            k += 1;
+            // `}; })` counts like `}})`, with or without the semicolon
+            if line.tokens.get(k).is_some_and(|x| x.kind() == ";") && line.tokens.get(k + 1).is_some_and(|x| is_closer(*x)) { k += 1; }
+            // the `>` of an opening tag opens the children again
+            if is_opener(line.tokens[k - 1]) {
+                reopens = true;
+                break;
+            }
+        }
+        // a closer goes back to the indentation of the line that opened it
+        let mut indent = match (closed, open_lines.last()) {
+            (Some(opened), _) => indents[opened],
+            (None, Some(opened)) => indents[*opened] + 1,
+            (None, None) => 0,
+        };
+        let first = line.tokens[0];
+        if closed.is_none() && i > 0 {
+            let previous = &lines[i - 1];
+            let last = previous.tokens[previous.tokens.len() - 1];
+            let opened_on_previous = open_lines.last() == Some(&(i - 1));
+            let previous_closes = is_closer(previous.tokens[0]);
+            let continues = (ends_with_continuation(last, src) && !opened_on_previous)
+                || (starts_with_continuation(first, src) && !previous_closes)
+                || in_case_body(first);
+            if continues { indent += 1; }
+            // the `{` of a block on a line of its own lines up with what the block belongs to,
+            // also when that is an arrow function inside a call
+            if first.kind() == "{" && brace_kind(first).is_some() {
+                let owner = first.parent().and_then(|x| x.parent()).unwrap();
+                let owner_line = (0..i).rev().find(|j| lines[*j].tokens[0].start_byte() <= owner.start_byte()).unwrap_or(i - 1);
+                indent = indents[owner_line];
+            }
+        }
+        indents.push(indent);
+        if reopens { open_lines.push(i); }
+        for token in &line.tokens[k..] {
+            if is_closer(*token) { open_lines.pop(); }
+            if is_opener(*token) { open_lines.push(i); }
+            if token.kind() == "jsx_text" {
+                let inner = open_lines.last().map_or(indent, |x| indents[*x] + 1);
+                text_indents.insert(token.id(), inner);
+            }
+        }
+    }
+    return (indents, text_indents);
+}
+
+fn starts_with_word(text: &str) -> bool {
+    return match text.chars().next() {
+        Some(c) => c.is_alphanumeric() || c == '_' || c == '$',
+        None => false,
+    };
+}
+
+fn is_keyword(node: Node, src: &str) -> bool {
+    return !node.is_named() && starts_with_word(get_text(node, src));
+}
+
+fn is_jsx(node: Node) -> bool {
+    return parent_kind(node).starts_with("jsx_") || node.kind() == "jsx_text";
+}
+
+// Inside JSX the spacing of the source is kept, since it's part of the text.
+fn source_separator(a: Node, b: Node) -> &'static str {
+    if a.end_byte() < b.start_byte() { return " "; }
+    return "";
+}
+
+fn get_separator(a: Node, b: Node, src: &str) -> &'static str {
+    let (ta, tb) = (get_text(a, src), get_text(b, src));
+    let (pa, pb) = (parent_kind(a), parent_kind(b));
+    if b.kind() == "comment" || a.kind() == "comment" { return " "; }
+    if is_jsx(a) || is_jsx(b) { return source_separator(a, b); }
+    if tb == ")" || tb == "]" || tb == ";" || tb == "," { return ""; }
+    // `= <const>[...]` and `(<any>w)`: a type assertion is written like a prefix
+    let asserts = |node: Node| node.parent().and_then(|x| x.parent()).is_some_and(|x| x.kind() == "type_assertion");
+    if pb == "type_arguments" && tb == "<" && asserts(b) { return if ta == "(" || ta == "[" { "" } else { " " }; }
+    if pa == "type_arguments" && ta == ">" && asserts(a) { return ""; }
+    if ANGLE_LISTS.contains(&pb) && tb == "<" { return if is_keyword(a, src) { " " } else { "" }; }
+    if ANGLE_LISTS.contains(&pb) && tb == ">" { return ""; }
+    if ANGLE_LISTS.contains(&pa) && ta == "<" { return ""; }
+    // a semicolon at the start of a line guards the statement after it: `;[1, 2].forEach(f)`
+    if ta == ";" && a.prev_sibling().is_some_and(|x| last_row(x, src) < first_row(a, src)) { return ""; }
+    if ta == "," || ta == ";" { return " "; }
+    if ta == "(" || ta == "[" { return ""; }
+    if ta == "." || tb == "." || ta == "?." || tb == "?." { return ""; }
+    if pb == "ternary_expression" || pb == "conditional_type" || pa == "ternary_expression" || pa == "conditional_type" {
+        return " ";
+    }
+    if tb == ":" || tb == "?" { return ""; }
+    if ta == ":" { return " "; }
+    if ta == "{" { return if tb == "}" { "" } else { " " }; }
+    if tb == "}" || tb == "{" { return " "; }
+    if ta == "..." || ta == "@" || ta == "#" { return ""; }
+    if tb == "!" && pb == "non_null_expression" { return ""; }
+    if PREFIX_NODES.contains(&pa) && is_first_child(a) && !a.is_named() && !is_keyword(a, src) { return ""; }
+    if pb == "update_expression" && !b.is_named() && !is_first_child(b) { return ""; }
+    if tb == "*" && (ta == "function" || ta == "yield") { return ""; }
+    if tb == "(" {
+        if ta == "function" { return " "; }
+        if ta == "import" || ta == "super" { return ""; }
+        if is_keyword(a, src) { return " "; }
+        if a.is_named() || ta == ")" || ta == "]" || ta == "!" { return ""; }
+        if ta == ">" && ANGLE_LISTS.contains(&pa) { return ""; }
+        return " ";
+    }
+    if tb == "[" {
+        if ["subscript_expression", "array_type", "lookup_type"].contains(&pb) { return ""; }
+        return " ";
+    }
+    if tb == "<" && pb == "type_arguments" { return ""; }
+    return " ";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::js_format;
+
+    fn format_with(src: &str, args: &[&str]) -> String {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+        let ast = js_format::parse_existing_js_file(src.to_string(), ".ts");
+        return traverse_js_ast(ast, src.to_string(), options::get_js_opts(&args), options::get_c_opts(&args));
+    }
+
+    #[test]
+    fn arrow_function_bodies() {
+        let src = "\
+const f = (x) => {
+  return x + 1;
+};
+items.forEach((item) => {
+  use(item);
+});
+";
+        let cases = [
+            (vec![], "\
+const f = (x) => {
+	return x + 1;
+};
+items.forEach((item) => {
+	use(item);
+});
+"),
+            (vec!["-s", "allman"], "\
+const f = (x) =>
+{
+	return x + 1;
+};
+items.forEach((item) =>
+{
+	use(item);
+});
+"),
+            (vec!["-s", "gnu"], "\
+const f = (x) =>
+{
+  return x + 1;
+};
+items.forEach((item) =>
+{
+  use(item);
+});
+"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn one_line_blocks() {
+        let src = "\
+function g(x) { if (x) { return 1 } else { return 2 } }
+class A {
+  m() { return 1; }
+  n() {}
+}
+";
+        let cases = [
+            (vec![], "\
+function g(x)
+{
+	if (x) { return 1; } else { return 2; }
+}
+class A
+{
+	m()
+	{
+		return 1;
+	}
+	n()
+	{}
+}
+"),
+            (vec!["-s", "allman"], "\
+function g(x)
+{
+	if (x)
+	{
+		return 1;
+	}
+	else
+	{
+		return 2;
+	}
+}
+class A
+{
+	m()
+	{
+		return 1;
+	}
+	n()
+	{}
+}
+"),
+            (vec!["-s", "1tbs"], "\
+function g(x) { if (x) { return 1; } else { return 2; } }
+class A {
+	m() { return 1; }
+	n() {}
+}
+"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn type_assertions_and_semicolons() {
+        let src = "\
+const y = <const>['a']
+const z = (<any>w).q
+let c = a
+;[1, 2].forEach(go)
+let d = 1
+; // one
+";
+        let cases = [
+            (vec![], "\
+const y = <const>[\"a\"];
+const z = (<any>w).q;
+let c = a;
+[1, 2].forEach(go);
+let d = 1; // one
+"),
+            (vec!["--semicolons", "never"], "\
+const y = <const>[\"a\"]
+const z = (<any>w).q
+let c = a
+;[1, 2].forEach(go)
+let d = 1
+// one
+"),
+            (vec!["--semicolons", "keep"], "\
+const y = <const>[\"a\"]
+const z = (<any>w).q
+let c = a
+;[1, 2].forEach(go)
+let d = 1
+; // one
+"),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(format_with(src, &args), expected, "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use tree_sitter::{Parser, Language};
+use crate::{utils, js_ast};
+
+// TypeScript needs its own grammar, and the TSX one for JSX in TypeScript.
+// The JavaScript grammar reads JSX as well.
+fn get_language(file_type: &str) -> Language {
+    return match file_type {
+        ".ts" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+        ".tsx" => tree_sitter_typescript::LANGUAGE_TSX.into(),
+        _ => tree_sitter_javascript::LANGUAGE.into(),
+    };
+}
+
+pub fn parse_js_file(path: String, file_type: &str) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language(file_type)).expect("Error loading JavaScript/TypeScript parser.");
+    let contents = std::fs::read_to_string(path).unwrap_or("".to_string());
+    let tree = parser.parse(contents.clone(), None).unwrap();
+    return tree;
+}
+
+pub fn parse_existing_js_file(text: String, file_type: &str) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&get_language(file_type)).expect("Error loading JavaScript/TypeScript parser.");
+    let tree = parser.parse(text, None).unwrap();
+    return tree;
+}
@@ -0,0 +1,134 @@
+use tree_sitter::{Tree, Node};
+use crate::options;
+
+// JSON is reprinted from the tree: objects get one member per line, arrays of
+// scalars stay on one line while they fit, and scalars (numbers included) are
+// copied exactly as written. Comments (JSONC) are kept on their own line, or
+// after the member they follow on the same line.
+
+// Arrays of scalars longer than this (indentation included) get one element per line.
+const COMPACT_WIDTH: usize = 80;
+
+struct JsonItem {
+    text: String,
+    sort_key: String,
+    leading_comments: Vec<String>,
+    trailing_comment: Option<String>,
+}
+
+pub fn traverse_json_ast(ast: Tree, src: String, opts: options::JsonOpts) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the JSON file was left as it is.");
+        return src;
+    }
+    let mut result = Vec::<String>::new();
+    for child in root.named_children(&mut root.walk()) {
+        result.push(handle_value(child, &src, 0, &opts));
+    }
+    return result.join("\n") + "\n";
+}
+
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    return node.utf8_text(src.as_bytes()).unwrap_or("");
+}
+
+fn indent(depth: usize, opts: &options::JsonOpts) -> String {
+    return opts.indent.repeat(depth);
+}
+
+fn handle_value(node: Node, src: &str, depth: usize, opts: &options::JsonOpts) -> String {
+    return match node.kind() {
+        "object" => handle_container(node, src, depth, opts, "{", "}"),
+        "array" => handle_container(node, src, depth, opts, "[", "]"),
+        "comment" => get_text(node, src).trim_end().to_string(),
+        // strings, numbers, true, false and null are kept as written
+        _ => get_text(node, src).to_string(),
+    };
+}
+
+fn collect_items(node: Node, src: &str, depth: usize, opts: &options::JsonOpts) -> (Vec<JsonItem>, Vec<String>) {
+    let mut items = Vec::<JsonItem>::new();
+    let mut pending_comments = Vec::<String>::new();
+    let mut last_row: Option<usize> = None;
+    for child in node.named_children(&mut node.walk()) {
+        if child.kind() == "comment" {
+            let comment = get_text(child, src).trim_end().to_string();
+            let is_trailing = last_row == Some(child.start_position().row) && items.len() > 0;
+            if is_trailing && pending_comments.is_empty() && items.last().unwrap().trailing_comment.is_none() {
+                items.last_mut().unwrap().trailing_comment = Some(comment);
+            }
+            else {
+                pending_comments.push(comment);
+            }
+            continue;
+        }
+        let (text, sort_key) = if child.kind() == "pair" {
+            let key = child.child_by_field_name("key").map(|key| get_text(key, src)).unwrap_or("");
+            let value = child.child_by_field_name("value").map(|value| handle_value(value, src, depth + 1, opts)).unwrap_or_default();
+            (format!("{}: {}", key, value), key.trim_matches('"').to_string())
+        }
+        else {
+            (handle_value(child, src, depth + 1, opts), String::new())
+        };
+        items.push(JsonItem { text, sort_key, leading_comments: pending_comments, trailing_comment: None });
+        pending_comments = Vec::new();
+        last_row = Some(child.end_position().row);
+    }
+    return (items, pending_comments);
+}
+
+fn handle_container(node: Node, src: &str, depth: usize, opts: &options::JsonOpts, open: &str, close: &str) -> String {
+    let (mut items, closing_comments) = collect_items(node, src, depth, opts);
+    let has_comments = !closing_comments.is_empty()
+        || items.iter().any(|item| !item.leading_comments.is_empty() || item.trailing_comment.is_some());
+    if items.is_empty() && !has_comments { return format!("{}{}", open, close); }
+    if open == "{" && opts.sort_keys { items.sort_by(|a, b| a.sort_key.cmp(&b.sort_key)); }
+    let all_scalars = node.named_children(&mut node.walk()).all(|child| child.kind() != "object" && child.kind() != "array");
+    if open == "[" && all_scalars && !has_comments {
+        let compact = format!("[{}]", items.iter().map(|item| item.text.as_str()).collect::<Vec<&str>>().join(", "));
+        if indent(depth, opts).replace('\t', "    ").len() + compact.len() <= COMPACT_WIDTH { return compact; }
+    }
+    let inner = indent(depth + 1, opts);
+    let mut lines = vec![open.to_string()];
+    for (i, item) in items.iter().enumerate() {
+        for comment in &item.leading_comments {
+            lines.push(format!("{}{}", inner, comment));
+        }
+        let comma = if i + 1 < items.len() { "," } else { "" };
+        let mut line = format!("{}{}{}", inner, item.text, comma);
+        if let Some(comment) = &item.trailing_comment { line = format!("{} {}", line, comment); }
+        lines.push(line);
+    }
+    for comment in &closing_comments {
+        lines.push(format!("{}{}", inner, comment));
+    }
+    lines.push(format!("{}{}", indent(depth, opts), close));
+    return lines.join("\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_format;
+
+    fn format(src: &str) -> String {
+        let ast = json_format::parse_existing_json_file(src.to_string());
+        let opts = options::JsonOpts { indent: "  ".to_string(), sort_keys: false };
+        return traverse_json_ast(ast, src.to_string(), opts);
+    }
+
+    #[test]
+    fn exponents_are_kept_as_written() {
+        let src = "{\"a\": 1e+10, \"b\": 1E5, \"c\": -0.5e-3, \"d\": \"1e+10\"}\n";
+        let expected = "\
+{
+  \"a\": 1e+10,
+  \"b\": 1E5,
+  \"c\": -0.5e-3,
+  \"d\": \"1e+10\"
+}
+";
+        assert_eq!(format(src), expected);
+    }
+}
@@ -0,0 +1,59 @@
+use tree_sitter::{Parser, Language};
+use crate::{utils, json_ast};
+
+pub fn parse_json_file(path: String) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_json::LANGUAGE.into()).expect("Error loading JSON parser.");
+    let contents = std::fs::read_to_string(path).unwrap_or("".to_string());
+    let tree = parser.parse(mask_exponent_signs(&contents), None).unwrap();
+    return tree;
+}
+
+pub fn parse_existing_json_file(text: String) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_json::LANGUAGE.into()).expect("Error loading JSON parser.");
+    let tree = parser.parse(mask_exponent_signs(&text), None).unwrap();
+    return tree;
+}
+
+// tree-sitter-json rejects a '+' after the exponent marker (1e+10), so the parser
+// reads it as a leading zero of the exponent instead. The byte offsets stay the
+// same and the numbers are printed from the original text.
+fn mask_exponent_signs(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut result = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' { i += 1; }
+                    i += 1;
+                }
+                i += 1;
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' { i += 1; }
+            },
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) { i += 1; }
+                i += 2;
+            },
+            b'-' | b'0'..=b'9' => {
+                while i < bytes.len() && (bytes[i].is_ascii_digit() || b"-.eE".contains(&bytes[i])) {
+                    let is_exponent = bytes[i] == b'e' || bytes[i] == b'E';
+                    if is_exponent && bytes.get(i + 1) == Some(&b'+') {
+                        result[i + 1] = b'0';
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            },
+            _ => i += 1,
+        }
+    }
+    // only ASCII bytes were swapped for ASCII bytes
+    return String::from_utf8(result).unwrap_or(text.to_string());
+}
@@ -2,6 +2,10 @@
 #![allow(unused_variables)]
 #![allow(unused_imports)]
 #![allow(unused_mut)]
+// the code is written with explicit returns, `.len() > 0` checks and `&String` parameters
+#![allow(clippy::needless_return)]
+#![allow(clippy::len_zero)]
+#![allow(clippy::ptr_arg)]
 
 use std::io::stdin;
 use tree_sitter::{Parser, Language};
@@ -16,6 +20,28 @@ mod c_format;
 mod cpp_format;
 mod c_ast;
 mod cpp_ast;
+mod go_format;
+mod go_ast;
+mod py_format;
+mod py_ast;
+mod md_format;
+mod md_ast;
+mod json_format;
+mod json_ast;
+mod toml_format;
+mod toml_ast;
+mod yaml_format;
+mod yaml_ast;
+mod sh_format;
+mod sh_ast;
+mod rs_format;
+mod rs_ast;
+mod js_format;
+mod js_ast;
+mod cmake_format;
+mod make_format;
+mod fences;
+mod external;
 mod txt_format;
 
 fn main() {
@@ -26,32 +52,73 @@ fn main() {
             None => return,
         }
     };
+    let args: Vec<String> = options::with_config_file(&filepath, args);
     let help_arg: usize = options::check_help_arg(&args);
     if help_arg == 1 { return; }
     let stdin_arg: String = options::check_stdin_arg(&args);
-    let write_arg: bool = options::check_write_arg(&args);
+    let output_mode: utils::OutputMode = options::check_output_mode(&args);
     // the variable being operated on is called "filepath",
     // but that is where the ext was saved if stdin.len > 0
     let file_type: String = if stdin_arg.len() > 0 { filepath.clone() } 
                     else { utils::infer_file_type(&filepath) }; 
+    // an external formatter configured for the extension replaces the built-in one
+    if let Some(opts) = options::get_external_opts(&args, &filepath) {
+        format::format_external_file(filepath, opts, output_mode, stdin_arg);
+        return;
+    }
     match file_type.as_str() {
         ".txt" => {
             let opts: options::TxtOpts = options::get_txt_opts(&args);
-            format::format_txt_file(filepath, opts, &args, write_arg);
+            let c_opts = options::get_c_opts(&args);
+            format::format_txt_file(filepath, opts, c_opts, &args, output_mode);
+        },
+        ".md" => {
+            let opts: options::TxtOpts = options::get_txt_opts(&args);
+            let c_opts = options::get_c_opts(&args);
+            format::format_md_file(filepath, opts, c_opts, output_mode, stdin_arg);
+        },
+        ".json" => {
+            let opts = options::get_json_opts(&args);
+            format::format_json_file(filepath, opts, output_mode, stdin_arg);
+        },
+        ".toml" => {
+            format::format_toml_file(filepath, output_mode, stdin_arg);
+        },
+        ".yaml" | ".yml" => {
+            format::format_yaml_file(filepath, output_mode, stdin_arg);
         },
         ".go" => {
-            format::format_go_file(filepath);
+            format::format_go_file(filepath, output_mode, stdin_arg);
         },
-        ".cpp" | ".cc" | ".C" | ".hpp" | ".hh" | ".H" => {
-            let style = options::get_c_style(&args);
-            format::format_cpp_file(filepath, style, write_arg, stdin_arg);
+        // CUDA is C++ with a few extensions, OpenCL C is C with a few extensions.
+        ".cpp" | ".cc" | ".C" | ".hpp" | ".hh" | ".H" | ".cu" | ".cuh" => {
+            let opts = options::get_c_opts(&args);
+            format::format_cpp_file(filepath, opts, output_mode, stdin_arg);
         },
-        ".c" | ".h" => {
-            let style = options::get_c_style(&args);
-            format::format_c_file(filepath, style, write_arg, stdin_arg);
+        ".c" | ".h" | ".cl" => {
+            let opts = options::get_c_opts(&args);
+            format::format_c_file(filepath, opts, output_mode, stdin_arg);
+        },
+        ".rs" => {
+            let opts = options::get_c_opts(&args);
+            format::format_rs_file(filepath, opts, output_mode, stdin_arg);
+        },
+        ".js" | ".jsx" | ".ts" | ".tsx" => {
+            let opts = options::get_js_opts(&args);
+            let c_opts = options::get_c_opts(&args);
+            format::format_js_file(filepath, file_type, opts, c_opts, output_mode, stdin_arg);
         },
         ".py" => {
-            format::format_py_file(filepath);
+            format::format_py_file(filepath, output_mode, stdin_arg);
+        },
+        ".sh" | ".bash" => {
+            format::format_sh_file(filepath, output_mode, stdin_arg);
+        },
+        ".cmake" => {
+            format::format_cmake_file(filepath, output_mode, stdin_arg);
+        },
+        ".mk" => {
+            format::format_makefile(filepath, output_mode, stdin_arg);
         },
         _ => {
             println!("Unsupported file type.");
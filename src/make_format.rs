@@ -0,0 +1,147 @@
+use regex::Regex;
+
+// Makefiles are formatted conservatively, line by line: recipe lines (the ones
+// starting with a tab) and `define` bodies are kept byte for byte, runs of
+// variable assignments get their operators aligned, continuation lines of an
+// assignment are lined up under its value, and runs of blank lines become one.
+// Everything else only loses its trailing whitespace.
+
+const CONTINUATION_INDENT: &str = "    ";
+
+// A variable assignment: `[export|override|private] NAME op value`.
+struct Assignment {
+    name: String,
+    operator: String,
+    // the first line of the value and the continuation lines after it
+    value: Vec<String>,
+}
+
+pub fn format_makefile(src: String) -> String {
+    let assignment = Regex::new(r"^((?:(?:export|override|private)\s+)*[^\s:#=?+!]+)\s*(::=|:::=|:=|\?=|\+=|!=|=)(.*)$").unwrap();
+    let lines = src.split('\n').collect::<Vec<&str>>();
+    let mut result = Vec::<String>::new();
+    // assignments waiting to be aligned with the ones right after them
+    let mut run = Vec::<Assignment>::new();
+    let mut in_define = false;
+    let mut i = 0;
+    while i < lines.len() {
+        // a logical line goes on until a line that doesn't end with `\`
+        let start = i;
+        while i + 1 < lines.len() && ends_with_continuation(lines[i]) { i += 1; }
+        let logical = &lines[start..=i];
+        i += 1;
+        let first = logical[0];
+        if in_define {
+            result.extend(logical.iter().map(|x| x.to_string()));
+            if first.trim_start().starts_with("endef") { in_define = false; }
+            continue;
+        }
+        if let Some(captures) = assignment.captures(first).filter(|_| !first.starts_with('\t')) {
+            let mut value = vec![captures[3].trim_start().to_string()];
+            value.extend(logical[1..].iter().map(|x| x.trim().to_string()));
+            run.push(Assignment {
+                name: captures[1].split_whitespace().collect::<Vec<&str>>().join(" "),
+                operator: captures[2].to_string(),
+                value,
+            });
+            continue;
+        }
+        flush_assignments(&mut run, &mut result);
+        if first.trim().is_empty() && logical.len() == 1 {
+            if result.last().is_some_and(|x| !x.is_empty()) { result.push(String::new()); }
+            continue;
+        }
+        if first.starts_with('\t') {
+            // recipes are passed to the shell as they are
+            result.extend(logical.iter().map(|x| x.to_string()));
+            continue;
+        }
+        if first.trim_start().starts_with("define ") || first.trim() == "define" { in_define = true; }
+        result.push(tidy_continued(first.trim_end()));
+        for line in &logical[1..] {
+            result.push(format!("{}{}", CONTINUATION_INDENT, tidy_continued(line.trim())));
+        }
+    }
+    flush_assignments(&mut run, &mut result);
+    while result.last().is_some_and(|x| x.is_empty()) { result.pop(); }
+    if result.is_empty() { return String::new(); }
+    return result.join("\n") + "\n";
+}
+
+// Whether the line goes on on the next one. `\\` at the end is an escaped backslash.
+fn ends_with_continuation(line: &str) -> bool {
+    let backslashes = line.len() - line.trim_end_matches('\\').len();
+    return backslashes % 2 == 1;
+}
+
+// A single space before the `\` of a continued line.
+fn tidy_continued(line: &str) -> String {
+    if !ends_with_continuation(line) { return line.to_string(); }
+    let text = line[..line.len() - 1].trim_end();
+    if text.is_empty() { return "\\".to_string(); }
+    return format!("{} \\", text);
+}
+
+// Prints a run of assignments with their operators (and values) in one column.
+fn flush_assignments(run: &mut Vec<Assignment>, result: &mut Vec<String>) {
+    let width = run.iter().map(|x| x.name.len()).max().unwrap_or(0);
+    let operator_width = run.iter().map(|x| x.operator.len()).max().unwrap_or(0);
+    for assignment in run.iter() {
+        let head = format!("{:<width$} {:>operator_width$}", assignment.name, assignment.operator);
+        let first = tidy_continued(&assignment.value[0]);
+        if first.is_empty() { result.push(head.trim_end().to_string()); }
+        else { result.push(format!("{} {}", head, first)); }
+        let continuation = " ".repeat(head.len() + 1);
+        for line in &assignment.value[1..] {
+            if line.is_empty() { result.push(String::new()); }
+            else { result.push(format!("{}{}", continuation, tidy_continued(line))); }
+        }
+    }
+    run.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments_are_aligned() {
+        let src = "\
+CC=gcc
+CFLAGS  +=   -O2 -Wall
+export PREFIX ?= /usr/local
+SRCS := main.c \\
+   util.c \\
+        io.c
+
+
+
+all: $(SRCS)   
+\t$(CC)   $(CFLAGS) -o $@ $^
+VERSION=1
+";
+        let expected = "\
+CC             = gcc
+CFLAGS        += -O2 -Wall
+export PREFIX ?= /usr/local
+SRCS          := main.c \\
+                 util.c \\
+                 io.c
+
+all: $(SRCS)
+\t$(CC)   $(CFLAGS) -o $@ $^
+VERSION = 1
+";
+        assert_eq!(format_makefile(src.to_string()), expected);
+    }
+
+    #[test]
+    fn define_bodies_are_kept() {
+        let src = "\
+define HELP
+  x  =  y
+endef
+";
+        assert_eq!(format_makefile(src.to_string()), src);
+    }
+}
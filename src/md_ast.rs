@@ -0,0 +1,520 @@
+use tree_sitter::{Tree, Node};
+use regex::Regex;
+use crate::{options, utils, txt_format, fences};
+
+// Markdown is printed block by block from the tree: paragraphs and list items
+// are reflowed with the txt paragraph engine, headings become ATX headings,
+// bullets become '-', ordered lists are renumbered with '.', pipe tables get
+// aligned, and code blocks are copied as they are. Containers (list items and
+// block quotes) print their children with a narrower width and prefix them.
+
+// Below this the text of deeply nested blocks is not narrowed any further.
+const MIN_WIDTH: usize = 20;
+
+pub fn traverse_md_ast(ast: Tree, src: String, opts: options::TxtOpts, c_opts: options::COpts) -> String {
+    // read back by the C/C++ code blocks
+    utils::set_active_c_opts(c_opts);
+    let root = ast.root_node();
+    let lines = handle_blocks(root, &src, opts.columns, opts);
+    let mut result = lines.join("\n");
+    result = result.trim_end().to_string();
+    result.push('\n');
+    return result;
+}
+
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    return node.utf8_text(src.as_bytes()).unwrap_or("");
+}
+
+fn collect_continuations(node: Node, ranges: &mut Vec<(usize, usize)>) {
+    if node.kind() == "block_continuation" {
+        ranges.push((node.start_byte(), node.end_byte()));
+        return;
+    }
+    for child in node.children(&mut node.walk()) {
+        collect_continuations(child, ranges);
+    }
+}
+
+// The text of a block without the prefixes (`> `, list indentation) that the
+// containers around it put at the start of its lines.
+fn text_without_continuations(node: Node, src: &str) -> String {
+    let mut ranges = Vec::<(usize, usize)>::new();
+    collect_continuations(node, &mut ranges);
+    ranges.sort();
+    let mut result = String::new();
+    let mut position = node.start_byte();
+    for (start, end) in ranges {
+        if start < position { continue; }
+        result += &src[position..start];
+        position = end;
+    }
+    if position < node.end_byte() { result += &src[position..node.end_byte()]; }
+    return result;
+}
+
+// The continuations of an indented code block hold the container prefixes
+// followed by the indentation of the code, which is kept.
+fn indented_code_text(node: Node, src: &str) -> String {
+    let mut ranges = Vec::<(usize, usize)>::new();
+    collect_continuations(node, &mut ranges);
+    ranges.sort();
+    let mut result = String::new();
+    let mut position = node.start_byte();
+    for (start, end) in ranges {
+        if start < position { continue; }
+        result += &src[position..start];
+        let continuation = &src[start..end];
+        if continuation.ends_with("    ") { result += "    "; }
+        else if continuation.ends_with('\t') { result += "\t"; }
+        position = end;
+    }
+    if position < node.end_byte() { result += &src[position..node.end_byte()]; }
+    return result;
+}
+
+fn is_block(node: Node) -> bool {
+    return node.is_named() && !node.kind().starts_with("list_marker") && !node.kind().starts_with("task_list_marker")
+        && !["block_continuation", "block_quote_marker"].contains(&node.kind());
+}
+
+// The last row with content in a block, so blank lines after it can be told apart.
+fn last_content_row(node: Node, src: &str) -> usize {
+    let text = get_text(node, src).trim_end();
+    return node.start_position().row + text.matches('\n').count();
+}
+
+fn blank_line_between(a: Node, b: Node, src: &str) -> bool {
+    return b.start_position().row > last_content_row(a, src) + 1;
+}
+
+// Prints the child blocks of `node`, separated by one blank line.
+fn handle_blocks(node: Node, src: &str, width: usize, opts: options::TxtOpts) -> Vec<String> {
+    let mut result = Vec::<String>::new();
+    for child in node.named_children(&mut node.walk()) {
+        if !is_block(child) { continue; }
+        let mut lines = handle_block(child, src, width, opts);
+        if lines.is_empty() { continue; }
+        if result.len() > 0 { result.push(String::new()); }
+        result.append(&mut lines);
+    }
+    return result;
+}
+
+fn handle_block(node: Node, src: &str, width: usize, opts: options::TxtOpts) -> Vec<String> {
+    return match node.kind() {
+        "section" | "document" => handle_blocks(node, src, width, opts),
+        "atx_heading" => handle_atx_heading(node, src),
+        "setext_heading" => handle_setext_heading(node, src),
+        "paragraph" => reflow(&text_without_continuations(node, src), width, opts),
+        "list" => handle_list(node, src, width, opts),
+        "block_quote" => handle_block_quote(node, src, width, opts),
+        "pipe_table" => handle_pipe_table(node, src),
+        "thematic_break" => vec!["---".to_string()],
+        "fenced_code_block" => handle_fenced_code_block(node, src),
+        // code blocks, html blocks, link reference definitions and front matter
+        _ => handle_verbatim(node, src),
+    };
+}
+
+fn handle_verbatim(node: Node, src: &str) -> Vec<String> {
+    let mut text = text_without_continuations(node, src);
+    if node.kind() == "indented_code_block" {
+        // the indentation of the first line is not part of the node
+        text = format!("{}{}", " ".repeat(4), indented_code_text(node, src).trim_start_matches(' '));
+    }
+    return text.trim_end().split('\n').map(|line| line.trim_end_matches('\r').to_string()).collect();
+}
+
+// C/C++ blocks are formatted with the C options, indented like their fence.
+// Everything else, and blocks that don't parse, are copied as they are.
+fn handle_fenced_code_block(node: Node, src: &str) -> Vec<String> {
+    let mut info = "";
+    let mut code = String::new();
+    let mut delimiters = Vec::<&str>::new();
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "info_string" => info = get_text(child, src),
+            "code_fence_content" => code = text_without_continuations(child, src),
+            "fenced_code_block_delimiter" => delimiters.push(get_text(child, src)),
+            _ => {},
+        }
+    }
+    if delimiters.len() < 2 { return handle_verbatim(node, src); }
+    let formatted = match fences::format_code_block(info, &code, utils::active_c_opts(), node.start_position().row + 1) {
+        Ok(Some(formatted)) => formatted,
+        Ok(None) => return handle_verbatim(node, src),
+        Err(report) => {
+            eprintln!("{}", report);
+            return handle_verbatim(node, src);
+        },
+    };
+    // only the spaces after the container prefixes (the block continuation) belong to the fence
+    let mut line_start = src[..node.start_byte()].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let mut continuations = Vec::<(usize, usize)>::new();
+    collect_continuations(node.parent().unwrap_or(node), &mut continuations);
+    for (_, end) in continuations {
+        if end >= line_start && end <= node.start_byte() { line_start = end; }
+    }
+    let before = &src[line_start.min(node.start_byte())..node.start_byte()];
+    let indent = if before.trim() == "" { before.to_string() } else { String::new() };
+    let mut result = vec![format!("{}{}{}", indent, delimiters[0], info)];
+    for line in formatted.split('\n') {
+        if line.is_empty() { result.push(String::new()); }
+        else { result.push(format!("{}{}", indent, line)); }
+    }
+    result.push(format!("{}{}", indent, delimiters[1]));
+    return result;
+}
+
+fn heading_text(text: &str) -> String {
+    let closing = Regex::new(r"(^|\s+)#+\s*$").unwrap();
+    let text = protect_code_spans(&closing.replace(text.trim(), ""));
+    // an ATX heading has a single line, a line break in a code span reads as a space anyway
+    return restore_code_spans(&text.split_whitespace().collect::<Vec<&str>>().join(" ")).replace('\n', " ");
+}
+
+// Whitespace inside code spans is swapped for these control characters while the
+// words around it are reflowed, so the spans come out byte for byte.
+const SPAN_WHITESPACE: [(char, char); 3] = [(' ', '\u{1}'), ('\t', '\u{2}'), ('\n', '\u{3}')];
+
+fn backtick_run(chars: &[char], start: usize) -> usize {
+    return chars[start..].iter().take_while(|c| **c == '`').count();
+}
+
+// A code span opens with a run of backticks and closes with the next run of the
+// same length; a run without a match is literal text.
+fn protect_code_spans(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' && i + 1 < chars.len() {
+            result.push(chars[i]);
+            result.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if chars[i] != '`' {
+            result.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let run = backtick_run(&chars, i);
+        let mut close = None;
+        let mut j = i + run;
+        while j < chars.len() {
+            if chars[j] != '`' {
+                j += 1;
+                continue;
+            }
+            let other = backtick_run(&chars, j);
+            if other == run {
+                close = Some(j);
+                break;
+            }
+            j += other;
+        }
+        let end = match close {
+            Some(j) => j + run,
+            None => i + run,
+        };
+        for (k, c) in chars[i..end].iter().enumerate() {
+            let inside = close.is_some() && k >= run && k < end - i - run;
+            let masked = SPAN_WHITESPACE.iter().find(|(from, _)| inside && from == c).map(|(_, to)| *to);
+            result.push(masked.unwrap_or(*c));
+        }
+        i = end;
+    }
+    return result;
+}
+
+fn restore_code_spans(text: &str) -> String {
+    let mut result = text.to_string();
+    for (from, to) in SPAN_WHITESPACE {
+        result = result.replace(to, from.to_string().as_str());
+    }
+    return result;
+}
+
+fn handle_atx_heading(node: Node, src: &str) -> Vec<String> {
+    let mut level = 1;
+    let mut text = String::new();
+    for child in node.named_children(&mut node.walk()) {
+        let kind = child.kind();
+        if kind.starts_with("atx_h") && kind.ends_with("_marker") { level = get_text(child, src).len(); }
+        else if kind == "inline" { text = heading_text(&text_without_continuations(child, src)); }
+    }
+    let marker = "#".repeat(level);
+    if text.is_empty() { return vec![marker]; }
+    return vec![format!("{} {}", marker, text)];
+}
+
+fn handle_setext_heading(node: Node, src: &str) -> Vec<String> {
+    let mut level = 1;
+    let mut text = String::new();
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "setext_h2_underline" => level = 2,
+            "paragraph" => text = heading_text(&text_without_continuations(child, src)),
+            _ => {},
+        }
+    }
+    return vec![format!("{} {}", "#".repeat(level), text)];
+}
+
+// Words that would turn a wrapped line into a heading, list item, quote, code fence or
+// setext underline if they ended up at the start of it.
+fn is_risky_line_start(word: &str) -> bool {
+    let marker = Regex::new(r"^(#{1,6}|[-+*]|\d{1,9}[.)]|=+|-+)$").unwrap();
+    return marker.is_match(word) || word.starts_with('>') || word.starts_with("```") || word.starts_with("~~~");
+}
+
+// Reflows the text of a paragraph with the txt paragraph engine, keeping hard line
+// breaks (a trailing backslash or two trailing spaces).
+fn reflow(text: &str, width: usize, opts: options::TxtOpts) -> Vec<String> {
+    let text = protect_code_spans(text);
+    let mut segments = Vec::<(String, &str)>::new();
+    let mut current = String::new();
+    for line in text.trim_end().split('\n') {
+        current += line;
+        current.push('\n');
+        let hard_break = if line.ends_with('\\') { "\\" } else if line.ends_with("  ") { "  " } else { "" };
+        if !hard_break.is_empty() {
+            let words = current.trim_end().trim_end_matches('\\').to_string();
+            segments.push((words, hard_break));
+            current = String::new();
+        }
+    }
+    if current.trim() != "" { segments.push((current, "")); }
+    let paragraph_opts = options::TxtOpts { columns: width.max(MIN_WIDTH), spacing: opts.spacing };
+    let mut result = Vec::<String>::new();
+    for (words, hard_break) in segments {
+        let formatted = txt_format::format_paragraph(words, paragraph_opts);
+        let mut lines = formatted.split('\n').map(|line| line.trim_end().to_string())
+            .filter(|line| !line.is_empty()).collect::<Vec<String>>();
+        let mut i = 1;
+        while i < lines.len() {
+            let first_word = lines[i].split(' ').next().unwrap_or("").to_string();
+            if is_risky_line_start(&first_word) {
+                lines[i - 1] = format!("{} {}", lines[i - 1], first_word);
+                lines[i] = lines[i][first_word.len()..].trim_start().to_string();
+                if lines[i].is_empty() {
+                    lines.remove(i);
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        if let Some(last) = lines.last_mut() { last.push_str(hard_break); }
+        result.append(&mut lines);
+    }
+    // a code span that went over a line break keeps it
+    let result = result.iter().flat_map(|line| restore_code_spans(line).split('\n').map(|x| x.to_string()).collect::<Vec<String>>()).collect();
+    return result;
+}
+
+fn prefix_lines(lines: Vec<String>, first: &str, rest: &str) -> Vec<String> {
+    let mut result = Vec::<String>::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        let prefix = if i == 0 { first } else { rest };
+        if line.is_empty() { result.push(prefix.trim_end().to_string()); }
+        else { result.push(format!("{}{}", prefix, line)); }
+    }
+    return result;
+}
+
+fn handle_block_quote(node: Node, src: &str, width: usize, opts: options::TxtOpts) -> Vec<String> {
+    let lines = handle_blocks(node, src, width.saturating_sub(2), opts);
+    return prefix_lines(lines, "> ", "> ");
+}
+
+fn handle_list(node: Node, src: &str, width: usize, opts: options::TxtOpts) -> Vec<String> {
+    let items = node.named_children(&mut node.walk()).filter(|child| child.kind() == "list_item").collect::<Vec<Node>>();
+    let is_loose = items.windows(2).any(|pair| blank_line_between(pair[0], pair[1], src));
+    let mut number = items.first().map(|item| first_number(*item, src)).unwrap_or(None);
+    let mut result = Vec::<String>::new();
+    for (i, item) in items.iter().enumerate() {
+        let marker = match number {
+            Some(n) => format!("{}. ", n),
+            None => "- ".to_string(),
+        };
+        if i > 0 && is_loose { result.push(String::new()); }
+        let mut lines = handle_list_item(*item, src, width.saturating_sub(marker.len()), opts);
+        if lines.is_empty() { lines.push(String::new()); }
+        result.append(&mut prefix_lines(lines, &marker, &" ".repeat(marker.len())));
+        number = number.map(|n| n + 1);
+    }
+    return result;
+}
+
+// The number an ordered list starts at, or None for bullet lists.
+fn first_number(item: Node, src: &str) -> Option<usize> {
+    let marker = item.named_child(0)?;
+    if marker.kind() != "list_marker_dot" && marker.kind() != "list_marker_parenthesis" { return None; }
+    let digits = get_text(marker, src).trim().trim_end_matches(['.', ')']).to_string();
+    return digits.parse::<usize>().ok();
+}
+
+fn handle_list_item(node: Node, src: &str, width: usize, opts: options::TxtOpts) -> Vec<String> {
+    let mut result = Vec::<String>::new();
+    let mut task_marker = "";
+    let mut previous: Option<Node> = None;
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "task_list_marker_checked" => task_marker = "[x] ",
+            "task_list_marker_unchecked" => task_marker = "[ ] ",
+            _ => {},
+        }
+        if !is_block(child) { continue; }
+        let mut lines = handle_block(child, src, width, opts);
+        if previous.is_some() && blank_line_between(previous.unwrap(), child, src) { result.push(String::new()); }
+        if previous.is_none() && !task_marker.is_empty() && lines.len() > 0 { lines[0] = format!("{}{}", task_marker, lines[0]); }
+        result.append(&mut lines);
+        previous = Some(child);
+    }
+    return result;
+}
+
+fn handle_pipe_table(node: Node, src: &str) -> Vec<String> {
+    let mut rows = Vec::<Vec<String>>::new();
+    let mut alignments = Vec::<(bool, bool)>::new();
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "pipe_table_header" | "pipe_table_row" => {
+                let cells = child.named_children(&mut child.walk()).filter(|cell| cell.kind() == "pipe_table_cell")
+                    .map(|cell| get_text(cell, src).trim().to_string()).collect::<Vec<String>>();
+                rows.push(cells);
+            },
+            "pipe_table_delimiter_row" => {
+                for cell in child.named_children(&mut child.walk()) {
+                    if cell.kind() != "pipe_table_delimiter_cell" { continue; }
+                    let text = get_text(cell, src).trim();
+                    alignments.push((text.starts_with(':'), text.ends_with(':')));
+                }
+            },
+            _ => {},
+        }
+    }
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0).max(alignments.len());
+    alignments.resize(columns, (false, false));
+    let mut widths = vec![3; columns];
+    for row in rows.iter_mut() {
+        row.resize(columns, String::new());
+        for (j, cell) in row.iter().enumerate() {
+            widths[j] = widths[j].max(cell.chars().count());
+        }
+    }
+    let print_row = |row: &Vec<String>| -> String {
+        let mut cells = Vec::<String>::new();
+        for (j, cell) in row.iter().enumerate() {
+            let padding = widths[j] - cell.chars().count();
+            let cell = match alignments[j] {
+                (false, true) => format!("{}{}", " ".repeat(padding), cell),
+                (true, true) => format!("{}{}{}", " ".repeat(padding / 2), cell, " ".repeat(padding - padding / 2)),
+                _ => format!("{}{}", cell, " ".repeat(padding)),
+            };
+            cells.push(cell);
+        }
+        return format!("| {} |", cells.join(" | "));
+    };
+    let mut result = Vec::<String>::new();
+    for (i, row) in rows.iter().enumerate() {
+        result.push(print_row(row));
+        if i == 0 {
+            let delimiters = (0..columns).map(|j| {
+                let (left, right) = alignments[j];
+                let dashes = "-".repeat(widths[j] - left as usize - right as usize);
+                return format!("{}{}{}", if left { ":" } else { "" }, dashes, if right { ":" } else { "" });
+            }).collect::<Vec<String>>();
+            result.push(format!("| {} |", delimiters.join(" | ")));
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md_format;
+
+    fn format(src: &str, columns: usize) -> String {
+        let ast = md_format::parse_existing_md_file(src.to_string());
+        let opts = options::TxtOpts { columns, spacing: 1 };
+        return traverse_md_ast(ast, src.to_string(), opts, options::COpts::default());
+    }
+
+    #[test]
+    fn code_spans_keep_their_whitespace() {
+        let src = "\
+# Title with `a  b` span
+
+Some `a   b` text and ``x  `y`  z`` here, long enough to be reflowed.
+";
+        let expected = "\
+# Title with `a  b` span
+
+Some `a   b` text and
+``x  `y`  z`` here, long
+enough to be reflowed.
+";
+        assert_eq!(format(src, 30), expected);
+    }
+
+    #[test]
+    fn indented_code_blocks_keep_every_line() {
+        let src = "\
+# Title
+
+    fn a() {
+        b();
+    }
+
+- item
+
+      code in item
+      more
+
+> quote
+>
+>     quoted code
+>     line two
+";
+        assert_eq!(format(src, 80), src);
+        assert_eq!(format(&format(src, 80), 80), src);
+    }
+
+    #[test]
+    fn fenced_c_blocks() {
+        let src = "\
+# Notes
+
+- A list item with code:
+
+  ```c
+  int add(int a,int b){return a+b;}
+  ```
+
+```c
+int broken( {
+```
+";
+        let expected = "\
+# Notes
+
+- A list item with code:
+
+  ```c
+  int add(int a, int b)
+  {
+  	return a + b;
+  }
+  ```
+
+```c
+int broken( {
+```
+";
+        assert_eq!(format(src, 80), expected);
+    }
+}
@@ -0,0 +1,17 @@
+use tree_sitter::{Parser, Language};
+use crate::{utils, md_ast};
+
+pub fn parse_md_file(path: String) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_md::LANGUAGE.into()).expect("Error loading Markdown parser.");
+    let contents = std::fs::read_to_string(path).unwrap_or("".to_string());
+    let tree = parser.parse(contents.clone(), None).unwrap();
+    return tree;
+}
+
+pub fn parse_existing_md_file(text: String) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_md::LANGUAGE.into()).expect("Error loading Markdown parser.");
+    let tree = parser.parse(text, None).unwrap();
+    return tree;
+}
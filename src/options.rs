@@ -2,14 +2,340 @@ use std::io::Read;
 
 use crate::utils;
 
+// An external program that formats the files of one extension in place of
+// the built-in formatter. It is run directly, never through a shell.
+#[derive(Clone, Debug)]
+pub struct ExternalOpts {
+    pub program: String,
+    pub args: Vec<String>,
+    pub mode: utils::ExternalMode,
+    pub exit_codes: Vec<i32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct JsonOpts {
+    // one level of indentation: a number of spaces or a tab
+    pub indent: String,
+    pub sort_keys: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct JsOpts {
+    pub semicolons: utils::Semicolons,
+    pub quotes: utils::QuoteStyle,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct TxtOpts {
     pub columns: usize,
     pub spacing: usize,
 }
 
+// Where the opening brace goes for each kind of block.
+// `catch` and `else` use the same placements: Attach keeps them on the closing brace's line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BraceWrapping {
+    pub function: utils::BracePlacement,
+    pub class: utils::BracePlacement,
+    pub union: utils::BracePlacement,
+    pub enumeration: utils::BracePlacement,
+    pub namespace: utils::BracePlacement,
+    pub control: utils::BracePlacement,
+    pub lambda: utils::BracePlacement,
+    pub extern_block: utils::BracePlacement,
+    pub catch: utils::BracePlacement,
+    pub else_clause: utils::BracePlacement,
+}
+
+impl BraceWrapping {
+    pub fn preset(style: utils::Style) -> BraceWrapping {
+        use utils::BracePlacement::{Attach, NewLine};
+        use utils::Style;
+        let all = |p| BraceWrapping {
+            function: p, class: p, union: p, enumeration: p, namespace: p,
+            control: p, lambda: p, extern_block: p, catch: p, else_clause: p,
+        };
+        match style {
+            Style::Allman | Style::Gnu | Style::Whitesmiths => return all(NewLine),
+            Style::Stroustrup => {
+                return BraceWrapping {
+                    control: Attach,
+                    lambda: Attach,
+                    ..all(NewLine)
+                };
+            },
+            Style::KnR | Style::Linux => return BraceWrapping { function: NewLine, ..all(Attach) },
+            Style::OneTrueBrace => return all(Attach),
+        }
+    }
+
+    pub fn get(&self, kind: utils::BraceKind) -> utils::BracePlacement {
+        use utils::BraceKind::*;
+        match kind {
+            Function => return self.function,
+            Class => return self.class,
+            Union => return self.union,
+            Enum => return self.enumeration,
+            Namespace => return self.namespace,
+            Control => return self.control,
+            Lambda => return self.lambda,
+            Extern => return self.extern_block,
+            Catch => return self.catch,
+            Else => return self.else_clause,
+        }
+    }
+
+    pub fn set(&mut self, kind: utils::BraceKind, placement: utils::BracePlacement) {
+        use utils::BraceKind::*;
+        match kind {
+            Function => self.function = placement,
+            Class => self.class = placement,
+            Union => self.union = placement,
+            Enum => self.enumeration = placement,
+            Namespace => self.namespace = placement,
+            Control => self.control = placement,
+            Lambda => self.lambda = placement,
+            Extern => self.extern_block = placement,
+            Catch => self.catch = placement,
+            Else => self.else_clause = placement,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct COpts {
+    pub style: utils::Style,
+    pub braces: BraceWrapping,
+    pub attribute_break: utils::AttributeBreak,
+    pub requires_clause: utils::RequiresClause,
+    pub indent_extern_blocks: bool,
+    pub namespace_indentation: utils::NamespaceIndentation,
+    pub namespace_end_comments: bool,
+    pub compact_namespaces: bool,
+    pub access_modifier_offset: utils::AccessModifierOffset,
+    pub member_order: Option<[utils::MemberKind; 4]>,
+    pub ctor_initializer_break: utils::CtorInitializerBreak,
+    pub ctor_initializer_packing: utils::CtorInitializerPacking,
+    pub lambda_body_anchor: utils::LambdaBodyAnchor,
+    pub template_break: utils::TemplateBreak,
+    pub space_after_template: bool,
+    pub spaced_angle_brackets: bool,
+}
+
+impl Default for COpts {
+    fn default() -> COpts {
+        let style = utils::Style::Stroustrup;
+        return COpts {
+            style,
+            braces: BraceWrapping::preset(style),
+            attribute_break: utils::AttributeBreak::Functions,
+            requires_clause: utils::RequiresClause::OwnLine,
+            indent_extern_blocks: true,
+            namespace_indentation: utils::NamespaceIndentation::All,
+            namespace_end_comments: false,
+            compact_namespaces: false,
+            access_modifier_offset: utils::AccessModifierOffset::Outdent,
+            member_order: None,
+            ctor_initializer_break: utils::CtorInitializerBreak::BeforeColon,
+            ctor_initializer_packing: utils::CtorInitializerPacking::All,
+            lambda_body_anchor: utils::LambdaBodyAnchor::Statement,
+            template_break: utils::TemplateBreak::Always,
+            space_after_template: true,
+            spaced_angle_brackets: false,
+        };
+    }
+}
+
+pub fn get_c_opts(args: &Vec<String>) -> COpts {
+    let mut opts = COpts::default();
+    opts.style = get_c_style(args);
+    opts.braces = BraceWrapping::preset(opts.style);
+    for (kind, placement) in get_brace_overrides(args) {
+        opts.braces.set(kind, placement);
+    }
+    if let Some(value) = get_flag_value(args, "--attribute-break") {
+        match value.as_str() {
+            "always" => opts.attribute_break = utils::AttributeBreak::Always,
+            "functions" => opts.attribute_break = utils::AttributeBreak::Functions,
+            "never" => opts.attribute_break = utils::AttributeBreak::Never,
+            _ => eprintln!("Unknown value for --attribute-break: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--requires-clause") {
+        match value.as_str() {
+            "own-line" => opts.requires_clause = utils::RequiresClause::OwnLine,
+            "template-line" => opts.requires_clause = utils::RequiresClause::TemplateLine,
+            _ => eprintln!("Unknown value for --requires-clause: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--extern-block") {
+        match value.as_str() {
+            "indent" => opts.indent_extern_blocks = true,
+            "no-indent" => opts.indent_extern_blocks = false,
+            _ => eprintln!("Unknown value for --extern-block: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--namespace-indent") {
+        match value.as_str() {
+            "none" => opts.namespace_indentation = utils::NamespaceIndentation::None,
+            "inner" => opts.namespace_indentation = utils::NamespaceIndentation::Inner,
+            "all" => opts.namespace_indentation = utils::NamespaceIndentation::All,
+            _ => eprintln!("Unknown value for --namespace-indent: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--namespace-comments") {
+        match value.as_str() {
+            "add" => opts.namespace_end_comments = true,
+            "keep" => opts.namespace_end_comments = false,
+            _ => eprintln!("Unknown value for --namespace-comments: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--nested-namespaces") {
+        match value.as_str() {
+            "compact" => opts.compact_namespaces = true,
+            "keep" => opts.compact_namespaces = false,
+            _ => eprintln!("Unknown value for --nested-namespaces: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--access-offset") {
+        match value.as_str() {
+            "outdent" | "-4" => opts.access_modifier_offset = utils::AccessModifierOffset::Outdent,
+            "half" | "-2" => opts.access_modifier_offset = utils::AccessModifierOffset::Half,
+            "none" | "0" => opts.access_modifier_offset = utils::AccessModifierOffset::None,
+            _ => eprintln!("Unknown value for --access-offset: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--ctor-init-break") {
+        match value.as_str() {
+            "before-colon" => opts.ctor_initializer_break = utils::CtorInitializerBreak::BeforeColon,
+            "before-comma" => opts.ctor_initializer_break = utils::CtorInitializerBreak::BeforeComma,
+            "after-colon" => opts.ctor_initializer_break = utils::CtorInitializerBreak::AfterColon,
+            _ => eprintln!("Unknown value for --ctor-init-break: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--ctor-init-pack") {
+        match value.as_str() {
+            "all" => opts.ctor_initializer_packing = utils::CtorInitializerPacking::All,
+            "bin-pack" => opts.ctor_initializer_packing = utils::CtorInitializerPacking::BinPack,
+            "fit" => opts.ctor_initializer_packing = utils::CtorInitializerPacking::Fit,
+            "one-per-line" => opts.ctor_initializer_packing = utils::CtorInitializerPacking::OnePerLine,
+            _ => eprintln!("Unknown value for --ctor-init-pack: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--lambda-indent") {
+        match value.as_str() {
+            "statement" => opts.lambda_body_anchor = utils::LambdaBodyAnchor::Statement,
+            "call" => opts.lambda_body_anchor = utils::LambdaBodyAnchor::Call,
+            _ => eprintln!("Unknown value for --lambda-indent: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--template-break") {
+        match value.as_str() {
+            "always" => opts.template_break = utils::TemplateBreak::Always,
+            "over-limit" => opts.template_break = utils::TemplateBreak::OverLimit,
+            "never" => opts.template_break = utils::TemplateBreak::Never,
+            _ => eprintln!("Unknown value for --template-break: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--template-space") {
+        match value.as_str() {
+            "space" => opts.space_after_template = true,
+            "none" => opts.space_after_template = false,
+            _ => eprintln!("Unknown value for --template-space: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--angle-brackets") {
+        match value.as_str() {
+            "merged" => opts.spaced_angle_brackets = false,
+            "spaced" => opts.spaced_angle_brackets = true,
+            _ => eprintln!("Unknown value for --angle-brackets: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--member-order") {
+        match get_member_order(&value) {
+            Some(order) => opts.member_order = Some(order),
+            None => eprintln!("Unknown value for --member-order: '{}'. Expected 'default' or an order of types, constructors, methods and fields.", value),
+        }
+    }
+    return opts;
+}
+
+// `default`, or all four member kinds separated by commas, e.g. `types,constructors,methods,fields`.
+fn get_member_order(value: &str) -> Option<[utils::MemberKind; 4]> {
+    let default_order = [
+        utils::MemberKind::Type,
+        utils::MemberKind::Constructor,
+        utils::MemberKind::Method,
+        utils::MemberKind::Field,
+    ];
+    if value == "default" { return Some(default_order); }
+    let mut order = Vec::<utils::MemberKind>::new();
+    for name in value.split(",") {
+        let kind = match name.trim() {
+            "types" => utils::MemberKind::Type,
+            "constructors" => utils::MemberKind::Constructor,
+            "methods" => utils::MemberKind::Method,
+            "fields" => utils::MemberKind::Field,
+            _ => return None,
+        };
+        if order.contains(&kind) { return None; }
+        order.push(kind);
+    }
+    return order.try_into().ok();
+}
+
+// Returns the (lowercased) argument following `flag`, if there is one.
+fn get_flag_value(args: &Vec<String>, flag: &str) -> Option<String> {
+    let idx = args.iter().position(|x| x == flag)?;
+    return args.get(idx + 1).map(|x| x.to_lowercase());
+}
+
+// Overrides look like `-b namespace=attach function=newline`.
+fn get_brace_overrides(args: &Vec<String>) -> Vec<(utils::BraceKind, utils::BracePlacement)> {
+    let mut result = Vec::new();
+    let start = match args.iter().position(|x| x == "-b" || x == "--braces") {
+        Some(idx) => idx + 1,
+        None => return result,
+    };
+    for arg in args[start..].iter().take_while(|x| !x.starts_with("-")) {
+        let (kind, placement) = match arg.to_lowercase().split_once("=") {
+            Some((a, b)) => (a.to_string(), b.to_string()),
+            None => {
+                eprintln!("Ignoring brace override '{}': expected <kind>=<attach|newline>.", arg);
+                continue;
+            },
+        };
+        let kind = match kind.as_str() {
+            "function" => utils::BraceKind::Function,
+            "class" | "struct" => utils::BraceKind::Class,
+            "union" => utils::BraceKind::Union,
+            "enum" => utils::BraceKind::Enum,
+            "namespace" => utils::BraceKind::Namespace,
+            "control" => utils::BraceKind::Control,
+            "lambda" => utils::BraceKind::Lambda,
+            "extern" => utils::BraceKind::Extern,
+            "catch" => utils::BraceKind::Catch,
+            "else" => utils::BraceKind::Else,
+            _ => {
+                eprintln!("Ignoring brace override '{}': unknown kind '{}'.", arg, kind);
+                continue;
+            },
+        };
+        let placement = match placement.as_str() {
+            "attach" => utils::BracePlacement::Attach,
+            "newline" => utils::BracePlacement::NewLine,
+            _ => {
+                eprintln!("Ignoring brace override '{}': unknown placement '{}'.", arg, placement);
+                continue;
+            },
+        };
+        result.push((kind, placement));
+    }
+    return result;
+}
+
 pub fn get_c_style(args: &Vec<String>) -> utils::Style {
-    let args: Vec<String> = args.into_iter().map(|x| x.to_lowercase()).collect();
+    let args: Vec<String> = args.iter().map(|x| x.to_lowercase()).collect();
     let result: utils::Style;
     if args.contains(&"-s".to_string()) || args.contains(&"--style".to_string()) {
         if args.contains(&"allman".to_string()) { 
@@ -21,6 +347,18 @@ pub fn get_c_style(args: &Vec<String>) -> utils::Style {
         else if args.contains(&"stroustrup".to_string()) { 
             result = utils::Style::Stroustrup; 
         }
+        else if args.contains(&"gnu".to_string()) { 
+            result = utils::Style::Gnu; 
+        }
+        else if args.contains(&"whitesmiths".to_string()) { 
+            result = utils::Style::Whitesmiths; 
+        }
+        else if args.contains(&"linux".to_string()) { 
+            result = utils::Style::Linux; 
+        }
+        else if args.contains(&"1tbs".to_string()) || args.contains(&"otbs".to_string()) { 
+            result = utils::Style::OneTrueBrace; 
+        }
         else { result = utils::Style::Stroustrup; }
     }
     else { result = utils::Style::Stroustrup; }
@@ -28,20 +366,195 @@ pub fn get_c_style(args: &Vec<String>) -> utils::Style {
 }
 
 pub fn get_txt_opts(args: &Vec<String>) -> TxtOpts {
-    let opts: TxtOpts;
-    if args.contains(&"-o".to_string()) || args.contains(&"--opts".to_string()) {
+    let opts = if args.contains(&"-o".to_string()) || args.contains(&"--opts".to_string()) {
         let args = args.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
-        opts = utils::extract_txt_opts(args);
+        utils::extract_txt_opts(args)
     }
     else {
-        opts = TxtOpts{columns: 80, spacing: 1};
+        TxtOpts{columns: 80, spacing: 1}
+    };
+    return opts;
+}
+
+pub fn get_json_opts(args: &Vec<String>) -> JsonOpts {
+    let mut opts = JsonOpts { indent: "  ".to_string(), sort_keys: args.contains(&"--sort-keys".to_string()) };
+    if let Some(value) = get_flag_value(args, "--json-indent") {
+        if value == "tab" { opts.indent = "\t".to_string(); }
+        else {
+            match value.parse::<usize>() {
+                Ok(width) => opts.indent = " ".repeat(width),
+                Err(_) => eprintln!("Unknown value for --json-indent: '{}'. Using the default.", value),
+            }
+        }
     }
     return opts;
 }
 
-pub fn check_write_arg(args: &Vec<String>) -> bool {
-    let contains_write_arg = args.contains(&"-w".to_string());
-    return contains_write_arg;
+pub fn get_js_opts(args: &Vec<String>) -> JsOpts {
+    let mut opts = JsOpts { semicolons: utils::Semicolons::Always, quotes: utils::QuoteStyle::Double };
+    if let Some(value) = get_flag_value(args, "--semicolons") {
+        match value.as_str() {
+            "always" => opts.semicolons = utils::Semicolons::Always,
+            "never" => opts.semicolons = utils::Semicolons::Never,
+            "keep" => opts.semicolons = utils::Semicolons::Keep,
+            _ => eprintln!("Unknown value for --semicolons: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(args, "--quotes") {
+        match value.as_str() {
+            "double" => opts.quotes = utils::QuoteStyle::Double,
+            "single" => opts.quotes = utils::QuoteStyle::Single,
+            "keep" => opts.quotes = utils::QuoteStyle::Keep,
+            _ => eprintln!("Unknown value for --quotes: '{}'. Using the default.", value),
+        }
+    }
+    return opts;
+}
+
+pub fn check_output_mode(args: &Vec<String>) -> utils::OutputMode {
+    if args.contains(&"-w".to_string()) || args.contains(&"--write".to_string()) { return utils::OutputMode::Write; }
+    if args.contains(&"--check".to_string()) { return utils::OutputMode::Check; }
+    if args.contains(&"--diff".to_string()) { return utils::OutputMode::Diff; }
+    return utils::OutputMode::Print;
+}
+
+// The first `--external <ext> "<program> <args>"` whose extension the file has.
+// The template is split on whitespace into the program and its arguments, and
+// `{path}` in an argument is replaced with the path of the file being formatted.
+// `--external-mode` and `--external-codes` belong to the `--external` before them.
+pub fn get_external_opts(args: &Vec<String>, filepath: &String) -> Option<ExternalOpts> {
+    let mut entry: Option<usize> = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg != "--external" || i + 2 >= args.len() { continue; }
+        let ext = if args[i + 1].starts_with('.') { args[i + 1].clone() } else { format!(".{}", args[i + 1]) };
+        if filepath.ends_with(&ext) {
+            entry = Some(i);
+            break;
+        }
+    }
+    let entry = entry?;
+    let mut words = args[entry + 2].split_whitespace().map(|x| x.to_string());
+    let program = match words.next() {
+        Some(program) => program,
+        None => {
+            eprintln!("Empty command for --external. Using the built-in formatter.");
+            return None;
+        },
+    };
+    let mut opts = ExternalOpts { program, args: words.collect(), mode: utils::ExternalMode::Pipe, exit_codes: vec![0] };
+    let end = args.iter().skip(entry + 3).position(|x| x == "--external").map(|x| x + entry + 3).unwrap_or(args.len());
+    let entry_args = args[entry + 3..end].to_vec();
+    if let Some(value) = get_flag_value(&entry_args, "--external-mode") {
+        match value.as_str() {
+            "stdin" | "pipe" => opts.mode = utils::ExternalMode::Pipe,
+            "in-place" | "inplace" => opts.mode = utils::ExternalMode::InPlace,
+            _ => eprintln!("Unknown value for --external-mode: '{}'. Using the default.", value),
+        }
+    }
+    if let Some(value) = get_flag_value(&entry_args, "--external-codes") {
+        let codes = value.split(',').map(|x| x.trim().parse::<i32>()).collect::<Vec<_>>();
+        if codes.iter().all(|code| code.is_ok()) { opts.exit_codes = codes.into_iter().map(|code| code.unwrap()).collect(); }
+        else { eprintln!("Unknown value for --external-codes: '{}'. Using the default.", value); }
+    }
+    return Some(opts);
+}
+
+// The name of the file whose flags apply to every file below its directory.
+const CONFIG_FILE_NAME: &str = ".blfmt";
+
+// Flags that only make sense on the command line.
+const COMMAND_LINE_ONLY: [&str; 9] = ["-", "--stdin", "-h", "--help", "-w", "--write", "--check", "--diff", "--config"];
+
+// Flags that may be given several times, so the config file adds to them.
+// The mode and codes of an `--external` entry follow it.
+const REPEATABLE_FLAGS: [&str; 3] = ["--external", "--external-mode", "--external-codes"];
+
+// Short and long spellings of the same flag.
+const FLAG_ALIASES: [(&str, &str); 4] = [("-s", "--style"), ("-b", "--braces"), ("-o", "--opts"), ("-t", "--titles")];
+
+// Adds the flags of the config file to the ones given on the command line.
+// The file is the one given with `--config <path>`, or else the closest
+// `.blfmt` in the directory of the file or one of its parents (the current
+// directory for stdin). Flags given on the command line win over the file.
+pub fn with_config_file(filepath: &String, args: Vec<String>) -> Vec<String> {
+    let config = match args.iter().position(|x| x == "--config") {
+        Some(idx) => args.get(idx + 1).map(std::path::PathBuf::from),
+        None => find_config_file(filepath, args.contains(&"-".to_string())),
+    };
+    let path = match config {
+        Some(path) => path,
+        None => return args,
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Could not read the config file '{}': {}", path.display(), e);
+            return args;
+        },
+    };
+    let mut result = args.clone();
+    for group in split_flag_groups(read_config_words(&contents)) {
+        let flag = group[0].as_str();
+        if !flag.starts_with('-') {
+            eprintln!("Ignoring '{}' in '{}': expected a flag.", flag, path.display());
+            continue;
+        }
+        if COMMAND_LINE_ONLY.contains(&flag) {
+            eprintln!("Ignoring '{}' in '{}': it can only be given on the command line.", flag, path.display());
+            continue;
+        }
+        let spellings = FLAG_ALIASES.iter().find(|(a, b)| *a == flag || *b == flag).map(|(a, b)| vec![*a, *b]).unwrap_or(vec![flag]);
+        let is_given = args.iter().any(|x| spellings.contains(&x.as_str()));
+        if is_given && !REPEATABLE_FLAGS.contains(&flag) { continue; }
+        result.extend(group);
+    }
+    return result;
+}
+
+fn find_config_file(filepath: &String, is_stdin: bool) -> Option<std::path::PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    let start = if is_stdin { cwd } else { cwd.join(filepath) };
+    for dir in start.ancestors() {
+        let candidate = dir.join(CONFIG_FILE_NAME);
+        if candidate.is_file() { return Some(candidate); }
+    }
+    return None;
+}
+
+// The words of the config file: whitespace separates them, quotes group
+// them and `#` starts a comment that goes on until the end of the line.
+fn read_config_words(contents: &str) -> Vec<String> {
+    let mut words = Vec::<String>::new();
+    for line in contents.lines() {
+        let mut word = String::new();
+        let mut quote: Option<char> = None;
+        let mut has_word = false;
+        for c in line.chars() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => word.push(c),
+                None if c == '"' || c == '\'' => { quote = Some(c); has_word = true; },
+                None if c == '#' && !has_word => break,
+                None if c.is_whitespace() => {
+                    if has_word { words.push(std::mem::take(&mut word)); }
+                    has_word = false;
+                },
+                None => { word.push(c); has_word = true; },
+            }
+        }
+        if has_word { words.push(word); }
+    }
+    return words;
+}
+
+// A flag along with the values after it, e.g. `-b function=attach control=newline`.
+fn split_flag_groups(words: Vec<String>) -> Vec<Vec<String>> {
+    let mut groups = Vec::<Vec<String>>::new();
+    for word in words {
+        if word.starts_with('-') || groups.is_empty() { groups.push(vec![word]); }
+        else { groups.last_mut().unwrap().push(word); }
+    }
+    return groups;
 }
 
 pub fn check_stdin_arg(args: &Vec<String>) -> String {
@@ -67,3 +580,55 @@ pub fn check_help_arg(args: &Vec<String>) -> usize {
     }
     return 0;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        return words.iter().map(|word| word.to_string()).collect();
+    }
+
+    #[test]
+    fn external_entries_have_their_own_mode_and_codes() {
+        let args = args(&[
+            "--external", ".lua", "stylua {path}", "--external-mode", "in-place", "--external-codes", "0,2",
+            "--external", "js", "prettier --stdin-filepath {path}",
+        ]);
+        let lua = get_external_opts(&args, &"a/b.lua".to_string()).unwrap();
+        assert_eq!(lua.program, "stylua");
+        assert_eq!(lua.args, vec!["{path}"]);
+        assert!(matches!(lua.mode, utils::ExternalMode::InPlace));
+        assert_eq!(lua.exit_codes, vec![0, 2]);
+        let js = get_external_opts(&args, &"a/b.js".to_string()).unwrap();
+        assert_eq!(js.program, "prettier");
+        assert!(matches!(js.mode, utils::ExternalMode::Pipe));
+        assert_eq!(js.exit_codes, vec![0]);
+        assert!(get_external_opts(&args, &"a/b.py".to_string()).is_none());
+    }
+
+    #[test]
+    fn brace_overrides() {
+        use utils::BraceKind::{Enum, Function, Namespace, Union};
+        use utils::BracePlacement::{Attach, NewLine};
+        let opts = get_c_opts(&args(&["a.c", "-s", "allman", "-b", "union=attach", "Enum=ATTACH", "oops", "-w"]));
+        assert_eq!(opts.braces.get(Union), Attach);
+        assert_eq!(opts.braces.get(Enum), Attach);
+        assert_eq!(opts.braces.get(Function), NewLine);
+        let overrides = get_brace_overrides(&args(&["--braces", "namespace=newline", "lambda", "else=sideways"]));
+        assert_eq!(overrides, vec![(Namespace, NewLine)]);
+        assert!(get_brace_overrides(&args(&["a.c", "union=attach"])).is_empty());
+    }
+
+    #[test]
+    fn txt_options_have_one_spelling() {
+        let path = std::env::temp_dir().join(format!("blfmt-opts-{}", std::process::id()));
+        std::fs::write(&path, "--opts 60 2\n").unwrap();
+        let config = path.to_string_lossy().to_string();
+        let opts = get_txt_opts(&with_config_file(&"a.md".to_string(), args(&["a.md", "--config", &config])));
+        assert_eq!((opts.columns, opts.spacing), (60, 2));
+        let opts = get_txt_opts(&with_config_file(&"a.md".to_string(), args(&["a.md", "-o", "72", "1", "--config", &config])));
+        assert_eq!((opts.columns, opts.spacing), (72, 1));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
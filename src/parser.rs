@@ -6,25 +6,25 @@ pub fn parse_args() -> Option<(String, Vec<String>)> {
     let args: Vec<String> = env::args().collect();
     // stdin and help are special commands
     if args.contains(&"-".to_string()) || args.contains(&"--stdin".to_string()) {
-        let mut most_likely = String::new();
-        for arg in args {
-            if !arg.contains("-") {
-                if !arg.contains(".") {
-                    most_likely = arg;
-                }  
-                else {
-                    if most_likely.is_empty() {
-                        most_likely = arg;
-                    }
-                }
-            } 
-        }
-        if most_likely.is_empty() {
+        // the extension is the word next to the flag, `blfmt - py --check` or `blfmt py -`
+        let idx = args.iter().position(|x| x == "-" || x == "--stdin").unwrap();
+        let is_word = |i: usize| i > 0 && i < args.len() && !args[i].starts_with("-");
+        let ext_idx = if is_word(idx + 1) { idx + 1 } else if is_word(idx - 1) { idx - 1 } else {
             utils::print_usage();
             return None;
-        }
+        };
+        let mut most_likely = args[ext_idx].clone();
         if !most_likely.starts_with(".") { most_likely = format!(".{}", most_likely) };
-        return Some((most_likely, vec!["-".to_string()]));
+        if args.contains(&"-w".to_string()) || args.contains(&"--write".to_string()) {
+            eprintln!("There is no file to write to when reading from stdin.");
+            std::process::exit(1);
+        }
+        // the other flags apply as they do to files, with `-` marking stdin
+        let mut rest = vec!["-".to_string()];
+        for (i, arg) in args.iter().enumerate().skip(1) {
+            if i != idx && i != ext_idx { rest.push(arg.to_owned()); }
+        }
+        return Some((most_likely, rest));
     }
     if args.contains(&"-h".to_string()) || args.contains(&"--help".to_string()) {
         return Some((String::new(), vec!["-h".to_string()]));
@@ -39,7 +39,7 @@ pub fn parse_args() -> Option<(String, Vec<String>)> {
     }
     else {
         let path = &args[1].to_string();
-        let rest = &args[2..].into_iter().map(|x| x.to_owned()).collect::<Vec<String>>();
+        let rest = &args[2..].iter().map(|x| x.to_owned()).collect::<Vec<String>>();
         return Some((path.to_owned(), rest.to_owned()));
     }
 }
@@ -0,0 +1,684 @@
+use tree_sitter::{Tree, Node};
+use std::collections::HashSet;
+
+// Like the Go formatter, the Python formatter works on the tokens of the tree.
+// The tokens are grouped into logical lines (a statement or the header of a
+// compound statement), each logical line is printed with normalized spacing and
+// re-indented from the blocks that contain it, the blank lines between them follow
+// the PEP 8 rules, and lines that get too long are split inside their brackets.
+
+const LINE_LENGTH: usize = 88;
+const INDENT: &str = "    ";
+
+// Nodes printed as they are, without looking at their children.
+const ATOMIC_NODES: [&str; 2] = ["string", "comment"];
+
+// Nodes whose children are statements, so a ';' in them only separates statements.
+const STATEMENT_CONTAINERS: [&str; 2] = ["module", "block"];
+
+const SPLAT_NODES: [&str; 4] = ["list_splat", "dictionary_splat", "list_splat_pattern", "dictionary_splat_pattern"];
+
+struct PyLine<'a> {
+    tokens: Vec<Node<'a>>,
+    trailing_comment: Option<Node<'a>>,
+    depth: usize,
+    blank_before: usize,
+    // the line has comments or backslashes inside it, so its line breaks are kept
+    keeps_breaks: bool,
+}
+
+impl<'a> PyLine<'a> {
+    fn new(blank_before: usize) -> PyLine<'a> {
+        return PyLine { tokens: Vec::new(), trailing_comment: None, depth: 0, blank_before, keeps_breaks: false };
+    }
+
+    fn first_text(&self, src: &'a str) -> &'a str {
+        return get_text(self.tokens[0], src);
+    }
+
+    fn is_comment(&self) -> bool {
+        return self.tokens.len() == 1 && self.tokens[0].kind() == "comment";
+    }
+
+    fn is_decorator(&self, src: &str) -> bool {
+        return self.first_text(src) == "@";
+    }
+
+    fn is_def(&self, src: &str) -> bool {
+        let first = self.first_text(src);
+        if first == "async" && self.tokens.len() > 1 { return get_text(self.tokens[1], src) == "def"; }
+        return first == "def";
+    }
+
+    fn is_class(&self, src: &str) -> bool {
+        return self.first_text(src) == "class";
+    }
+
+    fn is_import(&self, src: &str) -> bool {
+        let first = self.tokens[0];
+        let is_statement = ["import_statement", "import_from_statement", "future_import_statement"].contains(&parent_kind(first));
+        return is_statement && (self.first_text(src) == "import" || self.first_text(src) == "from");
+    }
+
+    fn opens_block(&self, src: &str) -> bool {
+        let last = self.tokens[self.tokens.len() - 1];
+        return get_text(last, src) == ":" && last.next_sibling().map(|next| next.kind() == "block").unwrap_or(false);
+    }
+}
+
+pub fn traverse_py_ast(ast: Tree, src: String) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the Python file was left as it is.");
+        return src;
+    }
+    let mut redundant_parens = Vec::<Node>::new();
+    find_redundant_parens(root, &mut redundant_parens);
+    let mut tokens = Vec::<Node>::new();
+    collect_tokens(root, &src, &mut tokens);
+    tokens.retain(|token| !redundant_parens.iter().any(|parens| parens.child(0) == Some(*token) || parens.child(parens.child_count() - 1) == Some(*token)));
+    let mut statement_starts = HashSet::<usize>::new();
+    find_statement_starts(root, &mut statement_starts);
+    let mut lines = split_into_lines(&tokens, &src, &statement_starts, &redundant_parens);
+    compute_depths(&mut lines, &src);
+    let blank_lines = compute_blank_lines(&lines, &src);
+    let mut result = Vec::<String>::new();
+    for (i, line) in lines.iter().enumerate() {
+        for _ in 0..blank_lines[i] {
+            result.push(String::new());
+        }
+        let mut printed = if line.keeps_breaks { print_kept_breaks(line, &src) }
+                          else { print_logical_line(&line.tokens, line.depth, &src) };
+        if let Some(comment) = line.trailing_comment {
+            let last = printed.len() - 1;
+            printed[last] = format!("{}  {}", printed[last], get_token_text(comment, &src, line.depth));
+        }
+        result.append(&mut printed);
+    }
+    let mut result = result.join("\n");
+    result = result.trim_end().to_string();
+    result.push('\n');
+    return result;
+}
+
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    return node.utf8_text(src.as_bytes()).unwrap_or("");
+}
+
+fn parent_kind(node: Node) -> &'static str {
+    return match node.parent() {
+        Some(parent) => parent.kind(),
+        None => "",
+    };
+}
+
+fn collect_tokens<'a>(node: Node<'a>, src: &str, tokens: &mut Vec<Node<'a>>) {
+    if node.child_count() == 0 || ATOMIC_NODES.contains(&node.kind()) {
+        if get_text(node, src).trim() != "" || node.kind() == "line_continuation" { tokens.push(node); }
+        return;
+    }
+    for child in node.children(&mut node.walk()) {
+        collect_tokens(child, src, tokens);
+    }
+}
+
+// Parentheses around a whole condition, return value or assigned value
+// change nothing, so black drops them unless there are comments inside.
+fn find_redundant_parens<'a>(node: Node<'a>, redundant: &mut Vec<Node<'a>>) {
+    if node.kind() == "parenthesized_expression" && !has_comments(node) {
+        let field = match parent_kind(node) {
+            "if_statement" | "elif_clause" | "while_statement" => "condition",
+            "assignment" => "right",
+            _ => "",
+        };
+        let parent = node.parent().unwrap();
+        let is_whole = match field {
+            "" => parent.kind() == "return_statement",
+            _ => parent.child_by_field_name(field) == Some(node),
+        };
+        let inner = node.named_children(&mut node.walk()).filter(|child| child.kind() != "comment").count();
+        let is_plain = node.named_child(0).map(|child| !["yield", "named_expression", "comment"].contains(&child.kind())).unwrap_or(false);
+        if is_whole && inner == 1 && is_plain { redundant.push(node); }
+    }
+    for child in node.children(&mut node.walk()) {
+        find_redundant_parens(child, redundant);
+    }
+}
+
+fn has_comments(node: Node) -> bool {
+    if node.kind() == "comment" { return true; }
+    return node.children(&mut node.walk()).any(has_comments);
+}
+
+fn first_token(node: Node) -> Node {
+    let mut node = node;
+    while node.child_count() > 0 && !ATOMIC_NODES.contains(&node.kind()) {
+        node = node.child(0).unwrap();
+    }
+    return node;
+}
+
+// The first token of every statement in a block starts a new line, which
+// splits `if x: return` and `class A: pass` into a header and a body.
+fn find_statement_starts(node: Node, starts: &mut HashSet<usize>) {
+    if node.kind() == "block" {
+        for child in node.named_children(&mut node.walk()) {
+            if child.kind() != "comment" { starts.insert(first_token(child).start_byte()); }
+        }
+    }
+    for child in node.children(&mut node.walk()) {
+        find_statement_starts(child, starts);
+    }
+}
+
+fn is_opener(text: &str) -> bool {
+    return text == "(" || text == "[" || text == "{";
+}
+
+fn is_closer(text: &str) -> bool {
+    return text == ")" || text == "]" || text == "}";
+}
+
+fn split_into_lines<'a>(tokens: &[Node<'a>], src: &str, statement_starts: &HashSet<usize>, redundant_parens: &[Node]) -> Vec<PyLine<'a>> {
+    let mut lines = Vec::<PyLine>::new();
+    let mut current = PyLine::new(0);
+    let mut last_row: Option<usize> = None;
+    let mut bracket_depth = 0;
+    let mut continued = false;
+    let mut break_next = false;
+    let mut previous_end: Option<usize> = None;
+    for token in tokens {
+        let text = get_text(*token, src);
+        let row = token.start_position().row;
+        // the scanner of string literals takes a backslash continuation in front of
+        // them as whitespace, so it is only found between the tokens
+        let gap_start = previous_end.unwrap_or(token.start_byte());
+        let gap = &src[gap_start..token.start_byte()];
+        previous_end = Some(token.end_byte());
+        if gap.contains('\\') {
+            continued = true;
+            current.keeps_breaks = true;
+        }
+        if token.kind() == "line_continuation" {
+            continued = true;
+            current.keeps_breaks = true;
+            continue;
+        }
+        // black drops the semicolons between statements and puts every statement on its own line.
+        if text == ";" && STATEMENT_CONTAINERS.contains(&parent_kind(*token)) {
+            break_next = true;
+            continue;
+        }
+        let on_new_row = last_row.is_none() || row > last_row.unwrap();
+        // the line breaks the dropped parentheses allowed still do not end the line,
+        // and neither does the one before the `:` that followed the closing parenthesis
+        let in_dropped_parens = redundant_parens.iter().any(|parens| parens.start_byte() < token.start_byte() && (token.end_byte() < parens.end_byte() || (text == ":" && gap_start < parens.end_byte())));
+        if token.kind() == "comment" && !on_new_row && current.tokens.len() > 0 {
+            if bracket_depth > 0 {
+                current.tokens.push(*token);
+                current.keeps_breaks = true;
+            }
+            else {
+                current.trailing_comment = Some(*token);
+            }
+            last_row = Some(token.end_position().row);
+            continue;
+        }
+        let starts_line = break_next || statement_starts.contains(&token.start_byte())
+            || (token.kind() == "comment" && bracket_depth == 0) || (on_new_row && bracket_depth == 0 && !continued && !in_dropped_parens);
+        if starts_line && (current.tokens.len() > 0 || current.trailing_comment.is_some()) {
+            lines.push(current);
+            let blank = match last_row {
+                Some(last) if row > last + 1 => row - last - 1,
+                _ => 0,
+            };
+            current = PyLine::new(blank);
+            bracket_depth = 0;
+        }
+        if is_opener(text) && !token.is_named() { bracket_depth += 1; }
+        else if is_closer(text) && !token.is_named() && bracket_depth > 0 { bracket_depth -= 1; }
+        if token.kind() == "comment" && on_new_row && bracket_depth > 0 { current.keeps_breaks = true; }
+        current.tokens.push(*token);
+        last_row = Some(token.end_position().row);
+        continued = false;
+        break_next = false;
+        if token.kind() == "comment" && bracket_depth == 0 {
+            // a comment on its own line is a line of its own
+            break_next = true;
+        }
+    }
+    if current.tokens.len() > 0 { lines.push(current); }
+    return lines;
+}
+
+fn count_blocks(node: Node) -> usize {
+    let mut count = 0;
+    let mut current = node.parent();
+    while current.is_some() {
+        let parent = current.unwrap();
+        if parent.kind() == "block" { count += 1; }
+        current = parent.parent();
+    }
+    return count;
+}
+
+// Code lines take their depth from the blocks around them. Comment lines
+// are indented like the code that follows them, unless they were written
+// deeper than it, in which case they belong to the block above.
+fn compute_depths(lines: &mut [PyLine], src: &str) {
+    for line in lines.iter_mut() {
+        if !line.is_comment() { line.depth = count_blocks(line.tokens[0]); }
+    }
+    for i in 0..lines.len() {
+        if !lines[i].is_comment() { continue; }
+        let column = lines[i].tokens[0].start_position().column;
+        let next = (i + 1..lines.len()).find(|j| !lines[*j].is_comment());
+        let next_column = next.map(|j| lines[j].tokens[0].start_position().column).unwrap_or(0);
+        let next_depth = next.map(|j| lines[j].depth).unwrap_or(0);
+        if column <= next_column || (next.is_none() && column == 0) {
+            lines[i].depth = next_depth;
+            continue;
+        }
+        let mut depth = next_depth;
+        for j in (0..i).rev() {
+            if lines[j].is_comment() { continue; }
+            let previous_column = lines[j].tokens[0].start_position().column;
+            if previous_column <= column {
+                depth = lines[j].depth;
+                if previous_column < column && lines[j].opens_block(src) { depth += 1; }
+                break;
+            }
+        }
+        lines[i].depth = depth;
+    }
+}
+
+// The blank line rules black applies on top of PEP 8: two blank lines around
+// top-level functions and classes, one around nested ones, none right after a
+// decorator or a function signature, at least one after the imports, and at
+// most two (one inside blocks) anywhere else.
+fn compute_blank_lines(lines: &[PyLine], src: &str) -> Vec<usize> {
+    let mut blank_lines = Vec::<usize>::new();
+    let mut previous_defs = Vec::<usize>::new();
+    for (i, line) in lines.iter().enumerate() {
+        let max_allowed = if line.depth == 0 { 2 } else { 1 };
+        let mut before = line.blank_before.min(max_allowed);
+        if i == 0 {
+            blank_lines.push(0);
+            if line.is_def(src) || line.is_class(src) { previous_defs.push(line.depth); }
+            continue;
+        }
+        let previous = &lines[i - 1];
+        let user_went_with_blank_lines = before > 0;
+        while previous_defs.last().is_some() && *previous_defs.last().unwrap() >= line.depth {
+            let def_depth = previous_defs.pop().unwrap();
+            let nested_def = def_depth > 0 && line.opens_block(src) && !["with", "try", "for", "while", "if", "match"].contains(&line.first_text(src));
+            if line.depth > 0 || nested_def { before = 1; }
+            else { before = 2; }
+        }
+        if previous.is_decorator(src) {
+            before = 0;
+        }
+        else if previous.depth < line.depth && (previous.is_def(src) || previous.is_class(src)) {
+            before = if previous.is_class(src) && user_went_with_blank_lines && !line.is_def(src) { 1 } else { 0 };
+        }
+        else if line.is_decorator(src) || line.is_def(src) || line.is_class(src) {
+            let newlines = if line.depth > 0 { 1 } else { 2 };
+            before = newlines;
+            if previous.is_comment() && previous.depth == line.depth && line.blank_before == 0 {
+                // the comments right above a definition belong to it, so the blank lines go before them
+                let mut start = i - 1;
+                while start > 0 && lines[start - 1].is_comment() && lines[start].blank_before == 0 && lines[start - 1].depth == line.depth {
+                    start -= 1;
+                }
+                let follows_opener = start > 0 && lines[start - 1].opens_block(src);
+                if start > 0 && !follows_opener && blank_lines[start] <= 1 {
+                    blank_lines[start] = blank_lines[start].max(newlines);
+                }
+                before = 0;
+            }
+        }
+        else if i == 1 && previous.depth == 0 && previous.tokens.len() == 1 && is_docstring(previous.tokens[0]) {
+            before = 1;
+        }
+        else if previous.is_import(src) && !line.is_import(src) && previous.depth == line.depth {
+            before = before.max(1);
+        }
+        if previous.opens_block(src) && previous.depth < line.depth && previous.is_def(src) { before = 0; }
+        if line.is_def(src) || line.is_class(src) { previous_defs.push(line.depth); }
+        blank_lines.push(before);
+    }
+    return blank_lines;
+}
+
+fn starts_with_word(text: &str) -> bool {
+    return match text.chars().next() {
+        Some(c) => c.is_alphanumeric() || c == '_',
+        None => false,
+    };
+}
+
+fn is_keyword(node: Node, src: &str) -> bool {
+    return !node.is_named() && starts_with_word(get_text(node, src));
+}
+
+fn is_simple_power_operand(node: Node) -> bool {
+    return match node.kind() {
+        "identifier" | "integer" | "float" | "none" | "true" | "false" => true,
+        "attribute" => node.named_children(&mut node.walk()).all(is_simple_power_operand),
+        "unary_operator" => node.named_child(0).map(is_simple_power_operand).unwrap_or(false),
+        _ => false,
+    };
+}
+
+// black hugs the power operator when both of its operands are simple.
+fn is_hugged_power(operator: Node, src: &str) -> bool {
+    if get_text(operator, src) != "**" || parent_kind(operator) != "binary_operator" { return false; }
+    let parent = operator.parent().unwrap();
+    let left = parent.child_by_field_name("left").map(is_simple_power_operand).unwrap_or(false);
+    let right = parent.child_by_field_name("right").map(is_simple_power_operand).unwrap_or(false);
+    return left && right;
+}
+
+fn get_separator(a: Node, b: Node, src: &str) -> &'static str {
+    let (ta, tb) = (get_text(a, src), get_text(b, src));
+    let (pa, pb) = (parent_kind(a), parent_kind(b));
+    if b.kind() == "comment" { return "  "; }
+    // a slice after a comma: a[1:2, ::3]
+    if ta == "," && tb == ":" { return " "; }
+    if tb == "," || tb == ";" || tb == ")" || tb == "]" || tb == "}" || tb == ":" { return ""; }
+    if ta == "," { return " "; }
+    if ta == ":" { return if pa == "slice" { "" } else { " " }; }
+    if ta == "(" || ta == "[" || ta == "{" { return ""; }
+    if ta == "." || tb == "." {
+        if is_keyword(a, src) || is_keyword(b, src) { return " "; }
+        return "";
+    }
+    if ta == "=" || tb == "=" {
+        let parent = if ta == "=" { pa } else { pb };
+        if parent == "keyword_argument" || parent == "default_parameter" { return ""; }
+        return " ";
+    }
+    if (ta == "*" || ta == "**") && SPLAT_NODES.contains(&pa) { return ""; }
+    if !a.is_named() && pa == "unary_operator" && !is_keyword(a, src) { return ""; }
+    if is_hugged_power(a, src) || is_hugged_power(b, src) { return ""; }
+    if ta == "@" && pa == "decorator" { return ""; }
+    if tb == "(" || tb == "[" {
+        if is_keyword(a, src) { return " "; }
+        if a.is_named() || ta == ")" || ta == "]" || ta == "}" { return ""; }
+        return " ";
+    }
+    return " ";
+}
+
+fn normalize_string_prefix(prefix: &str) -> String {
+    return prefix.replace('F', "f").replace('B', "b").replace(['U', 'u'], "");
+}
+
+// Prefers double quotes, unless the string contains double quotes that would need escaping.
+fn normalize_string_quotes(text: &str) -> String {
+    let prefix_len = text.find(['\'', '"']).unwrap_or(0);
+    let prefix = normalize_string_prefix(&text[..prefix_len]);
+    let body = &text[prefix_len..];
+    if body.starts_with("'''") && body.len() >= 6 {
+        let inner = &body[3..body.len() - 3];
+        if inner.contains('"') { return format!("{}{}", prefix, body); }
+        return format!("{}\"\"\"{}\"\"\"", prefix, inner);
+    }
+    if body.starts_with('\'') && body.len() >= 2 {
+        let inner = &body[1..body.len() - 1];
+        if inner.contains('"') { return format!("{}{}", prefix, body); }
+        let is_raw = prefix.contains('r') || prefix.contains('R');
+        let inner = if is_raw { inner.to_string() } else { inner.replace("\\'", "'") };
+        return format!("{}\"{}\"", prefix, inner);
+    }
+    return format!("{}{}", prefix, body);
+}
+
+fn normalize_number(text: &str) -> String {
+    let lower = text.to_lowercase();
+    if lower.starts_with("0x") { return format!("0x{}", text[2..].to_uppercase()); }
+    return lower;
+}
+
+fn normalize_comment(text: &str) -> String {
+    let text = text.trim_end();
+    let body = &text[1..];
+    if body.is_empty() || body.starts_with([' ', '!', ':', '#']) { return text.to_string(); }
+    return format!("# {}", body);
+}
+
+fn is_docstring(node: Node) -> bool {
+    if node.kind() != "string" || parent_kind(node) != "expression_statement" { return false; }
+    let statement = node.parent().unwrap();
+    if statement.named_child_count() != 1 { return false; }
+    let container = match statement.parent() {
+        Some(container) => container,
+        None => return false,
+    };
+    let is_first = container.named_children(&mut container.walk()).find(|child| child.kind() != "comment") == Some(statement);
+    let is_body = container.kind() == "module"
+        || ["function_definition", "class_definition"].contains(&parent_kind(container));
+    return is_first && is_body;
+}
+
+// Re-indents the lines of a docstring to the depth it ends up at and strips
+// the whitespace around its text.
+fn normalize_docstring(text: &str, depth: usize) -> String {
+    let prefix_len = text.find(['\'', '"']).unwrap_or(0);
+    let quote = if text[prefix_len..].starts_with("\"\"\"") || text[prefix_len..].starts_with("'''") { &text[prefix_len..prefix_len + 3] }
+                else { &text[prefix_len..prefix_len + 1] };
+    if text.len() < prefix_len + 2 * quote.len() { return text.to_string(); }
+    let inner = &text[prefix_len + quote.len()..text.len() - quote.len()];
+    let indent = INDENT.repeat(depth);
+    let lines: Vec<&str> = inner.split('\n').collect();
+    let common = lines[1..].iter().filter(|line| line.trim() != "")
+        .map(|line| line.len() - line.trim_start().len()).min().unwrap_or(0);
+    let mut fixed = vec![lines[0].trim().to_string()];
+    for (i, line) in lines[1..].iter().enumerate() {
+        let stripped = if line.len() >= common { line[common..].trim_end() } else { line.trim() };
+        if !stripped.is_empty() || i == lines.len() - 2 { fixed.push(format!("{}{}", indent, stripped)); }
+        else { fixed.push(String::new()); }
+    }
+    let mut inner = fixed.join("\n");
+    if lines.len() == 1 { inner = inner.trim().to_string(); }
+    if inner.ends_with(&quote[..1]) { inner.push(' '); }
+    if inner.starts_with(&quote[..1]) { inner.insert(0, ' '); }
+    return normalize_string_quotes(&format!("{}{}{}{}", &text[..prefix_len], quote, inner, quote));
+}
+
+fn get_token_text(node: Node, src: &str, depth: usize) -> String {
+    let text = get_text(node, src);
+    return match node.kind() {
+        "string" if is_docstring(node) => normalize_docstring(text, depth),
+        "string" => normalize_string_quotes(text),
+        "integer" | "float" => normalize_number(text),
+        "comment" => normalize_comment(text),
+        _ => text.to_string(),
+    };
+}
+
+fn join_tokens(tokens: &[Node], src: &str, depth: usize) -> String {
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 { text += get_separator(tokens[i - 1], *token, src); }
+        text += &get_token_text(*token, src, depth);
+    }
+    return text;
+}
+
+fn fits(text: &str, depth: usize) -> bool {
+    return depth * INDENT.len() + text.chars().count() <= LINE_LENGTH;
+}
+
+// Finds the last bracket pair of the tokens that has something inside it.
+fn find_last_bracket_pair(tokens: &[Node], src: &str) -> Option<(usize, usize)> {
+    let mut close: Option<usize> = None;
+    let mut depth = 0;
+    for i in (0..tokens.len()).rev() {
+        if tokens[i].is_named() { continue; }
+        let text = get_text(tokens[i], src);
+        if is_closer(text) {
+            if depth == 0 { close = Some(i); }
+            depth += 1;
+        }
+        else if is_opener(text) && depth > 0 {
+            depth -= 1;
+            if depth == 0 {
+                if close.unwrap() > i + 1 { return Some((i, close.unwrap())); }
+                close = None;
+            }
+        }
+    }
+    return None;
+}
+
+// A comma before the closing bracket tells black to put one element per line,
+// except in one-element tuples and subscripts where the comma is needed.
+fn has_magic_trailing_comma(tokens: &[Node], src: &str) -> bool {
+    for (i, token) in tokens.iter().enumerate() {
+        if i == 0 || token.is_named() || !is_closer(get_text(*token, src)) { continue; }
+        if get_text(tokens[i - 1], src) != "," { continue; }
+        let parent = token.parent().unwrap();
+        let is_single = parent.named_children(&mut parent.walk()).filter(|child| child.kind() != "comment").count() == 1;
+        if is_single && (parent.kind() == "tuple" || parent.kind() == "subscript") { continue; }
+        return true;
+    }
+    return false;
+}
+
+// Splits the tokens inside a bracket pair on the commas that separate its elements.
+fn split_on_commas<'a>(tokens: &[Node<'a>], src: &str) -> Vec<Vec<Node<'a>>> {
+    let mut elements = Vec::<Vec<Node>>::new();
+    let mut current = Vec::<Node>::new();
+    let mut depth = 0;
+    for token in tokens {
+        let text = get_text(*token, src);
+        if !token.is_named() && is_opener(text) { depth += 1; }
+        if !token.is_named() && is_closer(text) { depth -= 1; }
+        // commas of `for a, b in` or `lambda a, b:` do not separate the elements
+        let separates = ["lambda_parameters", "pattern_list", "for_in_clause", "expression_list"].iter().all(|kind| parent_kind(*token) != *kind);
+        if depth == 0 && text == "," && separates {
+            elements.push(current);
+            current = Vec::new();
+            continue;
+        }
+        current.push(*token);
+    }
+    if current.len() > 0 { elements.push(current); }
+    return elements;
+}
+
+fn has_top_level_comma(tokens: &[Node], src: &str) -> bool {
+    return split_on_commas(tokens, src).len() > 1 || tokens.last().map(|token| get_text(*token, src) == ",").unwrap_or(false);
+}
+
+// Prints a logical line, splitting it the way black does when it does not fit:
+// the contents of the last bracket pair go on their own indented line, and
+// when they do not fit there either they are split one element per line.
+fn print_logical_line(tokens: &[Node], depth: usize, src: &str) -> Vec<String> {
+    let text = join_tokens(tokens, src, depth);
+    let indent = INDENT.repeat(depth);
+    if fits(&text, depth) && !has_magic_trailing_comma(tokens, src) {
+        return vec![format!("{}{}", indent, text)];
+    }
+    let (open, close) = match find_last_bracket_pair(tokens, src) {
+        Some(pair) => pair,
+        None => return vec![format!("{}{}", indent, text)],
+    };
+    let mut result = print_logical_line(&tokens[..=open], depth, src);
+    let body = &tokens[open + 1..close];
+    let body_has_comma = has_top_level_comma(body, src);
+    let body_text = join_tokens(body, src, depth + 1);
+    let magic_comma = body.last().map(|token| get_text(*token, src) == ",").unwrap_or(false) && has_magic_trailing_comma(&tokens[open..=close], src);
+    if body_has_comma && (magic_comma || !fits(&body_text, depth + 1)) {
+        for element in split_on_commas(body, src) {
+            let mut lines = print_logical_line(&element, depth + 1, src);
+            let last = lines.len() - 1;
+            lines[last].push(',');
+            result.append(&mut lines);
+        }
+    }
+    else {
+        result.append(&mut print_logical_line(body, depth + 1, src));
+    }
+    let tail = join_tokens(&tokens[close..], src, depth);
+    result.push(format!("{}{}", indent, tail));
+    return result;
+}
+
+// Lines with comments or backslashes inside them keep their line breaks,
+// and get indented one level for every bracket that is still open.
+fn print_kept_breaks(line: &PyLine, src: &str) -> Vec<String> {
+    let mut rows = Vec::<Vec<Node>>::new();
+    let mut last_row: Option<usize> = None;
+    for token in &line.tokens {
+        let row = token.start_position().row;
+        if last_row.is_none() || row > last_row.unwrap() { rows.push(Vec::new()); }
+        rows.last_mut().unwrap().push(*token);
+        last_row = Some(token.end_position().row);
+    }
+    let mut result = Vec::<String>::new();
+    let mut open_rows = Vec::<usize>::new();
+    for (i, row) in rows.iter().enumerate() {
+        let continued = i > 0 && open_rows.is_empty();
+        let mut k = 0;
+        while k < row.len() && !row[k].is_named() && is_closer(get_text(row[k], src)) {
+            open_rows.pop();
+            k += 1;
+        }
+        let mut distinct = open_rows.clone();
+        distinct.dedup();
+        let mut depth = line.depth + distinct.len();
+        if continued { depth += 1; }
+        let text = join_tokens(row, src, depth);
+        if continued {
+            let last = result.len() - 1;
+            result[last].push_str(" \\");
+        }
+        result.push(format!("{}{}", INDENT.repeat(depth), text));
+        for token in &row[k..] {
+            if token.is_named() { continue; }
+            let text = get_text(*token, src);
+            if is_opener(text) { open_rows.push(i); }
+            else if is_closer(text) { open_rows.pop(); }
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::py_format;
+
+    fn format(src: &str) -> String {
+        let ast = py_format::parse_existing_py_file(src.to_string());
+        return traverse_py_ast(ast, src.to_string());
+    }
+
+    #[test]
+    fn continuations_before_strings() {
+        let src = "h = 'x' \\\n    'y'\nassert x, \\\n    'msg'\nx = a + \\\n    'y'\n";
+        let expected = "h = \"x\" \\\n    \"y\"\nassert x, \\\n    \"msg\"\nx = a + \\\n    \"y\"\n";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn continuations_between_names() {
+        let src = "z = a + \\\n        b\nif a and \\\n   b:\n    pass\n";
+        let expected = "z = a + \\\n    b\nif a and \\\n    b:\n    pass\n";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn slices_after_commas() {
+        assert_eq!(format("b = a[1:2, ::3]\n"), "b = a[1:2, ::3]\n");
+    }
+
+    #[test]
+    fn dropped_parens_before_colons() {
+        let src = "if (\n    a\n    and b\n):\n    pass\n";
+        assert_eq!(format(src), "if a and b:\n    pass\n");
+    }
+}
@@ -0,0 +1,442 @@
+use tree_sitter::{Tree, Node};
+use std::collections::HashSet;
+use crate::{options, utils};
+
+// Rust is formatted like Go: the line structure of the source is kept and the
+// tokens of the tree are walked to normalize the spacing between them and to
+// recompute the indentation of every line. On top of that the braces follow
+// the same placement options as the C family, so functions, types, modules,
+// control flow, closures and `else` get their braces attached or on a new line
+// the way the C and C++ files of the project do, with tabs for indentation.
+
+// Nodes printed as they are, without looking at their children.
+const ATOMIC_NODES: [&str; 7] = ["string_literal", "raw_string_literal", "char_literal", "line_comment", "block_comment", "lifetime", "label"];
+
+// Nodes with a `<...>` list of types, lifetimes or bounds.
+const ANGLE_LISTS: [&str; 4] = ["type_arguments", "type_parameters", "for_lifetimes", "bracketed_type"];
+
+// Nodes starting with an operator that applies to what comes right after it.
+const PREFIX_NODES: [&str; 7] = ["unary_expression", "reference_expression", "reference_type", "reference_pattern", "pointer_type", "self_parameter", "negative_literal"];
+
+// Parents of a `{` and the construct they open, for brace placement.
+const BRACE_OWNERS: [(&str, &str, utils::BraceKind); 15] = [
+    ("block", "function_item", utils::BraceKind::Function),
+    ("block", "closure_expression", utils::BraceKind::Lambda),
+    ("block", "if_expression", utils::BraceKind::Control),
+    ("block", "else_clause", utils::BraceKind::Control),
+    ("block", "while_expression", utils::BraceKind::Control),
+    ("block", "loop_expression", utils::BraceKind::Control),
+    ("block", "for_expression", utils::BraceKind::Control),
+    ("match_block", "match_expression", utils::BraceKind::Control),
+    ("declaration_list", "impl_item", utils::BraceKind::Class),
+    ("declaration_list", "trait_item", utils::BraceKind::Class),
+    ("declaration_list", "mod_item", utils::BraceKind::Namespace),
+    ("declaration_list", "foreign_mod_item", utils::BraceKind::Extern),
+    ("field_declaration_list", "struct_item", utils::BraceKind::Class),
+    ("field_declaration_list", "union_item", utils::BraceKind::Union),
+    ("enum_variant_list", "enum_item", utils::BraceKind::Enum),
+];
+
+struct RsLine<'a> {
+    tokens: Vec<Node<'a>>,
+    blank_before: bool,
+}
+
+pub fn traverse_rs_ast(ast: Tree, src: String, opts: options::COpts) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the Rust file was left as it is.");
+        return src;
+    }
+    let mut tokens = Vec::<Node>::new();
+    collect_tokens(root, &src, &mut tokens);
+    let mut breaks = HashSet::<usize>::new();
+    let mut joins = HashSet::<usize>::new();
+    place_braces(&tokens, opts, &mut breaks, &mut joins);
+    let lines = split_into_lines(&tokens, &breaks, &joins);
+    let indents = compute_indentation(&lines, &src);
+    // the physical lines of every line, more than one when it holds a multi-line string or comment
+    let mut result = Vec::<Vec<String>>::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.blank_before && result.len() > 0 { result.push(vec![String::new()]); }
+        let mut text = "\t".repeat(indents[i]);
+        for (j, token) in line.tokens.iter().enumerate() {
+            if j > 0 {
+                // the separators of macro repetitions like `$($x),*` aren't in the tree
+                let gap = &src[line.tokens[j - 1].end_byte()..token.start_byte()];
+                if gap.trim().is_empty() { text += get_separator(line.tokens[j - 1], *token, &src); }
+                else { text += gap; }
+            }
+            text += get_text(*token, &src);
+        }
+        result.push(text.trim_end().split('\n').map(|x| x.to_string()).collect());
+    }
+    // GNU and Whitesmiths move the lines with braces, which mustn't touch the
+    // inside of multi-line strings and comments.
+    let mut first_lines = result.iter().map(|x| x[0].clone()).collect::<Vec<String>>().join("\n");
+    utils::indent_braces(&mut first_lines, opts.style);
+    for (physical, first) in result.iter_mut().zip(first_lines.split('\n')) {
+        physical[0] = first.to_string();
+    }
+    let mut result = result.concat().join("\n");
+    result = result.trim_end().to_string();
+    result.push('\n');
+    return result;
+}
+
+// Doc comments take the newline after them, which isn't printed.
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    let text = node.utf8_text(src.as_bytes()).unwrap_or("");
+    if node.kind() == "line_comment" { return text.trim_end(); }
+    return text;
+}
+
+// The last row holding text of the node, since nodes may end at the start of the next line.
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row { return end.row - 1; }
+    return end.row;
+}
+
+fn parent_kind(node: Node) -> &'static str {
+    return match node.parent() {
+        Some(parent) => parent.kind(),
+        None => "",
+    };
+}
+
+fn is_first_child(node: Node) -> bool {
+    return node.prev_sibling().is_none();
+}
+
+fn is_last_child(node: Node) -> bool {
+    return node.next_sibling().is_none();
+}
+
+fn collect_tokens<'a>(node: Node<'a>, src: &str, tokens: &mut Vec<Node<'a>>) {
+    if node.child_count() == 0 || ATOMIC_NODES.contains(&node.kind()) {
+        let text = get_text(node, src);
+        if text.trim() != "" { tokens.push(node); }
+        return;
+    }
+    for child in node.children(&mut node.walk()) {
+        collect_tokens(child, src, tokens);
+    }
+}
+
+fn brace_kind(open: Node) -> Option<utils::BraceKind> {
+    let list = open.parent()?;
+    let owner = list.parent()?;
+    for (list_kind, owner_kind, kind) in BRACE_OWNERS {
+        if list.kind() == list_kind && owner.kind() == owner_kind { return Some(kind); }
+    }
+    return None;
+}
+
+fn is_line_comment(node: Node) -> bool {
+    return node.kind() == "line_comment";
+}
+
+// The `{` of a block is attached to the line before it or moved to a line of
+// its own, and the `else` after a block goes on the `}` line or on the next
+// one, as the brace options say. A block written on one line stays that way
+// when its brace is attached, and is spread over several lines, one item per
+// line, when its brace goes on a new line.
+fn place_braces(tokens: &[Node], opts: options::COpts, breaks: &mut HashSet<usize>, joins: &mut HashSet<usize>) {
+    let mut expanded = HashSet::<usize>::new();
+    for i in 1..tokens.len() {
+        let (previous, token) = (tokens[i - 1], tokens[i]);
+        let on_new_line = token.start_position().row > last_row(previous);
+        let placement;
+        let block;
+        if token.kind() == "{" {
+            let kind = match brace_kind(token) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            placement = opts.braces.get(kind);
+            block = token.parent().unwrap();
+        }
+        else if token.kind() == "else" && previous.kind() == "}" {
+            placement = opts.braces.get(utils::BraceKind::Else);
+            block = previous.parent().unwrap();
+            // `if a { 1 } else { 2 }` stays on one line unless its first block was spread out
+            if is_one_line(block) && !expanded.contains(&block.id()) { continue; }
+        }
+        else {
+            continue;
+        }
+        match placement {
+            utils::BracePlacement::Attach => {
+                if is_one_line(block) && token.kind() == "{" { continue; }
+                // a `{` after a `where` clause stays on its own line
+                let after_where = previous.parent().is_some_and(|x| in_where_clause(x) || x.kind() == "where_clause");
+                if on_new_line && !is_line_comment(previous) && !after_where { joins.insert(token.id()); }
+            },
+            utils::BracePlacement::NewLine => {
+                if !on_new_line { breaks.insert(token.id()); }
+                if is_one_line(block) && token.kind() == "{" {
+                    // the one-line blocks around it are spread out with it
+                    let mut current = Some(block);
+                    while let Some(node) = current {
+                        let is_block = node.child(0).is_some_and(|x| x.kind() == "{" && brace_kind(x).is_some());
+                        if is_block && is_one_line(node) && expanded.insert(node.id()) { expand_block(node, breaks); }
+                        current = node.parent();
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn is_one_line(node: Node) -> bool {
+    return node.start_position().row == node.end_position().row;
+}
+
+// Breaks the line before every item of a block and before its `}`.
+fn expand_block(block: Node, breaks: &mut HashSet<usize>) {
+    for child in block.children(&mut block.walk()) {
+        if child.kind() == "}" { breaks.insert(child.id()); }
+        else if child.is_named() { breaks.insert(first_token(child).id()); }
+    }
+}
+
+fn first_token(node: Node) -> Node {
+    let mut current = node;
+    while current.child_count() > 0 && !ATOMIC_NODES.contains(&current.kind()) {
+        current = current.child(0).unwrap();
+    }
+    return current;
+}
+
+fn split_into_lines<'a>(tokens: &[Node<'a>], breaks: &HashSet<usize>, joins: &HashSet<usize>) -> Vec<RsLine<'a>> {
+    let mut lines = Vec::<RsLine>::new();
+    let mut current = RsLine { tokens: Vec::new(), blank_before: false };
+    let mut previous_row: Option<usize> = None;
+    for token in tokens {
+        let row = token.start_position().row;
+        let mut new_line = previous_row.is_none() || row > previous_row.unwrap();
+        if breaks.contains(&token.id()) { new_line = true; }
+        if joins.contains(&token.id()) { new_line = false; }
+        if new_line && current.tokens.len() > 0 {
+            lines.push(current);
+            let blank = previous_row.is_some() && row > previous_row.unwrap() + 1;
+            current = RsLine { tokens: Vec::new(), blank_before: blank };
+        }
+        current.tokens.push(*token);
+        previous_row = Some(last_row(*token));
+    }
+    if current.tokens.len() > 0 { lines.push(current); }
+    return lines;
+}
+
+fn is_opener(text: &str) -> bool {
+    return text == "(" || text == "[" || text == "{";
+}
+
+fn is_closer(text: &str) -> bool {
+    return text == ")" || text == "]" || text == "}";
+}
+
+fn is_binary_operator(token: Node) -> bool {
+    return !token.is_named() && parent_kind(token) == "binary_expression" && !is_first_child(token);
+}
+
+// Lines after one ending with an operator, `=` or `=>` are indented once more.
+fn ends_with_continuation(token: Node, src: &str) -> bool {
+    if token.is_named() { return false; }
+    let text = get_text(token, src);
+    if is_binary_operator(token) { return true; }
+    return match parent_kind(token) {
+        "let_declaration" | "assignment_expression" | "compound_assignment_expr" | "const_item" | "static_item" | "type_item" => text != ";",
+        "match_arm" => text == "=>",
+        _ => false,
+    };
+}
+
+// Method chains and operators carried over to the next line are indented once more.
+fn starts_with_continuation(token: Node, src: &str) -> bool {
+    let text = get_text(token, src);
+    if text == "." || text == "?" { return true; }
+    return is_binary_operator(token);
+}
+
+fn in_where_clause(token: Node) -> bool {
+    if token.kind() == "where" { return false; }
+    let mut current = token.parent();
+    while let Some(node) = current {
+        if node.kind() == "where_clause" { return true; }
+        if node.kind().ends_with("_item") { return false; }
+        current = node.parent();
+    }
+    return false;
+}
+
+fn compute_indentation(lines: &[RsLine], src: &str) -> Vec<usize> {
+    let mut indents = Vec::<usize>::new();
+    // the line on which each unclosed bracket was opened
+    let mut open_lines = Vec::<usize>::new();
+    for (i, line) in lines.iter().enumerate() {
+        let mut k = 0;
+        let mut closed: Option<usize> = None;
+        while k < line.tokens.len() && is_closer(get_text(line.tokens[k], src)) {
+            closed = open_lines.pop();
+            k += 1;
+        }
+        // a closer goes back to the indentation of the line that opened it
+        let mut indent = match (closed, open_lines.last()) {
+            (Some(opened), _) => indents[opened],
+            (None, Some(opened)) => indents[*opened] + 1,
+            (None, None) => 0,
+        };
+        let first = line.tokens[0];
+        if closed.is_none() && i > 0 {
+            let previous = &lines[i - 1];
+            let last = previous.tokens[previous.tokens.len() - 1];
+            let opened_on_previous = open_lines.last() == Some(&(i - 1));
+            let previous_closes = is_closer(get_text(previous.tokens[0], src));
+            let continues = (ends_with_continuation(last, src) && !opened_on_previous)
+                || (starts_with_continuation(first, src) && !previous_closes)
+                || in_where_clause(first);
+            if continues { indent += 1; }
+            // a closure's `{` moved to its own line inside a call lines up with the call
+            if first.kind() == "{" && brace_kind(first).is_some() && open_lines.last() == Some(&(i - 1)) { indent = indents[i - 1]; }
+        }
+        indents.push(indent);
+        for token in &line.tokens[k..] {
+            let text = get_text(*token, src);
+            if token.is_named() { continue; }
+            if is_opener(text) { open_lines.push(i); }
+            else if is_closer(text) { open_lines.pop(); }
+        }
+    }
+    return indents;
+}
+
+fn starts_with_word(text: &str) -> bool {
+    return match text.chars().next() {
+        Some(c) => c.is_alphanumeric() || c == '_',
+        None => false,
+    };
+}
+
+fn is_keyword(node: Node, src: &str) -> bool {
+    return !node.is_named() && starts_with_word(get_text(node, src));
+}
+
+// Where the token sits among macro tokens: inside the rules of a
+// `macro_rules!` the spacing is left alone, inside the arguments of a macro
+// call only the spacing around commas and semicolons is normalized.
+fn macro_context(node: Node) -> &'static str {
+    let mut current = node.parent();
+    let mut result = "";
+    while let Some(parent) = current {
+        match parent.kind() {
+            "macro_rule" => return "rule",
+            "token_tree" | "token_tree_pattern" => result = "tree",
+            _ => {},
+        }
+        current = parent.parent();
+    }
+    return result;
+}
+
+fn source_separator(a: Node, b: Node) -> &'static str {
+    if a.end_byte() < b.start_byte() { return " "; }
+    return "";
+}
+
+fn get_separator(a: Node, b: Node, src: &str) -> &'static str {
+    let (ta, tb) = (get_text(a, src), get_text(b, src));
+    let (pa, pb) = (parent_kind(a), parent_kind(b));
+    if b.kind() == "line_comment" || b.kind() == "block_comment" || a.kind() == "block_comment" { return " "; }
+    let (ca, cb) = (macro_context(a), macro_context(b));
+    if ca == "rule" && cb == "rule" { return source_separator(a, b); }
+    if ca == "tree" && cb == "tree" {
+        if tb == "," || tb == ";" { return ""; }
+        if ta == "," || ta == ";" { return " "; }
+        return source_separator(a, b);
+    }
+    if ta == "#" { return ""; }
+    if ta == "!" && ["macro_invocation", "inner_attribute_item"].contains(&pa) { return ""; }
+    if tb == "!" && pb == "macro_invocation" { return ""; }
+    if ta == "," || ta == ";" { return " "; }
+    if tb == "," || tb == ";" || tb == ")" || tb == "]" { return ""; }
+    if tb == ":" || ta == "::" || tb == "::" { return ""; }
+    if ta == "(" || ta == "[" { return ""; }
+    if ta == "." || tb == "." { return ""; }
+    if ta == ":" { return " "; }
+    if ta == "{" {
+        if tb == "}" || pa == "use_list" { return ""; }
+        return " ";
+    }
+    if tb == "}" { return if pb == "use_list" { "" } else { " " }; }
+    if tb == "{" { return " "; }
+    if ta == ".." || ta == "..=" { return ""; }
+    if tb == ".." || tb == "..=" { return if a.is_named() || is_closer(ta) { "" } else { " " }; }
+    if tb == "?" && pb == "try_expression" { return ""; }
+    if ta == "?" && pa == "removed_trait_bound" { return ""; }
+    if PREFIX_NODES.contains(&pa) && is_first_child(a) && !a.is_named() { return ""; }
+    if pa == "closure_parameters" && ta == "|" && is_first_child(a) { return ""; }
+    if pb == "closure_parameters" && tb == "|" && is_last_child(b) { return ""; }
+    if ANGLE_LISTS.contains(&pb) && (tb == "<" || tb == ">") { return ""; }
+    if ANGLE_LISTS.contains(&pa) && ta == "<" { return ""; }
+    if tb == "(" {
+        if pa == "visibility_modifier" || (ta == "fn" && pa == "function_type") { return ""; }
+        if is_keyword(a, src) { return " "; }
+        if a.is_named() || ta == ")" || ta == "]" { return ""; }
+        if ta == ">" && ANGLE_LISTS.contains(&pa) { return ""; }
+        return " ";
+    }
+    if tb == "[" && pb == "index_expression" { return ""; }
+    return " ";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rs_format;
+
+    fn format(src: &str, args: &[&str]) -> String {
+        let ast = rs_format::parse_existing_rs_file(src.to_string());
+        let opts = options::get_c_opts(&args.iter().map(|x| x.to_string()).collect());
+        return traverse_rs_ast(ast, src.to_string(), opts);
+    }
+
+    #[test]
+    fn one_line_blocks_follow_the_brace_options() {
+        let src = "\
+struct P { x: i32 }
+
+mod m { fn get(p: &P) -> i32 { if p.x > 0 { p.x } else { 0 } } }
+";
+        let allman = "\
+struct P
+{
+	x: i32
+}
+
+mod m
+{
+	fn get(p: &P) -> i32
+	{
+		if p.x > 0
+		{
+			p.x
+		}
+		else
+		{
+			0
+		}
+	}
+}
+";
+        let one_true = "\
+struct P { x: i32 }
+
+mod m { fn get(p: &P) -> i32 { if p.x > 0 { p.x } else { 0 } } }
+";
+        assert_eq!(format(src, &["-s", "allman"]), allman);
+        assert_eq!(format(src, &["-s", "1tbs"]), one_true);
+    }
+}
@@ -0,0 +1,17 @@
+use tree_sitter::{Parser, Language};
+use crate::{utils, rs_ast};
+
+pub fn parse_rs_file(path: String) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_rust::LANGUAGE.into()).expect("Error loading Rust parser.");
+    let contents = std::fs::read_to_string(path).unwrap_or("".to_string());
+    let tree = parser.parse(contents.clone(), None).unwrap();
+    return tree;
+}
+
+pub fn parse_existing_rs_file(text: String) -> tree_sitter::Tree {
+    let mut parser = Parser::new();
+    parser.set_language(&tree_sitter_rust::LANGUAGE.into()).expect("Error loading Rust parser.");
+    let tree = parser.parse(text, None).unwrap();
+    return tree;
+}
@@ -0,0 +1,493 @@
+use tree_sitter::{Tree, Node};
+
+// Shell scripts are reprinted statement by statement. Compound statements that
+// span several lines get their bodies indented, with `then` and `do` moved to the
+// line of their `if`, `while` or `for`, and case items one-liners aligned. Other
+// statements (and compound statements written on one line) are kept as written,
+// apart from their indentation. Strings and heredoc bodies are never touched.
+
+const INDENT: &str = "    ";
+
+// Nodes whose lines are copied byte for byte.
+const VERBATIM_KINDS: [&str; 5] = ["string", "raw_string", "ansi_c_string", "translated_string", "heredoc_redirect"];
+
+pub fn traverse_sh_ast(ast: Tree, src: String) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the shell script was left as it is.");
+        return src;
+    }
+    let lines = format_body(&body_nodes(root), &src, 0, None);
+    if lines.is_empty() { return String::new(); }
+    return lines.join("\n") + "\n";
+}
+
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    return node.utf8_text(src.as_bytes()).unwrap_or("");
+}
+
+// The last row holding text of the node, since nodes may end at the start of the next line.
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row { return end.row - 1; }
+    return end.row;
+}
+
+fn indent(depth: usize) -> String {
+    return INDENT.repeat(depth);
+}
+
+// The width of the indentation of a line, tabs counting as one level.
+fn leading_width(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += INDENT.len(),
+            _ => break,
+        }
+    }
+    return width;
+}
+
+fn in_verbatim(node: Node, byte: usize) -> bool {
+    let mut current = node.descendant_for_byte_range(byte, byte);
+    while let Some(n) = current {
+        if VERBATIM_KINDS.contains(&n.kind()) && n.start_byte() < byte && byte < n.end_byte() { return true; }
+        if n.id() == node.id() { break; }
+        current = n.parent();
+    }
+    return false;
+}
+
+// The statements (and comments) of a body, along with the `&` that put some of
+// them in the background.
+fn body_nodes(node: Node) -> Vec<Node> {
+    return node.children(&mut node.walk()).filter(|x| x.is_named() || x.kind() == "&").collect();
+}
+
+// Reprints the source between two bytes of `node` at `depth`. The lines after the
+// first keep their indentation relative to the first one (or get one level when
+// they had none), unless they start inside a string or heredoc.
+fn format_range(node: Node, start: usize, end: usize, src: &str, depth: usize) -> Vec<String> {
+    let line_start = src[..start].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    let base = leading_width(&src[line_start..start]);
+    let mut lines = Vec::<String>::new();
+    let mut offset = start;
+    for (i, line) in src[start..end].split('\n').enumerate() {
+        let line_begin = offset;
+        let line_end = offset + line.len();
+        offset = line_end + 1;
+        let ends_verbatim = line_end < end && in_verbatim(node, line_end);
+        if i > 0 && in_verbatim(node, line_begin) {
+            lines.push(line.to_string());
+            continue;
+        }
+        let text = if ends_verbatim { line.trim_start() } else { line.trim() };
+        if text.is_empty() { lines.push(String::new()); continue; }
+        if i == 0 {
+            lines.push(format!("{}{}", indent(depth), text));
+            continue;
+        }
+        let width = leading_width(line);
+        let extra = if width > base { width - base } else { INDENT.len() };
+        lines.push(format!("{}{}{}", indent(depth), " ".repeat(extra), text));
+    }
+    return lines;
+}
+
+// Whether every line break of the node (outside strings and heredocs) is escaped with `\`.
+fn only_continued(node: Node, src: &str) -> bool {
+    let start = node.start_byte();
+    for (i, _) in get_text(node, src).match_indices('\n') {
+        let byte = start + i;
+        if !in_verbatim(node, byte) && !src[..byte].ends_with('\\') { return false; }
+    }
+    return true;
+}
+
+fn format_leaf(node: Node, src: &str, depth: usize) -> Vec<String> {
+    return format_range(node, node.start_byte(), node.end_byte(), src, depth);
+}
+
+fn append(lines: &mut Vec<String>, text: &str) {
+    match lines.last_mut() {
+        Some(last) => *last = format!("{} {}", last, text.trim_start()),
+        None => lines.push(text.to_string()),
+    }
+}
+
+// Formats a list of statements, one per line. Statements that shared a line
+// keep sharing it when they're one-liners, comments stay at the end of the line
+// they were on, and runs of blank lines become one. A comment on `header_row`
+// (the line with `then` or `do`) is put first without indentation, for
+// `push_body` to move it up to that line.
+fn format_body(nodes: &[Node], src: &str, depth: usize, header_row: Option<usize>) -> Vec<String> {
+    let mut lines = Vec::<String>::new();
+    let mut prev: Option<Node> = None;
+    for node in nodes {
+        let row = node.start_position().row;
+        if node.kind() == "&" {
+            if let Some(last) = lines.last_mut() { *last += " &"; }
+            continue;
+        }
+        let same_row = prev.is_some_and(|p| last_row(p) == row);
+        if node.kind() == "comment" {
+            let comment = get_text(*node, src).trim_end();
+            if prev.is_none() && header_row == Some(row) {
+                lines.push(comment.to_string());
+            }
+            else if same_row && !lines.is_empty() {
+                append(&mut lines, comment);
+            }
+            else {
+                if prev.is_some_and(|p| row > last_row(p) + 1) { lines.push(String::new()); }
+                lines.push(format!("{}{}", indent(depth), comment));
+            }
+            prev = Some(*node);
+            continue;
+        }
+        let rendered = format_statement(*node, src, depth);
+        let joinable = same_row && rendered.len() == 1 && prev.is_some_and(|p| p.kind() != "comment" && p.start_position().row == last_row(p));
+        if joinable && !lines.is_empty() {
+            let last = lines.len() - 1;
+            let separator = if lines[last].ends_with('&') { " " } else { "; " };
+            lines[last] = format!("{}{}{}", lines[last], separator, rendered[0].trim_start());
+        }
+        else {
+            if prev.is_some_and(|p| row > last_row(p) + 1) { lines.push(String::new()); }
+            lines.extend(rendered);
+        }
+        prev = Some(*node);
+    }
+    return lines;
+}
+
+// Adds a body under a header line, moving a comment that was on the header's
+// row to the end of the header line.
+fn push_body(lines: &mut Vec<String>, nodes: &[Node], src: &str, depth: usize, header_row: usize) {
+    let mut body = format_body(nodes, src, depth, Some(header_row));
+    let first_is_header_comment = nodes.first().is_some_and(|x| x.kind() == "comment" && x.start_position().row == header_row);
+    if first_is_header_comment && !body.is_empty() {
+        let comment = body.remove(0);
+        append(lines, &comment);
+    }
+    lines.extend(body);
+}
+
+fn format_statement(node: Node, src: &str, depth: usize) -> Vec<String> {
+    // compound statements written on one line (or continued with `\`) are kept that way
+    if node.start_position().row == last_row(node) || only_continued(node, src) { return format_leaf(node, src, depth); }
+    return match node.kind() {
+        "if_statement" => format_if(node, src, depth),
+        "for_statement" | "c_style_for_statement" | "while_statement" | "select_statement" => format_loop(node, src, depth),
+        "case_statement" => format_case(node, src, depth),
+        "function_definition" => format_function(node, src, depth),
+        "compound_statement" | "subshell" => format_group(node, src, depth),
+        "redirected_statement" => format_redirected(node, src, depth),
+        "list" | "pipeline" | "negated_command" => format_list(node, src, depth),
+        _ => format_leaf(node, src, depth),
+    };
+}
+
+// `if cond; then`, `elif cond; then`, `else` and `fi`, with the bodies one level deeper.
+fn format_if(node: Node, src: &str, depth: usize) -> Vec<String> {
+    let mut flat = Vec::<Node>::new();
+    for child in node.children(&mut node.walk()) {
+        if child.kind() == "elif_clause" || child.kind() == "else_clause" { flat.extend(child.children(&mut child.walk())); }
+        else { flat.push(child); }
+    }
+    let mut lines = Vec::<String>::new();
+    let mut keyword = "";
+    let mut condition = Vec::<Node>::new();
+    let mut comments = Vec::<String>::new();
+    let mut body = Vec::<Node>::new();
+    let mut header_row = 0;
+    let mut in_condition = false;
+    for child in flat {
+        match child.kind() {
+            "if" | "elif" | "else" | "fi" => {
+                push_body(&mut lines, &body, src, depth + 1, header_row);
+                body.clear();
+                if child.kind() == "if" || child.kind() == "elif" {
+                    keyword = child.kind();
+                    condition.clear();
+                    comments.clear();
+                    in_condition = true;
+                }
+                else {
+                    lines.push(format!("{}{}", indent(depth), child.kind()));
+                    header_row = child.start_position().row;
+                }
+            },
+            "then" => {
+                let mut header = vec![format!("{}{}", indent(depth), keyword)];
+                let parts = condition.iter().map(|x| format_statement(*x, src, depth)).collect::<Vec<Vec<String>>>();
+                for (i, part) in parts.into_iter().enumerate() {
+                    let last = header.len() - 1;
+                    let separator = if i == 0 { " " } else { "; " };
+                    header[last] = format!("{}{}{}", header[last], separator, part[0].trim_start());
+                    header.extend(part.into_iter().skip(1));
+                }
+                let last = header.len() - 1;
+                header[last] += "; then";
+                for comment in &comments { append(&mut header, comment); }
+                lines.extend(header);
+                header_row = child.start_position().row;
+                in_condition = false;
+            },
+            ";" | "\n" => {},
+            "comment" if in_condition => comments.push(get_text(child, src).trim_end().to_string()),
+            _ if in_condition => condition.push(child),
+            _ => if child.is_named() || child.kind() == "&" { body.push(child); },
+        }
+    }
+    return lines;
+}
+
+// `for ...; do`, `while ...; do` and friends, the body and `done`.
+fn format_loop(node: Node, src: &str, depth: usize) -> Vec<String> {
+    let children = node.children(&mut node.walk()).collect::<Vec<Node>>();
+    let body = match children.iter().find(|x| x.kind() == "do_group") {
+        Some(body) => *body,
+        None => return format_leaf(node, src, depth),
+    };
+    let header_end = children.iter()
+        .filter(|x| x.end_byte() <= body.start_byte() && x.kind() != ";" && x.kind() != "comment")
+        .map(|x| x.end_byte()).max().unwrap_or(node.start_byte());
+    let mut lines = format_range(node, node.start_byte(), header_end, src, depth);
+    let last = lines.len() - 1;
+    lines[last] += "; do";
+    for comment in children.iter().filter(|x| x.kind() == "comment" && x.end_byte() <= body.start_byte()) {
+        append(&mut lines, get_text(*comment, src).trim_end());
+    }
+    let nodes = body_nodes(body);
+    push_body(&mut lines, &nodes, src, depth + 1, body.start_position().row);
+    lines.push(format!("{}done", indent(depth)));
+    let rest = src[body.end_byte()..node.end_byte()].trim();
+    if !rest.is_empty() { append(&mut lines, rest); }
+    return lines;
+}
+
+// The items of a case statement go one level deeper than `case`. Items written
+// on one line stay that way, with their commands aligned to the longest pattern
+// of the run of one-line items they belong to.
+fn format_case(node: Node, src: &str, depth: usize) -> Vec<String> {
+    let children = node.children(&mut node.walk()).collect::<Vec<Node>>();
+    let header_end = children.iter().find(|x| x.kind() == "in").map(|x| x.end_byte()).unwrap_or(node.start_byte());
+    let mut lines = format_range(node, node.start_byte(), header_end, src, depth);
+    let header_row = node.start_position().row;
+    let items = children.iter().filter(|x| x.is_named() && x.end_byte() > header_end).copied().collect::<Vec<Node>>();
+    // the width of the patterns of each item that is a one-liner
+    let mut widths = vec![0; items.len()];
+    let mut i = 0;
+    while i < items.len() {
+        let mut j = i;
+        while j < items.len() && is_one_line_item(items[j]) && (j == i || items[j].start_position().row == last_row(items[j - 1]) + 1) {
+            j += 1;
+        }
+        let width = items[i..j].iter().map(|x| case_pattern(*x, src).len()).max().unwrap_or(0);
+        for w in &mut widths[i..j] { *w = width; }
+        i = if j > i { j } else { i + 1 };
+    }
+    let mut prev: Option<Node> = None;
+    for (i, item) in items.iter().enumerate() {
+        let row = item.start_position().row;
+        if item.kind() == "comment" {
+            let comment = get_text(*item, src).trim_end();
+            if (prev.is_some_and(|p| last_row(p) == row) || (prev.is_none() && row == header_row)) && !lines.is_empty() {
+                append(&mut lines, comment);
+            }
+            else {
+                if prev.is_some_and(|p| row > last_row(p) + 1) { lines.push(String::new()); }
+                lines.push(format!("{}{}", indent(depth + 1), comment));
+            }
+            prev = Some(*item);
+            continue;
+        }
+        if prev.is_some_and(|p| row > last_row(p) + 1) { lines.push(String::new()); }
+        lines.extend(format_case_item(*item, src, depth + 1, widths[i]));
+        prev = Some(*item);
+    }
+    lines.push(format!("{}esac", indent(depth)));
+    return lines;
+}
+
+fn is_one_line_item(node: Node) -> bool {
+    return node.kind() == "case_item" && node.start_position().row == last_row(node);
+}
+
+// The patterns of a case item, up to and including the `)`.
+fn case_pattern(node: Node, src: &str) -> String {
+    let close = node.children(&mut node.walk()).find(|x| x.kind() == ")").map(|x| x.end_byte()).unwrap_or(node.start_byte());
+    return src[node.start_byte()..close].trim().to_string();
+}
+
+fn format_case_item(node: Node, src: &str, depth: usize, width: usize) -> Vec<String> {
+    let pattern = case_pattern(node, src);
+    let close = node.children(&mut node.walk()).find(|x| x.kind() == ")").map(|x| x.end_byte()).unwrap_or(node.start_byte());
+    let children = node.children(&mut node.walk()).collect::<Vec<Node>>();
+    let terminator = children.iter().find(|x| matches!(x.kind(), ";;" | ";&" | ";;&")).map(|x| x.kind()).unwrap_or("");
+    let body = children.iter().filter(|x| x.start_byte() >= close && (x.is_named() || x.kind() == "&")).copied().collect::<Vec<Node>>();
+    if is_one_line_item(node) {
+        let mut line = format!("{}{}", indent(depth), pattern);
+        let statements = format_body(&body, src, 0, None);
+        if !statements.is_empty() {
+            line = format!("{}{} {}", line, " ".repeat(width - pattern.len()), statements.join("; "));
+        }
+        if !terminator.is_empty() { line = format!("{} {}", line, terminator); }
+        return vec![line];
+    }
+    let mut lines = vec![format!("{}{}", indent(depth), pattern)];
+    push_body(&mut lines, &body, src, depth + 1, node.start_position().row);
+    if !terminator.is_empty() { lines.push(format!("{}{}", indent(depth + 1), terminator)); }
+    return lines;
+}
+
+fn format_function(node: Node, src: &str, depth: usize) -> Vec<String> {
+    let body = match node.child_by_field_name("body") {
+        Some(body) => body,
+        None => return format_leaf(node, src, depth),
+    };
+    let header = src[node.start_byte()..body.start_byte()].trim();
+    let mut lines = format_statement(body, src, depth);
+    lines[0] = format!("{}{} {}", indent(depth), header, lines[0].trim_start());
+    let rest = src[body.end_byte()..node.end_byte()].trim();
+    if !rest.is_empty() { append(&mut lines, rest); }
+    return lines;
+}
+
+// `{ ... }` and `( ... )` with the statements inside one level deeper.
+fn format_group(node: Node, src: &str, depth: usize) -> Vec<String> {
+    let (open, close) = if node.kind() == "subshell" { ("(", ")") } else { ("{", "}") };
+    let mut lines = vec![format!("{}{}", indent(depth), open)];
+    let nodes = body_nodes(node);
+    push_body(&mut lines, &nodes, src, depth + 1, node.start_position().row);
+    lines.push(format!("{}{}", indent(depth), close));
+    return lines;
+}
+
+// A compound statement followed by redirections, which stay after its last line.
+fn format_redirected(node: Node, src: &str, depth: usize) -> Vec<String> {
+    let body = match node.child_by_field_name("body") {
+        Some(body) if body.start_position().row != last_row(body) => body,
+        _ => return format_leaf(node, src, depth),
+    };
+    let mut lines = format_statement(body, src, depth);
+    let mut rest = format_range(node, body.end_byte(), node.end_byte(), src, depth);
+    if !rest.is_empty() && !rest[0].trim().is_empty() {
+        let first = rest.remove(0);
+        append(&mut lines, &first);
+    }
+    lines.extend(rest);
+    return lines;
+}
+
+// Commands joined by `&&`, `||` or `|`. The operators stay at the end of their
+// line, and a command that was on the next line is indented one level deeper.
+fn format_list(node: Node, src: &str, depth: usize) -> Vec<String> {
+    let mut lines = Vec::<String>::new();
+    let mut prefix = String::new();
+    let mut prev_row: Option<usize> = None;
+    for child in node.children(&mut node.walk()) {
+        let row = child.start_position().row;
+        let same_row = prev_row == Some(row);
+        if child.kind() == "comment" {
+            let comment = get_text(child, src).trim_end();
+            if same_row && !lines.is_empty() { append(&mut lines, comment); }
+            else { lines.push(format!("{}{}", indent(depth + 1), comment)); }
+        }
+        else if !child.is_named() {
+            if lines.is_empty() { prefix = format!("{}{} ", prefix, child.kind()); }
+            else { append(&mut lines, child.kind()); }
+        }
+        else if lines.is_empty() {
+            lines = format_statement(child, src, depth);
+            lines[0] = format!("{}{}{}", indent(depth), prefix, lines[0].trim_start());
+        }
+        else if same_row {
+            let rendered = format_statement(child, src, depth);
+            append(&mut lines, &rendered[0]);
+            lines.extend(rendered.into_iter().skip(1));
+        }
+        else {
+            lines.extend(format_statement(child, src, depth + 1));
+        }
+        prev_row = Some(last_row(child));
+    }
+    return lines;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sh_format;
+
+    fn format(src: &str) -> String {
+        let ast = sh_format::parse_existing_sh_file(src.to_string());
+        return traverse_sh_ast(ast, src.to_string());
+    }
+
+    #[test]
+    fn heredocs() {
+        let src = "\
+if [ -n \"$1\" ]
+then
+cat <<EOF
+  keep   this
+\tand this
+EOF
+fi
+write() {
+cat <<-END >\"$1\"
+\ttabbed  body
+\tEND
+}
+";
+        let expected = "\
+if [ -n \"$1\" ]; then
+    cat <<EOF
+  keep   this
+\tand this
+EOF
+fi
+write() {
+    cat <<-END >\"$1\"
+\ttabbed  body
+\tEND
+}
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn case_alignment() {
+        let src = "\
+case \"$1\" in  # mode
+start) run;;
+stop|halt) kill \"$pid\" ;;
+-h) usage ;;
+
+restart)
+stop
+start
+;;
+*) echo \"unknown: $1\"; exit 1;;
+esac
+";
+        let expected = "\
+case \"$1\" in # mode
+    start)     run ;;
+    stop|halt) kill \"$pid\" ;;
+    -h)        usage ;;
+
+    restart)
+        stop
+        start
+        ;;
+    *) echo \"unknown: $1\"; exit 1 ;;
+esac
+";
+        assert_eq!(format(src), expected);
+    }
+}
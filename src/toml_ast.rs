@@ -0,0 +1,276 @@
+use tree_sitter::{Tree, Node};
+
+// TOML is reprinted from the tree: `key = value` with single spaces, one blank
+// line before every table header (and the comments right above it), at most one
+// blank line elsewhere, and keys and values inside tables left unindented.
+// Literal strings that need no escaping become basic strings, multi-line strings
+// and numbers are kept as written, and comments stay where they were.
+
+const INDENT: &str = "    ";
+
+// Arrays longer than this get one element per line.
+const COMPACT_WIDTH: usize = 80;
+
+// A line of the output along with the rows it came from.
+struct TomlEntry {
+    lines: Vec<String>,
+    start_row: usize,
+    end_row: usize,
+    is_header: bool,
+    is_comment: bool,
+}
+
+pub fn traverse_toml_ast(ast: Tree, src: String) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the TOML file was left as it is.");
+        return src;
+    }
+    let mut entries = Vec::<TomlEntry>::new();
+    for child in root.named_children(&mut root.walk()) {
+        match child.kind() {
+            "table" | "table_array_element" => collect_table(child, &src, &mut entries),
+            _ => push_entry(child, &src, &mut entries),
+        }
+    }
+    let mut result = Vec::<String>::new();
+    for i in 0..entries.len() {
+        if i > 0 && blank_line_before(&entries, i) { result.push(String::new()); }
+        result.extend(entries[i].lines.clone());
+    }
+    if result.is_empty() { return String::new(); }
+    return result.join("\n") + "\n";
+}
+
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    return node.utf8_text(src.as_bytes()).unwrap_or("");
+}
+
+// The last row holding text of the node, since nodes may end at the start of the next line.
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row { return end.row - 1; }
+    return end.row;
+}
+
+// Table headers get a blank line before them, which goes above the comments
+// right above the header. Everywhere else blank lines are kept, but never more
+// than one.
+fn blank_line_before(entries: &[TomlEntry], i: usize) -> bool {
+    let is_adjacent = |j: usize| entries[j].start_row == entries[j - 1].end_row + 1;
+    let mut next = i;
+    while entries[next].is_comment && next + 1 < entries.len() && entries[next + 1].start_row == entries[next].end_row + 1 {
+        next += 1;
+    }
+    if entries[next].is_header {
+        // only the first of the comments leading into the header gets the blank line
+        let starts_run = !(entries[i - 1].is_comment && is_adjacent(i));
+        return starts_run;
+    }
+    return entries[i].start_row > entries[i - 1].end_row + 1;
+}
+
+fn collect_table(node: Node, src: &str, entries: &mut Vec<TomlEntry>) {
+    let (open, close) = if node.kind() == "table" { ("[", "]") } else { ("[[", "]]") };
+    let mut header = String::new();
+    let header_row = node.start_position().row;
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "bare_key" | "quoted_key" | "dotted_key" => header = format!("{}{}{}", open, render_key(child, src), close),
+            "comment" if child.start_position().row == header_row => {
+                header = format!("{} {}", header, get_text(child, src).trim_end());
+            },
+            _ => {
+                if !header.is_empty() {
+                    entries.push(TomlEntry { lines: vec![header.clone()], start_row: header_row, end_row: header_row, is_header: true, is_comment: false });
+                    header.clear();
+                }
+                push_entry(child, src, entries);
+            },
+        }
+    }
+    if !header.is_empty() {
+        entries.push(TomlEntry { lines: vec![header], start_row: header_row, end_row: header_row, is_header: true, is_comment: false });
+    }
+}
+
+fn push_entry(node: Node, src: &str, entries: &mut Vec<TomlEntry>) {
+    let lines = match node.kind() {
+        "pair" => render_pair(node, src),
+        _ => vec![get_text(node, src).trim_end().to_string()],
+    };
+    entries.push(TomlEntry {
+        lines,
+        start_row: node.start_position().row,
+        end_row: last_row(node),
+        is_header: false,
+        is_comment: node.kind() == "comment",
+    });
+}
+
+fn render_key(node: Node, src: &str) -> String {
+    return match node.kind() {
+        "dotted_key" => {
+            let parts = node.named_children(&mut node.walk()).map(|x| render_key(x, src)).collect::<Vec<String>>();
+            parts.join(".")
+        },
+        "quoted_key" => normalize_quotes(get_text(node, src)),
+        _ => get_text(node, src).to_string(),
+    };
+}
+
+fn render_pair(node: Node, src: &str) -> Vec<String> {
+    let mut key = String::new();
+    let mut lines = vec![String::new()];
+    let mut comment: Option<String> = None;
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "bare_key" | "quoted_key" | "dotted_key" => key = render_key(child, src),
+            "comment" => comment = Some(get_text(child, src).trim_end().to_string()),
+            _ => lines = render_value(child, src),
+        }
+    }
+    lines[0] = format!("{} = {}", key, lines[0]);
+    if let Some(comment) = comment {
+        let last = lines.len() - 1;
+        lines[last] = format!("{} {}", lines[last], comment);
+    }
+    return lines;
+}
+
+// The first line goes after `key = `, the other lines are already indented.
+fn render_value(node: Node, src: &str) -> Vec<String> {
+    return match node.kind() {
+        "string" => {
+            let text = get_text(node, src);
+            // multi-line strings are kept as written
+            if text.starts_with("'''") || text.starts_with("\"\"\"") { text.split('\n').map(|x| x.to_string()).collect() }
+            else { vec![normalize_quotes(text)] }
+        },
+        "array" => render_array(node, src),
+        "inline_table" => {
+            let pairs = node.named_children(&mut node.walk())
+                .filter(|x| x.kind() == "pair")
+                .map(|x| render_pair(x, src).join(" "))
+                .collect::<Vec<String>>();
+            if pairs.is_empty() { vec!["{}".to_string()] } else { vec![format!("{{ {} }}", pairs.join(", "))] }
+        },
+        // numbers, booleans and dates are kept as written
+        _ => vec![get_text(node, src).to_string()],
+    };
+}
+
+// 'text' becomes "text" when that needs no escapes.
+fn normalize_quotes(text: &str) -> String {
+    if !text.starts_with('\'') || text.len() < 2 { return text.to_string(); }
+    let inner = &text[1..text.len() - 1];
+    if inner.contains('"') || inner.contains('\\') { return text.to_string(); }
+    return format!("\"{}\"", inner);
+}
+
+fn render_array(node: Node, src: &str) -> Vec<String> {
+    let children = node.named_children(&mut node.walk()).collect::<Vec<Node>>();
+    if children.is_empty() { return vec!["[]".to_string()]; }
+    let has_comments = children.iter().any(|x| x.kind() == "comment");
+    let is_multiline = node.start_position().row != node.end_position().row;
+    let items = children.iter().filter(|x| x.kind() != "comment").map(|x| render_value(*x, src)).collect::<Vec<Vec<String>>>();
+    if !has_comments && !is_multiline && items.iter().all(|x| x.len() == 1) {
+        let compact = format!("[{}]", items.iter().map(|x| x[0].as_str()).collect::<Vec<&str>>().join(", "));
+        if compact.len() <= COMPACT_WIDTH { return vec![compact]; }
+    }
+    // one element per line, with a trailing comma
+    let mut lines = vec!["[".to_string()];
+    let mut prev_row: Option<usize> = None;
+    let mut items = items.into_iter();
+    for child in &children {
+        let row = child.start_position().row;
+        if child.kind() == "comment" {
+            let comment = get_text(*child, src).trim_end();
+            if prev_row == Some(row) && lines.len() > 1 {
+                let last = lines.len() - 1;
+                lines[last] = format!("{} {}", lines[last], comment);
+            }
+            else {
+                lines.push(format!("{}{}", INDENT, comment));
+            }
+            continue;
+        }
+        let mut item = items.next().unwrap_or_default();
+        // multi-line strings can't be indented without changing them
+        let is_verbatim = item.len() > 1 && child.kind() == "string";
+        for (i, line) in item.iter_mut().enumerate() {
+            if !is_verbatim || i == 0 { *line = format!("{}{}", INDENT, line); }
+        }
+        let last = item.len() - 1;
+        item[last] += ",";
+        lines.extend(item);
+        prev_row = Some(last_row(*child));
+    }
+    lines.push("]".to_string());
+    return lines;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toml_format;
+
+    fn format(src: &str) -> String {
+        let ast = toml_format::parse_existing_toml_file(src.to_string());
+        return traverse_toml_ast(ast, src.to_string());
+    }
+
+    #[test]
+    fn quoting() {
+        let src = "\
+name='blfmt'
+path = 'C:\\tools'
+quote = 'say \"hi\"'
+authors=['a', 'b']
+regex = {version='1', features=['std']}
+";
+        let expected = "\
+name = \"blfmt\"
+path = 'C:\\tools'
+quote = 'say \"hi\"'
+authors = [\"a\", \"b\"]
+regex = { version = \"1\", features = [\"std\"] }
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn comments() {
+        let src = "\
+# Package metadata
+[package]
+name = \"blfmt\"   # the binary name
+version   =  \"0.1.0\"
+
+
+authors = []
+# Dependencies come next
+[dependencies]
+list = [
+  1,
+  2 # two
+]
+";
+        let expected = "\
+# Package metadata
+[package]
+name = \"blfmt\" # the binary name
+version = \"0.1.0\"
+
+authors = []
+
+# Dependencies come next
+[dependencies]
+list = [
+    1,
+    2, # two
+]
+";
+        assert_eq!(format(src), expected);
+    }
+}
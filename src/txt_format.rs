@@ -2,11 +2,11 @@ use crate::options;
 
 pub fn format_paragraph(paragraph: String, opts: options::TxtOpts) -> String {
     let mut result = String::new();
-    let cols = opts.columns.clone();
+    let cols = opts.columns;
     let words = paragraph.split_whitespace();
     let mut line = String::new();
     for word in words {
-        if &line.len() + &word.len() > cols {
+        if line.len() + word.len() > cols {
             result += (line.to_owned() + "\n").as_str();
             line = "".to_string();
         }
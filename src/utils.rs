@@ -44,97 +44,352 @@ impl StringUtils for str {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Style {
     Allman,
     KnR,
     Stroustrup,
+    Gnu,
+    Whitesmiths,
+    Linux,
+    OneTrueBrace,
 }
 
-pub fn format_else_lines(file: &mut String, style: &Style) {
+// The kinds of constructs that open a brace block.
+// Brace placement is decided separately for each of these (see `options::BraceWrapping`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BraceKind {
+    Function,
+    Class,
+    Union,
+    Enum,
+    Namespace,
+    Control,
+    Lambda,
+    Extern,
+    Catch,
+    Else,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BracePlacement {
+    Attach,
+    NewLine,
+}
+
+// Whether C++11/C23 attributes like `[[nodiscard]]` go on their own line
+// before the declaration they belong to, or stay inline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeBreak {
+    Always,
+    Functions,
+    Never,
+}
+
+// Where a `requires` clause following a template parameter list goes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequiresClause {
+    OwnLine,
+    TemplateLine,
+}
+
+// Which namespace bodies get indented: none, only namespaces nested in another one, or all.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NamespaceIndentation {
+    None,
+    Inner,
+    All,
+}
+
+// How far `public:`, `protected:` and `private:` sit from the members they introduce.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccessModifierOffset {
+    Outdent,
+    Half,
+    None,
+}
+
+// The groups checked by `--member-order`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemberKind {
+    Type,
+    Constructor,
+    Method,
+    Field,
+}
+
+impl MemberKind {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            MemberKind::Type => "types",
+            MemberKind::Constructor => "constructors",
+            MemberKind::Method => "methods",
+            MemberKind::Field => "fields",
+        };
+    }
+}
+
+// Where constructor initializer lists break: `\n: a(a), b(b)`, `\n: a(a)\n, b(b)` or ` :\na(a),\nb(b)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CtorInitializerBreak {
+    BeforeColon,
+    BeforeComma,
+    AfterColon,
+}
+
+// How many constructor initializers share a line: all of them, as many as fit,
+// all of them if they fit and otherwise one each, or always one each.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CtorInitializerPacking {
+    All,
+    BinPack,
+    Fit,
+    OnePerLine,
+}
+
+// What the body of a lambda passed as an argument is indented from: the start of the statement
+// or the start of the call it is passed to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LambdaBodyAnchor {
+    Statement,
+    Call,
+}
+
+// When the declaration after `template <...>` goes on its own line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TemplateBreak {
+    Always,
+    OverLimit,
+    Never,
+}
+
+// Whether JavaScript/TypeScript statements get semicolons added, dropped where
+// a line break does the same, or left as they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Semicolons {
+    Always,
+    Never,
+    Keep,
+}
+
+// Which quotes JavaScript/TypeScript strings are written with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuoteStyle {
+    Double,
+    Single,
+    Keep,
+}
+
+// What happens to the formatted result: printed, written back, compared
+// against the file (exiting with 1 when they differ) or printed as a diff.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputMode {
+    Print,
+    Write,
+    Check,
+    Diff,
+}
+
+// How an external formatter gets the contents: piped through stdin/stdout,
+// or written to a temporary copy of the file that the program rewrites.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExternalMode {
+    Pipe,
+    InPlace,
+}
+
+thread_local! {
+    // Set once per traversal so the handlers don't need the options passed through every call.
+    static ACTIVE_C_OPTS: std::cell::Cell<options::COpts> = std::cell::Cell::new(options::COpts::default());
+}
+
+pub fn set_active_c_opts(opts: options::COpts) {
+    ACTIVE_C_OPTS.with(|x| x.set(opts));
+}
+
+pub fn active_c_opts() -> options::COpts {
+    return ACTIVE_C_OPTS.with(|x| x.get());
+}
+
+pub fn column_limit(style: Style) -> usize {
     match style {
-        Style::KnR => return,
-        Style::Allman => format_to_allman(file),
-        Style::Stroustrup => format_to_stroustrup(file),
+        Style::Linux => return 80,
+        _ => return 100,
     }
 }
 
-fn format_to_allman(file: &mut String) {
-    let mut lines: Vec<String> = file.lines().map(|x| x.to_string()).collect();
-    let pattern = Regex::new("^.*[^\\\"]\\belse\\b[^\\\"].*$").unwrap();
-    let catch_pattern = Regex::new("^.*[^\\\"]\\bcatch\\b[^\\\"].*$").unwrap();
-    let mut i = 0;
-    while i < lines.len() {
-        let line = lines[i].trim_start();
-        // Skip comments
-        if line.starts_with("//") || line.starts_with("/*") {
-            i += 1;
-            continue;
-        }
-        // Handle `else`
-        if pattern.is_match(&lines[i]) {
-            if let Some(idx) = lines[i].find("else") {
-                let indents = lines[i].chars().take_while(|&c| c == '\t').count();
-                lines[i].insert(idx, '\n');
-                for _ in 0..indents {
-                    lines[i].insert(idx + 1, '\t');
+// Lays out constructor initializers on their own lines, without indentation.
+// With `AfterColon` the colon is left for the caller to put after the declarator.
+pub fn layout_ctor_initializers(initializers: &[String]) -> String {
+    let opts = active_c_opts();
+    // The initializers sit roughly two levels deep: the member and the continuation.
+    let budget = column_limit(opts.style).saturating_sub(8);
+    let mut lines = Vec::<Vec<String>>::new();
+    match opts.ctor_initializer_packing {
+        CtorInitializerPacking::All => lines.push(initializers.to_vec()),
+        CtorInitializerPacking::OnePerLine => lines = initializers.iter().map(|x| vec![x.clone()]).collect(),
+        CtorInitializerPacking::Fit => {
+            if initializers.join(", ").len() + 2 <= budget { lines.push(initializers.to_vec()); }
+            else { lines = initializers.iter().map(|x| vec![x.clone()]).collect(); }
+        },
+        CtorInitializerPacking::BinPack => {
+            let mut current = Vec::<String>::new();
+            for initializer in initializers {
+                let width = current.join(", ").len() + initializer.len() + 4;
+                if !current.is_empty() && width > budget {
+                    lines.push(current);
+                    current = Vec::new();
                 }
+                current.push(initializer.clone());
             }
+            if !current.is_empty() { lines.push(current); }
+        },
+    }
+    let last = lines.len().saturating_sub(1);
+    let mut result = Vec::<String>::new();
+    for (i, line) in lines.iter().enumerate() {
+        let joined = line.join(", ");
+        let trailing_comma = if i < last { "," } else { "" };
+        let temp = match opts.ctor_initializer_break {
+            CtorInitializerBreak::BeforeColon if i == 0 => format!(": {}{}", joined, trailing_comma),
+            CtorInitializerBreak::BeforeColon => format!("  {}{}", joined, trailing_comma),
+            CtorInitializerBreak::BeforeComma if i == 0 => format!(": {}", joined),
+            CtorInitializerBreak::BeforeComma => format!(", {}", joined),
+            CtorInitializerBreak::AfterColon => format!("{}{}", joined, trailing_comma),
+        };
+        result.push(temp);
+    }
+    return result.join("\n");
+}
+
+// Joins the head of a construct (e.g. `if (x)`) with its brace block,
+// placing the opening brace according to the active style.
+pub fn join_block(head: &str, block: &str, kind: BraceKind) -> String {
+    let head = head.trim_end();
+    let block = block.trim_start();
+    if head.is_empty() { return block.to_string(); }
+    match active_c_opts().braces.get(kind) {
+        BracePlacement::Attach => return format!("{} {}", head, block),
+        BracePlacement::NewLine => return format!("{}\n{}", head, block),
+    }
+}
+
+// Joins a closing block with a trailing `else` or `catch` clause.
+pub fn join_continuation(prev: &str, next: &str, kind: BraceKind) -> String {
+    let prev = prev.trim_end();
+    if active_c_opts().braces.get(kind) == BracePlacement::Attach && prev.ends_with("}") {
+        return format!("{} {}", prev, next.trim_start());
+    }
+    return format!("{}\n{}", prev, next.trim_start());
+}
+
+// Places leading attributes before a declaration according to the active options.
+pub fn place_attributes(attributes: &[String], declaration: &str, is_function: bool) -> String {
+    if attributes.is_empty() { return declaration.to_string(); }
+    let own_line = match active_c_opts().attribute_break {
+        AttributeBreak::Always => true,
+        AttributeBreak::Functions => is_function,
+        AttributeBreak::Never => false,
+    };
+    let sep = if own_line { "\n" } else { " " };
+    return format!("{}{}{}", attributes.join(" "), sep, declaration.trim_start());
+}
+
+pub fn join_requires_clause(template_head: &str, requires_clause: &str) -> String {
+    return match active_c_opts().requires_clause {
+        RequiresClause::OwnLine => format!("{}\n{}\n", template_head.trim_end(), requires_clause),
+        RequiresClause::TemplateLine => format!("{} {}\n", template_head.trim_end(), requires_clause),
+    };
+}
+
+// Joins `template <...>` and the declaration it introduces. A head that already spans
+// several lines (e.g. with a `requires` clause on its own line) is left as it is.
+pub fn join_template_head(template_head: &str, declaration: &str) -> String {
+    let head = template_head.trim_end();
+    if head.contains("\n") { return format!("{}\n{}", head, declaration); }
+    let first_line = declaration.lines().next().unwrap_or("");
+    let fits = head.len() + 1 + first_line.len() <= column_limit(active_c_opts().style);
+    return match active_c_opts().template_break {
+        TemplateBreak::Always => format!("{}\n{}", head, declaration),
+        TemplateBreak::OverLimit if !fits => format!("{}\n{}", head, declaration),
+        TemplateBreak::OverLimit | TemplateBreak::Never => format!("{} {}", head, declaration),
+    };
+}
+
+// GNU and Whitesmiths indent the braces themselves, which can only be done
+// once the final indentation levels are known.
+pub fn indent_braces(file: &mut String, style: Style) {
+    match style {
+        Style::Gnu => indent_braces_gnu(file),
+        Style::Whitesmiths => indent_braces_whitesmiths(file),
+        _ => (),
+    }
+}
+
+fn is_brace_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    return trimmed == "{" || trimmed == "}" || trimmed == "};" || trimmed == "{}";
+}
+
+fn indent_braces_whitesmiths(file: &mut String) {
+    let lines: Vec<String> = file.lines().map(|x| x.to_string()).collect();
+    let mut result = Vec::<String>::new();
+    // Each open brace records whether it is on a line of its own, so that the
+    // closing brace of one attached to its line is left where it is.
+    let mut stack = Vec::<bool>::new();
+    for line in lines {
+        let trimmed = line.trim();
+        let mut indented = is_brace_line(&line);
+        if trimmed == "{" { stack.push(true); }
+        else if trimmed != "{}" {
+            if trimmed.starts_with("}") { indented = stack.pop().unwrap_or(true) && indented; }
+            if trimmed.ends_with("{") { stack.push(false); }
         }
-        // Handle `catch`
-        else if catch_pattern.is_match(&lines[i]) {
-            if let Some(idx) = lines[i].find("catch") {
-                let indents = lines[i].chars().take_while(|&c| c == '\t').count();
-                lines[i].insert(idx, '\n');
-                for _ in 0..indents {
-                    lines[i].insert(idx + 1, '\t');
-                }
-            }
-        }
-        // Handle opening braces `{` on the same line as code (Allman conversion)
-        if let Some(pos) = lines[i].find('{') {
-            // Make sure it's not the only thing on the line already
-            if lines[i].trim() != "{" {
-                let indent = lines[i].chars().take_while(|&c| c == '\t').collect::<String>();
-                let before = lines[i][..pos].trim_end().to_string();
-                let after = &lines[i][pos + 1..].to_string().clone(); // Skip over the brace
-                lines[i] = before;
-                lines.insert(i + 1, format!("{}{{{}", indent, after));
-                i += 1; // skip over the inserted line
-            }
-        }
-        i += 1;
+        if indented { result.push(format!("\t{}", line)); }
+        else { result.push(line); }
     }
-    let result = lines.join("\n");
-    *file = result;
+    *file = result.join("\n");
 }
 
-fn format_to_stroustrup(file: &mut String) {
-    let mut lines: Vec<String> = file.lines().into_iter().map(|x| x.to_string()).collect();
-    let pattern = Regex::new("^.*[^\\\"]\\belse\\b[^\\\"].*$").unwrap();
-    let catch_pattern = Regex::new("^.*[^\\\"]\\bcatch\\b[^\\\"].*$").unwrap();
-    for i in 0..lines.len() {
-        if lines[i].trim_start().starts_with("//") || lines[i].trim_start().starts_with("/*") { continue; }
-        if pattern.is_match(&lines[i]) {
-            let idx = lines[i].find("else").unwrap();
-            let indents = lines[i].chars().filter(|x| *x == '\t').count();
-            lines[i].insert(idx-1, '\n');
-            lines[i].remove(idx);
-            for _ in 0..indents { lines[i].insert(idx, '\t'); }
+fn opens_control_block(line: &str) -> bool {
+    let keywords = ["if", "else", "for", "while", "do", "switch", "try", "catch", "match", "loop"];
+    let first = line.trim().split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("");
+    return keywords.contains(&first);
+}
+
+fn indent_braces_gnu(file: &mut String) {
+    let lines: Vec<String> = file.lines().map(|x| x.to_string()).collect();
+    let mut result = Vec::<String>::new();
+    // Each open brace records whether it was indented by half a level.
+    let mut stack = Vec::<bool>::new();
+    let mut last_code_line = String::new();
+    for line in lines {
+        let tabs = detect_indentation(&line);
+        let content = line.trim_start_matches('\t');
+        let trimmed = content.trim();
+        let mut extra = stack.iter().filter(|x| **x).count();
+        if trimmed == "{" {
+            let half = opens_control_block(&last_code_line);
+            if half { extra += 1; }
+            stack.push(half);
+        }
+        // An empty block (`{}`) opens and closes on the same line.
+        else if trimmed.starts_with("{}") {
+            if opens_control_block(&last_code_line) { extra += 1; }
         }
-        else if catch_pattern.is_match(&lines[i]) {
-            let idx = lines[i].find("catch").unwrap();
-            let indents = lines[i].chars().filter(|x| *x == '\t').count();
-            lines[i].insert(idx-1, '\n');
-            lines[i].remove(idx);
-            for _ in 0..indents { lines[i].insert(idx, '\t'); }
+        else {
+            if trimmed.starts_with("}") { stack.pop(); }
+            if trimmed.ends_with("{") { stack.push(false); }
         }
+        if content.is_empty() { result.push(String::new()); }
+        else { result.push(format!("{}{}", " ".repeat(2 * tabs + 2 * extra), content)); }
+        if !trimmed.is_empty() { last_code_line = trimmed.to_string(); }
     }
-    let result = lines.join("\n");
-    *file = result;
+    *file = result.join("\n");
 }
 
-pub fn tidy_up_loose_ends(file: &mut String, style: Style) {
-    let mut lines: Vec<String> = file.lines().into_iter().map(|x| x.to_string()).collect();
+pub fn tidy_up_loose_ends(file: &mut String, opts: options::COpts) {
+    let mut lines: Vec<String> = file.lines().map(|x| x.to_string()).collect();
     let mut lines_clone: Vec<String> = lines.clone();
     for (i, line) in lines_clone.into_iter().enumerate() {
         if line.contains(",") {
@@ -149,8 +404,10 @@ pub fn tidy_up_loose_ends(file: &mut String, style: Style) {
     ensure_no_consecutive_blank_lines(file);
     ensure_proper_doc_comment_spacing(file);
     fix_indentation_levels(file);
+    anchor_lambda_bodies(file);
     shift_back_preproc_lines(file);
-    join_single_line_constructs(file, style);
+    join_single_line_constructs(file, opts);
+    indent_braces(file, opts.style);
 }
 
 pub fn detect_indentation(line: &String) -> usize {
@@ -174,7 +431,7 @@ fn fix_indentation(line: &String, current: usize, target: usize) -> String {
     else {
         let mut temp = line.clone();
         for _ in target..current {
-            temp = remove_single_tab(line);
+            temp = remove_single_tab(&temp);
         }
         result = temp;
     }
@@ -185,21 +442,135 @@ fn fix_indentation_levels(file: &mut String) {
     let lines: Vec<String> = file.lines().map(|x| x.to_string()).collect();
     let mut result = Vec::<String>::new();
     let mut indent_level = 0;
+    // Whether each open brace added a level and whether it opened a namespace;
+    // `extern "C" {` and namespaces may not add one.
+    let mut opened = Vec::<(bool, bool)>::new();
+    let indent_extern_blocks = active_c_opts().indent_extern_blocks;
+    let namespace_indentation = active_c_opts().namespace_indentation;
+    // Constructor initializers are indented one level past their constructor.
+    let mut in_ctor_initializers = false;
     for (i, line) in lines.clone().into_iter().enumerate() {
-        if line == "}" || line.trim_start().starts_with("}") {
+        let trimmed = line.trim();
+        if trimmed.starts_with(": ") || (i > 0 && lines[i-1].trim_end().ends_with(") :")) {
+            in_ctor_initializers = true;
+        }
+        else if trimmed.starts_with("{") || trimmed.is_empty() {
+            in_ctor_initializers = false;
+        }
+        if (line == "}" || line.trim_start().starts_with("}")) && opened.pop().map(|x| x.0).unwrap_or(true) {
             indent_level -= 1;
         }
         let current_level = detect_indentation(&line);
-        if current_level != indent_level {
-            let temp = fix_indentation(&line, current_level, indent_level);
+        // A `requires` clause on its own line is indented one level past its template.
+        let is_requires_line = {
+            i > 0 &&
+            line.trim_start().starts_with("requires ") &&
+            lines[i-1].trim_start().starts_with("template")
+        };
+        let target_level = if is_requires_line || in_ctor_initializers { indent_level + 1 } else { indent_level };
+        if line.trim().is_empty() {
+            result.push(String::new());
+        }
+        else if current_level != target_level {
+            let temp = fix_indentation(&line, current_level, target_level);
             result.push(temp);
         }
         else {
             let temp = line.clone();
             result.push(temp);
         }
+        // `: a_(a), b_(b) {}` closes the constructor body on the initializer line
+        if in_ctor_initializers && trimmed.ends_with("}") { in_ctor_initializers = false; }
         if line == "{" || line.ends_with("{") {
-            indent_level += 1;
+            let is_extern_block = {
+                line.trim_start().starts_with("extern \"") ||
+                (line.trim() == "{" && i > 0 && lines[i-1].trim_start().starts_with("extern \""))
+            };
+            let is_namespace = {
+                line_opens_namespace(&line) ||
+                (line.trim() == "{" && i > 0 && line_opens_namespace(&lines[i-1]))
+            };
+            let in_namespace = opened.iter().any(|x| x.1);
+            let adds_level = match namespace_indentation {
+                _ if is_extern_block => indent_extern_blocks,
+                _ if !is_namespace => true,
+                NamespaceIndentation::None => false,
+                NamespaceIndentation::Inner => in_namespace,
+                NamespaceIndentation::All => true,
+            };
+            if adds_level { indent_level += 1; }
+            opened.push((adds_level, is_namespace));
+            in_ctor_initializers = false;
+        }
+    }
+    *file = result.join("\n");
+}
+
+// `namespace foo {`, `inline namespace v1`, `namespace {`, but not `namespace fs = std::filesystem;`.
+fn line_opens_namespace(line: &str) -> bool {
+    let line = line.trim_start();
+    let is_namespace = line.starts_with("namespace") || line.starts_with("inline namespace");
+    return is_namespace && !line.contains("=") && !line.ends_with(";");
+}
+
+pub fn line_is_access_specifier(line: &str) -> bool {
+    return ["public:", "protected:", "private:"].contains(&line.trim());
+}
+
+// The column (after indentation) of the call whose argument list is still open at the end
+// of `line`, e.g. 10 for `auto r = run(v, [](int x) {`.
+fn find_open_call_column(line: &str) -> Option<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut open_parens = Vec::<usize>::new();
+    let mut in_literal: Option<char> = None;
+    for (i, c) in chars.iter().enumerate() {
+        if let Some(quote) = in_literal {
+            if *c == quote && (i == 0 || chars[i-1] != '\\') { in_literal = None; }
+            continue;
+        }
+        match c {
+            '"' | '\'' => in_literal = Some(*c),
+            '(' => open_parens.push(i),
+            ')' => { open_parens.pop(); },
+            _ => (),
+        }
+    }
+    let mut start = *open_parens.last()?;
+    // Walk back over the callee, e.g. `std::for_each` or `obj.run`.
+    while start > 0 && (chars[start-1].is_alphanumeric() || ['_', ':', '.'].contains(&chars[start-1])) {
+        start -= 1;
+    }
+    return Some(start);
+}
+
+// Moves the bodies of lambdas passed as arguments over to the call they are passed to.
+// Runs after the indentation levels are fixed, and only adds spaces after the tabs.
+fn anchor_lambda_bodies(file: &mut String) {
+    if active_c_opts().lambda_body_anchor != LambdaBodyAnchor::Call { return; }
+    let lines: Vec<String> = file.split("\n").map(|x| x.to_string()).collect();
+    let mut result = Vec::<String>::new();
+    // The brace depth each anchored lambda was opened at, and its extra spaces.
+    let mut anchors = Vec::<(usize, usize)>::new();
+    let mut depth = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let tabs = detect_indentation(line);
+        if trimmed.starts_with("}") { depth -= 1; }
+        // An Allman-style lambda brace on its own line belongs to the line before it.
+        let column = {
+            if trimmed.ends_with("{") && trimmed != "{" { find_open_call_column(trimmed) }
+            else if trimmed == "{" && i > 0 { find_open_call_column(lines[i-1].trim()) }
+            else { None }
+        };
+        let own_brace_line = trimmed == "{" && column.is_some();
+        if own_brace_line { anchors.push((depth, column.unwrap_or(0))); }
+        let spaces: usize = anchors.iter().map(|x| x.1).sum();
+        if trimmed.is_empty() || spaces == 0 { result.push(line.clone()); }
+        else { result.push(format!("{}{}{}", "\t".repeat(tabs), " ".repeat(spaces), line.trim_start())); }
+        if trimmed.starts_with("}") && anchors.last().is_some_and(|x| x.0 == depth) { anchors.pop(); }
+        if trimmed.ends_with("{") {
+            if !own_brace_line && column.is_some() { anchors.push((depth, column.unwrap_or(0))); }
+            depth += 1;
         }
     }
     *file = result.join("\n");
@@ -214,8 +585,8 @@ fn line_is_doc_comment(line: &String) -> bool {
 }
 
 fn find_next_function_def(remaining_lines: &[String], pattern: &regex::Regex) -> i32 {
-    for (i, line) in remaining_lines.into_iter().enumerate() {
-        if line_is_function_def(line, &pattern) {
+    for (i, line) in remaining_lines.iter().enumerate() {
+        if line_is_function_def(line, pattern) {
             return i as i32;
         }
     }
@@ -223,7 +594,7 @@ fn find_next_function_def(remaining_lines: &[String], pattern: &regex::Regex) ->
 }
 
 fn find_next_doc_comment(remaining_lines: &[String]) -> i32 {
-    for (i, line) in remaining_lines.into_iter().enumerate() {
+    for (i, line) in remaining_lines.iter().enumerate() {
         if line.trim_start().starts_with("*") && line.contains("*/") {
             return i as i32;
         }
@@ -237,13 +608,10 @@ fn ensure_proper_doc_comment_spacing(file: &mut String) {
     let pattern = Regex::new("^(\\w*\\s*)*\\w+\\s+\\w+\\(.*\\)\\s*;?\\s*$").unwrap();
     let mut lines_to_remove = Vec::<usize>::new();
     for (i, line) in lines.clone().into_iter().enumerate() {
-        if i > 0 && i < lines.len()-1 {
-            if line.trim().is_empty() {
-                if line_is_doc_comment(lines.iter().nth(i-1).unwrap()) &&
-                   line_is_function_def(lines.iter().nth(i+1).unwrap(), &pattern) {
-                   continue;
-               }
-            }
+        if i > 0 && i < lines.len()-1 && line.trim().is_empty() &&
+           line_is_doc_comment(lines.get(i-1).unwrap()) &&
+           line_is_function_def(lines.get(i+1).unwrap(), &pattern) {
+            continue;
         }
         result.push(line);
     }
@@ -252,10 +620,27 @@ fn ensure_proper_doc_comment_spacing(file: &mut String) {
 
 pub fn ensure_space_after_char(line: &String, target: char) -> String {
     let mut result = String::new();
+    // Leave string and character literals alone, e.g. asm constraints like "=r".
+    let mut in_literal: Option<char> = None;
+    let mut escaped = false;
     for (i, c) in line.char_indices() {
         result += c.to_string().as_str();
+        if let Some(quote) = in_literal {
+            if escaped { escaped = false; }
+            else if c == '\\' { escaped = true; }
+            else if c == quote { in_literal = None; }
+            continue;
+        }
+        if c == '"' || c == '\'' {
+            in_literal = Some(c);
+            continue;
+        }
         if i < line.len()-1 {
-            if c == target && line.chars().nth(i+1) != Some(' ') && line.chars().nth(i+1) != Some(target) {
+            // `<=>` is a single operator.
+            let is_spaceship = c == '=' && line.chars().nth(i+1) == Some('>');
+            // A default capture: `[=]` or `[=, &x]`.
+            let is_capture = c == '=' && i > 0 && line.chars().nth(i-1) == Some('[');
+            if c == target && line.chars().nth(i+1) != Some(' ') && line.chars().nth(i+1) != Some(target) && !is_spaceship && !is_capture {
                 result += " ";
             }
         }
@@ -269,11 +654,14 @@ fn remove_blank_lines_from_blocks(file: &mut String) {
     let mut result = Vec::<String>::new();
     let mut in_block = false;
     for (i, line) in lines.clone().into_iter().enumerate() {
-        let at_block_end = in_block && (line == "}".to_string() || line == "};".to_string());
-        if !in_block && line == "{".to_string() { in_block = true; }
+        let at_block_end = in_block && (line == "}" || line == "};");
+        // Namespace and `export { ... }` bodies keep the blank lines between their declarations.
+        let opens_namespace = i > 0 && (line_opens_namespace(&lines[i-1]) || lines[i-1] == "export");
+        if !in_block && line == "{" && !opens_namespace { in_block = true; }
         else if at_block_end { in_block = false; }
-        if in_block && !check_line_is_blank(&line) { result.push(line.clone()); }
-        else if !in_block { result.push(line.clone()); }
+        // Access sections are separated by a blank line.
+        let before_access_specifier = i+1 < lines.len() && line_is_access_specifier(&lines[i+1]);
+        if !in_block || !check_line_is_blank(&line) || before_access_specifier { result.push(line.clone()); }
     }
     *file = result.join("\n").trim_end().to_string() + "\n";
 }
@@ -305,16 +693,14 @@ pub fn scan_for_lines_before_blank_lines(file: String) -> Vec<(usize, String)> {
         // only add lines that are inside of blocks
         if line.contains("{") { open_braces += 1; }
         if line.contains("}") { open_braces -= 1; }
-        if open_braces > 0 {
-            if i < lines.len()-1  {
-                let has_no_visible_chars = {
-                    lines[i+1]
-                        .chars()
-                        .all(|x| x.is_whitespace() || x == '\t')
-                };
-                if lines[i+1].is_empty() || has_no_visible_chars {
-                    result.push((i+1, line));
-                }
+        if open_braces > 0 && i < lines.len()-1 {
+            let has_no_visible_chars = {
+                lines[i+1]
+                    .chars()
+                    .all(|x| x.is_whitespace() || x == '\t')
+            };
+            if lines[i+1].is_empty() || has_no_visible_chars {
+                result.push((i+1, line));
             }
         }
     }
@@ -329,10 +715,8 @@ fn format_long_lines(file: &mut String, target_len: usize) {
     let mut lines: Vec<String> = file.lines().map(|x| x.to_string()).collect();
     let mut temp = String::new();
     for (i, entry) in lines.clone().iter().enumerate() {
-        if entry.len() > target_len {
-            if entry.chars().filter(|x| *x == '\"').count() == 2 && !entry.contains("{") {
-                lines[i] = format_long_string_line(&entry, target_len);
-            }
+        if entry.len() > target_len && entry.chars().filter(|x| *x == '\"').count() == 2 && !entry.contains("{") {
+            lines[i] = format_long_string_line(entry, target_len);
         }
     }
     *file = lines.join("\n");
@@ -343,7 +727,7 @@ fn format_long_lines(file: &mut String, target_len: usize) {
 fn format_long_string_line(line: &String, target_len: usize) -> String {
     let mut result = line.clone();
     if let Some(idx) = line.find("\"") {
-        let indents = count_leading_chars(&line, '\t');
+        let indents = count_leading_chars(line, '\t');
         let mut s = "\n".to_string();
         for _ in 0..indents+1 { s.push('\t'); }
         result.insert_str(idx-1, s.as_str());
@@ -376,11 +760,9 @@ pub fn add_blank_lines_back(file: &mut String, target_lines: Vec<(usize, String)
             else {
                 let mut idx = entry.0;
                 'Inner: for i in (0..entry.0).rev() {
-                    if &i < &lines.len() {
-                        if &lines[i] == &line {
-                            idx = i+1;
-                            break 'Inner;
-                        }
+                    if i < lines.len() && lines[i] == line {
+                        idx = i+1;
+                        break 'Inner;
                     }
                 }
                 lines.insert(idx, "".to_string());
@@ -390,11 +772,9 @@ pub fn add_blank_lines_back(file: &mut String, target_lines: Vec<(usize, String)
             let line = spaces_to_tabs(&entry.1);
             let mut idx = entry.0;
             'Inner: for i in (0..entry.0).rev() {
-                if &i < &lines.len() {
-                    if &lines[i] == &line {
-                        idx = i+1;
-                        break 'Inner;
-                    }
+                if i < lines.len() && lines[i] == line {
+                    idx = i+1;
+                    break 'Inner;
                 }
             }
             lines.insert(idx, "".to_string());
@@ -403,21 +783,17 @@ pub fn add_blank_lines_back(file: &mut String, target_lines: Vec<(usize, String)
     *file = lines.join("\n");
 }
 
-fn join_single_line_constructs(file: &mut String, style: Style) {
-    let mut allman = false;
-    match style {
-        Style::Allman => allman = true, 
-        _ => (),
-    };
+fn join_single_line_constructs(file: &mut String, opts: options::COpts) {
+    let newline_braces = opts.braces.control == BracePlacement::NewLine;
     let lines: Vec<String> = file.split("\n").map(|x| x.to_string()).collect();
     let mut parts = Vec::<String>::new();
     let mut temp = String::new();
     let mut end = true;
     let mut indent = 0;
     for (i, line) in lines.iter().enumerate() {
-        if allman && i < lines.len()-1 {
+        if newline_braces && i < lines.len()-1 {
             let check = line.trim();
-            let check2 = lines.iter().nth(i+1).unwrap().trim();
+            let check2 = lines.get(i+1).unwrap().trim();
             if check.starts_with("switch") && (!check2.ends_with("{") && !check.ends_with(";")) {
                 // must be added here to preserve indentation
                 temp += format!("{} ", line.trim_end()).as_str();
@@ -452,9 +828,26 @@ fn join_single_line_constructs(file: &mut String, style: Style) {
 fn shift_back_preproc_lines(file: &mut String) {
     let lines: Vec<String> = file.split("\n").map(|x| x.to_string()).collect();
     let mut parts = Vec::<String>::new();
+    let access_modifier_offset = active_c_opts().access_modifier_offset;
     for line in lines {
+        if line_is_access_specifier(&line) {
+            let temp = match access_modifier_offset {
+                AccessModifierOffset::Outdent => remove_single_tab(&line),
+                // the remaining tabs first, then the half level
+                AccessModifierOffset::Half => {
+                    let outdented = remove_single_tab(&line);
+                    let tabs = detect_indentation(&outdented);
+                    format!("{}  {}", "\t".repeat(tabs), outdented.trim_start())
+                },
+                AccessModifierOffset::None => line.clone(),
+            };
+            parts.push(temp);
+            continue;
+        }
         // Labels are also shifted back here because it just made sense
-        if line.trim_start().starts_with("#") || line.trim_end().ends_with(":") {
+        // A constructor followed by its initializers (`Widget() :`) isn't a label.
+        let is_label = line.trim_end().ends_with(":") && !line.trim_end().ends_with(") :");
+        if line.trim_start().starts_with("#") || is_label {
             let temp = remove_single_tab(&line);
             parts.push(temp);
         }
@@ -466,7 +859,7 @@ fn shift_back_preproc_lines(file: &mut String) {
 }
 
 pub fn close_empty_curly_brace_blocks(file: &mut String) {
-    let mut lines: Vec<String> = file.lines().into_iter().map(|x| x.to_string()).collect();
+    let mut lines: Vec<String> = file.lines().map(|x| x.to_string()).collect();
     let mut lines_to_remove = Vec::<usize>::new();
     dbg!(&file);
     for i in 0..lines.len()-1 {
@@ -477,7 +870,8 @@ pub fn close_empty_curly_brace_blocks(file: &mut String) {
             lines_to_remove.push(i+1);
         }
     }
-    for n in lines_to_remove {
+    // Back to front, so the earlier removals don't shift the later indices.
+    for n in lines_to_remove.into_iter().rev() {
         lines.remove(n);
     }
     *file =  lines.join("\n");
@@ -497,14 +891,14 @@ pub fn sort_include_groups(file: String) -> String {
                     sort_includes(&mut temp_group);
                 }
                 // Add sorted group to the result
-                result.extend(temp_group.drain(..));
+                result.append(&mut temp_group);
             }
             result.push(line);
         }
     }
     if !temp_group.is_empty() {
         sort_includes(&mut temp_group);
-        result.extend(temp_group.drain(..));
+        result.append(&mut temp_group);
     }
     return result.join("\n");
 }
@@ -549,73 +943,71 @@ pub fn fix_stars(file: String) -> String {
     for line in lines {
         let mut temp_line = String::new();
         for (i, c) in line.char_indices() {
-            if line.len() > 1 {
-                if i <= line.len()-2 && i > 0 {
-                    // dereferences *must* be in parenthesis or at the start of the line
-                    let deref = {
-                        c == ' ' &&
-                        line.at(i-1).unwrap() == '*' &&
-                        line.at(i+1).unwrap().is_alphanumeric() &&
-                        (
-                            line.at(i-2).unwrap() == '(' ||
-                            line.at(i-2).unwrap() == '\t'
-                        )
-                    };
-                    if deref { continue; }
-                    let ok_mult = {
-                        c == '*' &&
-                        line.at(i-1).unwrap() == ' ' &&
-                        line.at(i+1).unwrap() == ' '
-                    };
-                    if ok_mult {
-                        temp_line.push(c);
-                        continue;
-                    }
-                    let ok_deref = {
-                        c == '*' &&
-                        line.at(i+1).unwrap().is_alphabetic() &&
-                        line.at(i-1).unwrap() == ' ' &&
-                        (
-                            line.at(i-2).unwrap() == ' ' || 
-                            line.at(i-2).unwrap() == ';' || 
-                            line.at(i-3).unwrap() == ';'  
-                        )
-                    };
-                    if ok_deref {
-                        temp_line.push(c);
-                        continue;
-                    }
-                    let ptr = {
-                        c == '*' &&
-                        line.at(i+1).unwrap().is_alphanumeric() &&
-                        (
-                            line.at(i-1).unwrap().is_alphanumeric() ||
-                            line.at(i-1).unwrap() == '*' 
-                        )
-                    };
-                    if ptr {
-                        temp_line += format!("{} ", c).as_str();
-                        continue;
-                    }
-                    let fix = {
-                        c == '*' &&
-                        line.at(i-1).unwrap().is_alphanumeric() &&
-                        line.at(i+1).unwrap().is_whitespace()
-                    };
-                    if fix {
-                        temp_line += format!(" {}", c).as_str();
-                        continue;
-                    }
-                    let swap_ptr = {
-                        c == '*' &&
-                        line.at(i-1).unwrap().is_whitespace() &&
-                        line.at(i+1).unwrap().is_alphanumeric()
-                    };
-                    if swap_ptr {
-                        temp_line.pop();
-                        temp_line += format!("{} ", c).as_str();
-                        continue
-                    }
+            if line.len() > 1 && i <= line.len()-2 && i > 0 {
+                // dereferences *must* be in parenthesis or at the start of the line
+                let deref = {
+                    c == ' ' &&
+                    line.at(i-1).unwrap() == '*' &&
+                    line.at(i+1).unwrap().is_alphanumeric() &&
+                    (
+                        line.at(i-2).unwrap() == '(' ||
+                        line.at(i-2).unwrap() == '\t'
+                    )
+                };
+                if deref { continue; }
+                let ok_mult = {
+                    c == '*' &&
+                    line.at(i-1).unwrap() == ' ' &&
+                    line.at(i+1).unwrap() == ' '
+                };
+                if ok_mult {
+                    temp_line.push(c);
+                    continue;
+                }
+                let ok_deref = {
+                    c == '*' &&
+                    line.at(i+1).unwrap().is_alphabetic() &&
+                    line.at(i-1).unwrap() == ' ' &&
+                    (
+                        line.at(i-2).unwrap() == ' ' || 
+                        line.at(i-2).unwrap() == ';' || 
+                        line.at(i-3).unwrap() == ';'  
+                    )
+                };
+                if ok_deref {
+                    temp_line.push(c);
+                    continue;
+                }
+                let ptr = {
+                    c == '*' &&
+                    (line.at(i+1).unwrap().is_alphanumeric() || line.at(i+1) == Some('_')) &&
+                    (
+                        line.at(i-1).unwrap().is_alphanumeric() ||
+                        line.at(i-1).unwrap() == '*' 
+                    )
+                };
+                if ptr {
+                    temp_line += format!("{} ", c).as_str();
+                    continue;
+                }
+                let fix = {
+                    c == '*' &&
+                    line.at(i-1).unwrap().is_alphanumeric() &&
+                    line.at(i+1).unwrap().is_whitespace()
+                };
+                if fix {
+                    temp_line += format!(" {}", c).as_str();
+                    continue;
+                }
+                let swap_ptr = {
+                    c == '*' &&
+                    line.at(i-1).unwrap().is_whitespace() &&
+                    (line.at(i+1).unwrap().is_alphanumeric() || line.at(i+1) == Some('_'))
+                };
+                if swap_ptr {
+                    temp_line.pop();
+                    temp_line += format!("{} ", c).as_str();
+                    continue
                 }
             }
             temp_line.push(c);
@@ -633,10 +1025,8 @@ pub fn remove_dereference_spaces(line: String) -> String {
             skip = false;
             continue;
         }
-        if i <= line.len()-2 {
-            if c == '*' && line.chars().nth(i+1) == Some(' ') {
-                skip = true;
-            }
+        if i <= line.len()-2 && c == '*' && line.chars().nth(i+1) == Some(' ') {
+            skip = true;
         }
         result += c.to_string().as_str();
     }
@@ -698,7 +1088,7 @@ pub fn switch_pointer_spaces(line: String) -> String {
 pub fn remove_reference_spaces(line: String) -> String {
     let mut result = String::new();
     for (i, c) in line.char_indices() {
-        if i <= line.len()-3 && i > 0 {
+        if i + 3 <= line.len() && i > 0 {
             let skip = {
                 c == ' ' &&
                 line.at(i+1) == Some('&') &&
@@ -741,8 +1131,8 @@ pub fn remove_object_constructor_space(line: String) -> String {
 }
 
 pub fn remove_unnecessary_spaces(line: &String) -> String {
-    let leading_tokens = vec!['(', '[', ' ', '!'];
-    let ending_tokens = vec![')', '[', ']', ' ', ',', ';'];
+    let leading_tokens = ['(', '[', ' ', '!'];
+    let ending_tokens = [')', '[', ']', ' ', ',', ';'];
     let mut result = String::new();
     let mut skip = false;
     let mut in_string = false;
@@ -753,15 +1143,10 @@ pub fn remove_unnecessary_spaces(line: &String) -> String {
         }
         if let Some(next) = line.at(i+1) {
             if next == '"' && c != '\\'{
-                if in_string { in_string = false; }
-                else { in_string = true; }
-            }
-            if ending_tokens.contains(&next) && !in_string {
-                if c == ' ' { continue; }
-            }
-            if leading_tokens.contains(&c) && !in_string {
-                if next == ' ' { skip = true; }
+                in_string = !in_string;
             }
+            if ending_tokens.contains(&next) && !in_string && c == ' ' { continue; }
+            if leading_tokens.contains(&c) && !in_string && next == ' ' { skip = true; }
         }
         result += c.to_string().as_str();
     }
@@ -771,9 +1156,7 @@ pub fn remove_unnecessary_spaces(line: &String) -> String {
 pub fn remove_double_spaces(line: &String) -> String {
     let mut result = "".to_string();
     for (i, c) in line.chars().enumerate() {
-        if i < line.len()-1 {
-            if c == ' ' && line.at(i+1) == Some(' ') { continue; }
-        }
+        if i < line.len()-1 && c == ' ' && line.at(i+1) == Some(' ') { continue; }
         result += c.to_string().as_str();
     }
     return result;
@@ -782,9 +1165,7 @@ pub fn remove_double_spaces(line: &String) -> String {
 pub fn remove_whitespace_before_commas(line: &String) -> String {
     let mut result = "".to_string();
     for (i, c) in line.chars().enumerate() {
-        if i < line.len()-1 {
-            if c == ' ' && line.at(i+1) == Some(',') { continue; }
-        }
+        if i < line.len()-1 && c == ' ' && line.at(i+1) == Some(',') { continue; }
         result += c.to_string().as_str();
     }
     return result;
@@ -807,13 +1188,6 @@ pub fn count_leading_chars(src: &String, target: char) -> usize {
     return count;
 }
 
-pub fn sanitize(input: String) -> String {
-    let allowed_chars = Regex::new(r"[a-zA-Z0-9.\-_\+\=/\s]+").unwrap();
-    let captures = allowed_chars.captures(&input).unwrap();
-    let result = captures.get(0).unwrap().as_str().to_string();
-    return result;
-}
-
 fn line_has_no_alphanumeric(line: &str) -> bool {
     for c in line.chars() {
         if c.is_alphanumeric() {
@@ -824,7 +1198,7 @@ fn line_has_no_alphanumeric(line: &str) -> bool {
 }
 
 pub fn remove_blank_lines(lines: Vec<&str>) -> String {
-    let result: Vec<&str> = lines.into_iter().filter(|line| !line_has_no_alphanumeric(line) || line.contains("}")).collect();
+    let result: Vec<&str> = lines.into_iter().filter(|line| !line_has_no_alphanumeric(line) || line.contains("}") || line.contains("{")).collect();
     return result.join("\n");
 }
 
@@ -836,26 +1210,24 @@ pub fn write_file(path: &String, contents: &[u8]) -> Result<(), Error> {
 
 pub fn extract_txt_opts(args: Vec<&str>) -> options::TxtOpts {
     let start = args.iter().position(|x| x == &"-o" || x == &"--opts").unwrap() + 1;
-    let remainder = args[start..].into_iter().map(|x| x.to_owned()).collect::<Vec<&str>>();
-    let pattern = Regex::new(r"\s-\w").unwrap();
+    let remainder = args[start..].iter().map(|x| x.to_owned()).collect::<Vec<&str>>();
+    let pattern = Regex::new(r"\s--?\w").unwrap();
     let remainder_string = &remainder.join(" ");
-    let matches = &pattern.find_iter(&remainder_string).map(|x| x.as_str()).collect::<Vec<&str>>();
-    let end: usize;
-    if matches.len() > 0 {
+    let matches = &pattern.find_iter(remainder_string).map(|x| x.as_str()).collect::<Vec<&str>>();
+    let end = if matches.len() > 0 {
         let flag = remainder.iter().filter(|x| x.contains("-")).collect::<Vec<&&str>>()[0].to_owned();
         let start = &start.clone();
-        end = args[*start..].iter().position(|x| x == &flag).unwrap() + start;
+        args[*start..].iter().position(|x| x == &flag).unwrap() + start
     }
     else {
-        end = args.len();
-    }
-    let opts = args[start..end].into_iter().map(|s| s.parse::<usize>().expect("Error parsing number.")).collect::<Vec<usize>>();
-    let result: TxtOpts;
+        args.len()
+    };
+    let opts = args[start..end].iter().map(|s| s.parse::<usize>().expect("Error parsing number.")).collect::<Vec<usize>>();
     let cols = opts[0];
     let spacing = opts[1];
-    result = TxtOpts{
+    let result = TxtOpts{
         columns: cols,
-        spacing: spacing
+        spacing
     };
     return result;
 }
@@ -869,7 +1241,7 @@ pub fn intersperse<T: Clone>(vec: Vec<T>, value: T) -> Vec<T> {
         result.push(item);
         result.push(value.clone());
     }
-    result = result[..result.len()-1].into_iter().map(|x| x.to_owned()).collect::<Vec<T>>();
+    result = result[..result.len()-1].iter().map(|x| x.to_owned()).collect::<Vec<T>>();
     return result;
 }
 
@@ -885,8 +1257,8 @@ pub fn check_for_even_line_length(lines: &Vec<&str>) -> bool {
     let med = median(&line_lens);
     let check_val = (med as f32 * 0.8).ceil();
     let mut count = 0;
-    for i in 0..line_lens.len() {
-        if (line_lens[i] as f32) < check_val && i != 0 {
+    for (i, len) in line_lens.iter().enumerate() {
+        if (*len as f32) < check_val && i != 0 {
             count += 1;
         }
     }
@@ -902,19 +1274,34 @@ fn check_line_is_blank(line: &String) -> bool {
 }
 
 pub fn infer_file_type(filepath: &String) -> String {
+    // build files are recognized by their name, CMakeLists.txt isn't prose
+    let name = std::path::Path::new(filepath).file_name().and_then(|x| x.to_str()).unwrap_or("");
+    for (file_name, file_type) in get_file_names_list() {
+        if name == file_name { return file_type.to_string(); }
+    }
+    let ext = get_file_extension(filepath);
     let supported_types = get_file_extensions_list();
     for item in supported_types {
-        if filepath.contains(&item) {
+        if ext == item {
             return item.to_string();
         }
     }
     return "unknown".to_string();
 }
 
+// The extension of the file name with its dot (".rs"), so that dots in directory
+// names (.cursor/a.py) or longer extensions (README.rst) do not count.
+pub fn get_file_extension(filepath: &str) -> String {
+    let ext = std::path::Path::new(filepath).extension().and_then(|x| x.to_str());
+    return match ext {
+        Some(ext) => format!(".{}", ext),
+        None => String::new(),
+    };
+}
+
 pub fn get_file_extensions_list() -> Vec<String> {
-    // .cpp has to stay before .c, otherwise it breaks
     let exts = {
-        vec![".cpp", ".cc", ".C", ".c", ".hpp", ".hh", ".H", ".h", ".go", ".py", ".txt"]
+        vec![".json", ".toml", ".yaml", ".yml", ".cmake", ".mk", ".rs", ".jsx", ".js", ".tsx", ".ts", ".cpp", ".cc", ".C", ".cuh", ".cu", ".cl", ".c", ".hpp", ".hh", ".H", ".h", ".go", ".py", ".sh", ".bash", ".md", ".txt"]
             .into_iter()
             .map(|x| x.to_string())
             .collect::<Vec<String>>()
@@ -922,17 +1309,67 @@ pub fn get_file_extensions_list() -> Vec<String> {
     return exts;
 }
 
+// File names that stand for a file type on their own.
+pub fn get_file_names_list() -> Vec<(&'static str, &'static str)> {
+    return vec![
+        ("CMakeLists.txt", ".cmake"),
+        ("Makefile", ".mk"),
+        ("makefile", ".mk"),
+        ("GNUmakefile", ".mk"),
+    ];
+}
+
+// CUDA (.cu, .cuh) and OpenCL C (.cl) sources.
+pub fn is_gpu_source(path: &str) -> bool {
+    return [".cu", ".cuh", ".cl"].iter().any(|x| path.ends_with(x));
+}
+
+// Function and address space qualifiers of CUDA and OpenCL C.
+// `__restrict__` is left out, both grammars know it as a type qualifier.
+const GPU_QUALIFIERS: [&str; 18] = [
+    "__global__", "__device__", "__host__", "__shared__", "__constant__", "__managed__",
+    "__forceinline__", "__noinline__",
+    "__kernel", "kernel", "__global", "global", "__local", "local", "__private", "private",
+    "__constant", "constant",
+];
+
+// Neither grammar knows these qualifiers, so they are passed through as GNU attributes,
+// which are kept where they were written, and restored afterwards.
+pub fn mask_gpu_qualifiers(src: &str, is_opencl: bool) -> String {
+    let mut result = src.to_string();
+    for qualifier in GPU_QUALIFIERS {
+        // The bare OpenCL spellings are only keywords in OpenCL C.
+        if !qualifier.starts_with("__") && !is_opencl { continue; }
+        let pattern = Regex::new(format!(r"\b{}\b", qualifier).as_str()).unwrap();
+        let replacement = format!("__attribute__((blfmt_{}))", qualifier);
+        result = pattern.replace_all(&result, replacement.as_str()).to_string();
+    }
+    return result;
+}
+
+pub fn unmask_gpu_qualifiers(src: &str) -> String {
+    let mut result = src.to_string();
+    for qualifier in GPU_QUALIFIERS {
+        result = result.replace(format!("__attribute__((blfmt_{}))", qualifier).as_str(), qualifier);
+    }
+    return result;
+}
+
 pub fn display_file_extensions() {
     let lines = get_file_extensions_list();
     for line in lines {
         println!("{}", line);
     }
+    for (name, _) in get_file_names_list() {
+        println!("{}", name);
+    }
 }
 
 pub fn check_valid_file_ext(path: &String) -> bool {
     let exts = get_file_extensions_list();
+    let path_ext = get_file_extension(path);
     for ext in exts {
-        if path.contains(&ext) { return true; }
+        if path_ext == ext { return true; }
     }
     return false;
 }
@@ -944,12 +1381,40 @@ USAGE:\nblfmt <file-path> <flags + opts>
 
 FLAGS:
     -h or --help
+    -w or --write | --check | --diff
+    --external <file-ext> \"<program> <args>\" ({{path}} is replaced by the file path)
+    --external-mode stdin(default)|in-place (for the --external before it)
+    --external-codes <comma-separated exit codes> (default: 0, for the --external before it)
+    --config <path> (default: the closest .blfmt file above the formatted file)
 (Code only):
     -  or --stdin <file-ext>
+(C/C++/Rust/JavaScript/TypeScript):
+    -s or --style allman|knr|stroustrup(default)|gnu|whitesmiths|linux|1tbs
+    -b or --braces <kind>=attach|newline ...
+        (kinds: function, class, union, enum, namespace, control, lambda, extern, catch, else)
 (C/C++ only):
-    -s or --style allman|knr|stroustrup(default)
-(Txt only):
-    -o or --options <columns> <spacing> (numbers)
+    --attribute-break always|functions(default)|never
+    --requires-clause own-line(default)|template-line
+    --extern-block indent(default)|no-indent
+    --namespace-indent none|inner|all(default)
+    --namespace-comments add|keep(default)
+    --nested-namespaces compact|keep(default)
+    --access-offset outdent(default)|half|none
+    --ctor-init-break before-colon(default)|before-comma|after-colon
+    --ctor-init-pack all(default)|bin-pack|fit|one-per-line
+    --lambda-indent statement(default)|call
+    --template-break always(default)|over-limit|never
+    --template-space space(default)|none
+    --angle-brackets merged(default)|spaced
+    --member-order default|<types,constructors,methods,fields in any order>
+(JavaScript/TypeScript only):
+    --semicolons always(default)|never|keep
+    --quotes double(default)|single|keep
+(JSON only):
+    --json-indent <spaces>(default: 2)|tab
+    --sort-keys
+(Txt only, -o also applies to Markdown):
+    -o or --opts <columns> <spacing> (numbers)
     -t or --titles \"Places\" \"where\" \"you\" \"want\" \"paragraphs\" ...
     (The txt flags may be combined)
 "
@@ -965,6 +1430,157 @@ pub fn write_results(path: &String, results: String) {
 }
 
 pub fn print_results(results: &String) {
-    println!("{}", results);
+    print!("{}", results);
+}
+
+pub fn emit_results(path: &String, original: &String, results: String, mode: OutputMode) {
+    // some formatters end their output with a newline and some do not, every
+    // mode gets exactly one so that printing, writing and checking agree
+    let results = if results.trim_end_matches('\n').is_empty() { results } else { format!("{}\n", results.trim_end_matches('\n')) };
+    match mode {
+        OutputMode::Print => print_results(&results),
+        OutputMode::Write => write_results(path, results),
+        OutputMode::Check => {
+            if *original != results {
+                println!("'{}' would be reformatted.", path);
+                std::process::exit(1);
+            }
+        },
+        OutputMode::Diff => print!("{}", unified_diff(path, original, &results)),
+    }
+}
+
+// Past this many line pairs the changed middle of the file is shown as a
+// single hunk instead of being diffed line by line.
+const MAX_DIFF_CELLS: usize = 25_000_000;
+
+// The lines of `old` and `new` as a list of edits: ' ' kept, '-' removed, '+' added.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_mid, new_mid) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    let mut edits = old[..prefix].iter().map(|line| (' ', *line)).collect::<Vec<(char, &str)>>();
+    if old_mid.len() * new_mid.len() > MAX_DIFF_CELLS {
+        edits.extend(old_mid.iter().map(|line| ('-', *line)));
+        edits.extend(new_mid.iter().map(|line| ('+', *line)));
+    }
+    else {
+        // lcs[i][j] is the length of the longest common subsequence of old_mid[i..] and new_mid[j..]
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] { lcs[i + 1][j + 1] + 1 }
+                            else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                edits.push((' ', old_mid[i]));
+                i += 1;
+                j += 1;
+            }
+            else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                edits.push(('-', old_mid[i]));
+                i += 1;
+            }
+            else {
+                edits.push(('+', new_mid[j]));
+                j += 1;
+            }
+        }
+    }
+    edits.extend(old[old.len() - suffix..].iter().map(|line| (' ', *line)));
+    return edits;
 }
 
+// A unified diff with three lines of context, empty when nothing changed.
+pub fn unified_diff(path: &String, old: &str, new: &str) -> String {
+    if old == new { return String::new(); }
+    let old_lines = old.lines().collect::<Vec<&str>>();
+    let new_lines = new.lines().collect::<Vec<&str>>();
+    let edits = diff_lines(&old_lines, &new_lines);
+    let context = 3;
+    let mut result = format!("--- {}\n+++ {}\n", path, path);
+    let mut i = 0;
+    while i < edits.len() {
+        if edits[i].0 == ' ' {
+            i += 1;
+            continue;
+        }
+        // a hunk runs until there are more than two context lines' worth of unchanged lines
+        let start = i.saturating_sub(context);
+        let mut end = i;
+        let mut unchanged = 0;
+        while end < edits.len() && unchanged <= 2 * context {
+            if edits[end].0 == ' ' { unchanged += 1; }
+            else { unchanged = 0; }
+            end += 1;
+        }
+        end -= unchanged.saturating_sub(context);
+        let old_start = edits[..start].iter().filter(|edit| edit.0 != '+').count();
+        let new_start = edits[..start].iter().filter(|edit| edit.0 != '-').count();
+        let old_count = edits[start..end].iter().filter(|edit| edit.0 != '+').count();
+        let new_count = edits[start..end].iter().filter(|edit| edit.0 != '-').count();
+        result += &format!("@@ -{},{} +{},{} @@\n", old_start + 1, old_count, new_start + 1, new_count);
+        for (kind, line) in &edits[start..end] {
+            result += &format!("{}{}\n", kind, line);
+        }
+        i = end;
+    }
+    if old.ends_with('\n') != new.ends_with('\n') {
+        result += "\\ No newline at end of file\n";
+    }
+    return result;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_type_comes_from_the_extension() {
+        assert_eq!(infer_file_type(&"src/main.rs".to_string()), ".rs");
+        assert_eq!(infer_file_type(&"kernel.cu".to_string()), ".cu");
+        assert_eq!(infer_file_type(&"a.cpp".to_string()), ".cpp");
+        assert_eq!(infer_file_type(&".cursor/a.py".to_string()), ".py");
+        assert_eq!(infer_file_type(&"build/CMakeLists.txt".to_string()), ".cmake");
+        assert_eq!(infer_file_type(&"README.rst".to_string()), "unknown");
+        assert_eq!(infer_file_type(&"data.tsv".to_string()), "unknown");
+        assert_eq!(infer_file_type(&"notes.c.orig".to_string()), "unknown");
+    }
+
+    #[test]
+    fn unified_diffs() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\n";
+        assert_eq!(unified_diff(&"x.c".to_string(), old, old), "");
+        assert_eq!(unified_diff(&"x.c".to_string(), old, new), "\
+--- x.c
++++ x.c
+@@ -2,7 +2,7 @@
+ b
+ c
+ d
+-e
++E
+ f
+ g
+ h
+");
+        assert_eq!(unified_diff(&"x.c".to_string(), "a", "a\n"), "--- x.c\n+++ x.c\n\\ No newline at end of file\n");
+        assert_eq!(unified_diff(&"x.c".to_string(), "a\n", "b\n"), "--- x.c\n+++ x.c\n@@ -1,1 +1,1 @@\n-a\n+b\n");
+    }
+
+    #[test]
+    fn written_results_end_with_one_newline() {
+        let path = std::env::temp_dir().join(format!("blfmt-test-{}.c", std::process::id())).to_string_lossy().to_string();
+        emit_results(&path, &"int x;".to_string(), "int x;\n\n\n".to_string(), OutputMode::Write);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "int x;\n");
+        // already formatted, so checking neither prints nor exits
+        emit_results(&path, &"int x;\n".to_string(), "int x;".to_string(), OutputMode::Check);
+        _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,490 @@
+use tree_sitter::{Tree, Node};
+
+// YAML is reprinted from the tree: two spaces per nesting level (sequences under
+// a key included), a single space after `:` and `-`, double quotes for single
+// quoted scalars that don't need escaping, and at most one blank line between
+// entries. Block scalars keep their inner layout and comments stay in place.
+
+const INDENT: &str = "  ";
+
+// Flow collections (`[a, b]`, `{ a: 1 }`) longer than this get one element per line.
+const COMPACT_WIDTH: usize = 80;
+
+// A rendered value: the text that goes on the line of its key or dash (anchors,
+// tags, a flow value or a block scalar header), a comment that goes on that line
+// too, and the lines after it, indented relative to the key or dash.
+struct YamlValue {
+    head: String,
+    comment: Option<String>,
+    body: Vec<String>,
+    is_block: bool,
+}
+
+pub fn traverse_yaml_ast(ast: Tree, src: String) -> String {
+    let root = ast.root_node();
+    if root.has_error() {
+        eprintln!("Syntax errors found, the YAML file was left as it is.");
+        return src;
+    }
+    let children = root.children(&mut root.walk()).collect::<Vec<Node>>();
+    let lines = render_entries(&children, &src, &|node| {
+        if node.kind() == "document" { render_document(node, &src) }
+        else { get_text(node, &src).trim_end().split('\n').map(|x| x.to_string()).collect() }
+    });
+    if lines.is_empty() { return String::new(); }
+    return lines.join("\n") + "\n";
+}
+
+fn get_text<'a>(node: Node, src: &'a str) -> &'a str {
+    return node.utf8_text(src.as_bytes()).unwrap_or("");
+}
+
+// The last row holding text of the node, since nodes (block scalars mostly) may
+// take in the blank lines after them.
+fn last_row(node: Node, src: &str) -> usize {
+    return node.start_position().row + get_text(node, src).trim_end().matches('\n').count();
+}
+
+fn indent_lines(lines: Vec<String>, indent: &str) -> Vec<String> {
+    return lines.into_iter().map(|x| if x.is_empty() { x } else { format!("{}{}", indent, x) }).collect();
+}
+
+// Renders the entries of a stream, document or block collection one after the
+// other. Comments on the last row of the previous entry stay at the end of its
+// line, and any run of blank lines between entries becomes a single one.
+fn render_entries(children: &[Node], src: &str, render: &dyn Fn(Node) -> Vec<String>) -> Vec<String> {
+    let mut lines = Vec::<String>::new();
+    let mut prev_row: Option<usize> = None;
+    for child in children {
+        let row = child.start_position().row;
+        if child.kind() == "comment" && prev_row == Some(row) && !lines.is_empty() {
+            let last = lines.len() - 1;
+            lines[last] = format!("{} {}", lines[last], get_text(*child, src).trim_end());
+            continue;
+        }
+        let entry = if child.kind() == "comment" { vec![get_text(*child, src).trim_end().to_string()] }
+                    else { render(*child) };
+        if entry.is_empty() { continue; }
+        let ends_blank = lines.last().is_some_and(|x| x.is_empty());
+        if prev_row.is_some_and(|prev| row > prev + 1) && !lines.is_empty() && !ends_blank { lines.push(String::new()); }
+        lines.extend(entry);
+        prev_row = Some(last_row(*child, src));
+    }
+    return lines;
+}
+
+fn render_document(node: Node, src: &str) -> Vec<String> {
+    let children = node.children(&mut node.walk()).collect::<Vec<Node>>();
+    return render_entries(&children, src, &|child| {
+        match child.kind() {
+            "block_node" | "flow_node" => {
+                let value = render_node(child, src, 0);
+                let mut lines = Vec::<String>::new();
+                if !value.head.is_empty() { lines.push(value.head); }
+                if let Some(comment) = value.comment { lines.push(comment); }
+                // top-level collections are not indented
+                let body = if value.is_block { value.body.into_iter().map(|x| x.strip_prefix(INDENT).map(|x| x.to_string()).unwrap_or(x)).collect() }
+                           else { value.body };
+                lines.extend(body);
+                lines
+            },
+            // `---`, `...` and directives
+            _ => vec![get_text(child, src).trim_end().to_string()],
+        }
+    });
+}
+
+// Renders a block or flow node. `owner_column` is the source column of the key or
+// dash the node belongs to, which block scalars with an explicit indentation
+// indicator are measured from.
+fn render_node(node: Node, src: &str, owner_column: usize) -> YamlValue {
+    if node.kind() != "block_node" {
+        let mut lines = render_flow(node, src);
+        let head = lines.remove(0);
+        return YamlValue { head, comment: None, body: lines, is_block: false };
+    }
+    let mut properties = Vec::<String>::new();
+    let mut value = YamlValue { head: String::new(), comment: None, body: Vec::new(), is_block: false };
+    for child in node.named_children(&mut node.walk()) {
+        match child.kind() {
+            "anchor" | "tag" => properties.push(get_text(child, src).to_string()),
+            "block_mapping" | "block_sequence" => {
+                value.body = indent_lines(render_collection(child, src), INDENT);
+                value.is_block = true;
+            },
+            "block_scalar" => {
+                let (header, body) = render_block_scalar(child, src, owner_column);
+                properties.push(header);
+                value.body = body;
+            },
+            "comment" => value.body.push(format!("{}{}", INDENT, get_text(child, src).trim_end())),
+            _ => properties.push(get_text(child, src).trim().to_string()),
+        }
+    }
+    value.head = properties.join(" ");
+    return value;
+}
+
+fn render_collection(node: Node, src: &str) -> Vec<String> {
+    let children = node.named_children(&mut node.walk())
+        .filter(|x| x.kind() != "comment" || owning_collection(*x) == Some(node))
+        .collect::<Vec<Node>>();
+    return render_entries(&children, src, &|child| {
+        let mut lines = match child.kind() {
+            "block_mapping_pair" => render_pair(child, src),
+            "block_sequence_item" => render_item(child, src),
+            _ => vec![get_text(child, src).trim_end().to_string()],
+        };
+        lines.extend(hoisted_comments(child, node, src));
+        lines
+    });
+}
+
+// The block collection a comment belongs to. The grammar puts the comments after
+// the last entry of a collection inside it, even when they are dedented to an outer
+// collection (a comment line before the next `- `, say), so those go up to the
+// innermost collection that starts at or before their column.
+fn owning_collection(comment: Node) -> Option<Node> {
+    let is_collection = |node: Node| node.kind() == "block_mapping" || node.kind() == "block_sequence";
+    if !comment.parent().is_some_and(is_collection) { return comment.parent(); }
+    let mut next = comment.next_named_sibling();
+    while let Some(sibling) = next {
+        if sibling.kind() != "comment" { return comment.parent(); }
+        next = sibling.next_named_sibling();
+    }
+    let column = comment.start_position().column;
+    let mut parent = comment.parent();
+    while let Some(node) = parent {
+        if is_collection(node) && node.start_position().column <= column { return Some(node); }
+        parent = node.parent();
+    }
+    return comment.parent();
+}
+
+// The comments inside an entry that belong to the collection holding the entry.
+fn hoisted_comments(entry: Node, collection: Node, src: &str) -> Vec<String> {
+    let mut comments = Vec::<Node>::new();
+    let mut stack = entry.named_children(&mut entry.walk()).collect::<Vec<Node>>();
+    while let Some(node) = stack.pop() {
+        if node.kind() == "comment" && node.parent() != Some(collection) && owning_collection(node) == Some(collection) {
+            comments.push(node);
+        }
+        stack.extend(node.named_children(&mut node.walk()));
+    }
+    comments.sort_by_key(|x| x.start_byte());
+    let mut lines = Vec::<String>::new();
+    for comment in comments {
+        let prev_row = comment.prev_named_sibling().map(|x| last_row(x, src)).unwrap_or(comment.start_position().row);
+        if comment.start_position().row > prev_row + 1 && lines.last().is_none_or(|x| !x.is_empty()) { lines.push(String::new()); }
+        lines.push(get_text(comment, src).trim_end().to_string());
+    }
+    return lines;
+}
+
+// Puts a value after `- `, `? ` or `: `. Block collections start on the same line,
+// the way `- name: x` is usually written.
+fn prefixed(prefix: &str, value: YamlValue) -> Vec<String> {
+    let mut lines = Vec::<String>::new();
+    let mut body = value.body;
+    if !value.head.is_empty() {
+        lines.push(format!("{}{}", prefix, value.head));
+    }
+    else if value.is_block && value.comment.is_none() && !body.is_empty() && body[0].starts_with(INDENT) {
+        let first = body.remove(0);
+        lines.push(format!("{}{}", prefix, &first[INDENT.len()..]));
+    }
+    else {
+        lines.push(prefix.trim_end().to_string());
+    }
+    if let Some(comment) = value.comment { lines[0] = format!("{} {}", lines[0], comment); }
+    lines.extend(body);
+    return lines;
+}
+
+// Comments between a key (or dash) and its value: the one on the line of the key
+// stays there, the others go before the value.
+fn attach_comments(value: &mut YamlValue, comments: Vec<(usize, String)>, owner_row: usize) {
+    let mut leading = Vec::<String>::new();
+    for (row, comment) in comments {
+        if row == owner_row && value.comment.is_none() && value.head.is_empty() { value.comment = Some(comment); }
+        else { leading.push(format!("{}{}", INDENT, comment)); }
+    }
+    if leading.is_empty() { return; }
+    if value.is_block || value.head.is_empty() {
+        leading.extend(std::mem::take(&mut value.body));
+        value.body = leading;
+    }
+    else {
+        value.body.extend(leading);
+    }
+}
+
+fn render_item(node: Node, src: &str) -> Vec<String> {
+    let owner_column = node.start_position().column;
+    let mut comments = Vec::<(usize, String)>::new();
+    let mut value: Option<YamlValue> = None;
+    for child in node.named_children(&mut node.walk()) {
+        if child.kind() == "comment" { comments.push((child.start_position().row, get_text(child, src).trim_end().to_string())); }
+        else { value = Some(render_node(child, src, owner_column)); }
+    }
+    let mut value = value.unwrap_or(YamlValue { head: String::new(), comment: None, body: Vec::new(), is_block: false });
+    attach_comments(&mut value, comments, node.start_position().row);
+    return prefixed("- ", value);
+}
+
+fn render_pair(node: Node, src: &str) -> Vec<String> {
+    let owner_column = node.start_position().column;
+    let key = node.child_by_field_name("key");
+    let value = node.child_by_field_name("value");
+    let is_explicit = node.child(0).is_some_and(|x| x.kind() == "?");
+    let mut comments = Vec::<(usize, String)>::new();
+    for child in node.named_children(&mut node.walk()) {
+        if child.kind() == "comment" { comments.push((child.start_position().row, get_text(child, src).trim_end().to_string())); }
+    }
+    let mut rendered = match value {
+        Some(value) => render_node(value, src, owner_column),
+        None => YamlValue { head: String::new(), comment: None, body: Vec::new(), is_block: false },
+    };
+    let value_row = value.map(|x| x.start_position().row).unwrap_or(node.start_position().row);
+    if is_explicit {
+        // `? key` on its own line, then `: value`
+        let mut lines = match key {
+            Some(key) => prefixed("? ", render_node(key, src, owner_column)),
+            None => vec!["?".to_string()],
+        };
+        attach_comments(&mut rendered, comments, value_row);
+        if value.is_some() || node.children(&mut node.walk()).any(|x| x.kind() == ":") { lines.extend(prefixed(": ", rendered)); }
+        return lines;
+    }
+    let key_text = key.map(|key| render_flow(key, src).join(" ")).unwrap_or_default();
+    attach_comments(&mut rendered, comments, node.start_position().row);
+    let mut lines = vec![format!("{}:", key_text)];
+    if !rendered.head.is_empty() { lines[0] = format!("{} {}", lines[0], rendered.head); }
+    if let Some(comment) = rendered.comment { lines[0] = format!("{} {}", lines[0], comment); }
+    lines.extend(rendered.body);
+    return lines;
+}
+
+// The header of a block scalar and its content lines, indented one level under
+// the key. With an explicit indentation indicator the content keeps its indentation
+// relative to the key, which the indicator is counted from.
+fn render_block_scalar(node: Node, src: &str, owner_column: usize) -> (String, Vec<String>) {
+    let text = get_text(node, src);
+    let mut lines = text.split('\n');
+    let header = lines.next().unwrap_or("").trim_end().to_string();
+    let mut content = lines.collect::<Vec<&str>>();
+    // trailing blank lines only belong to the value with keep chomping (`|+`)
+    let keep = header.split('#').next().unwrap_or("").contains('+');
+    while !keep && content.last().is_some_and(|x| x.trim().is_empty()) { content.pop(); }
+    let indicator = header.split('#').next().unwrap_or("").chars().any(|x| x.is_ascii_digit());
+    let strip = if indicator { owner_column }
+                else { content.iter().filter(|x| !x.trim().is_empty()).map(|x| x.len() - x.trim_start().len()).min().unwrap_or(0) };
+    let prefix = if indicator { "" } else { INDENT };
+    let body = content.iter().map(|line| {
+        if line.trim().is_empty() && line.len() <= strip { String::new() }
+        else { format!("{}{}", prefix, line.get(strip..).unwrap_or(line.trim_start())) }
+    }).collect();
+    return (header, body);
+}
+
+// Renders a flow node. The first line goes on the line of its key, and the other
+// lines are indented relative to that key.
+fn render_flow(node: Node, src: &str) -> Vec<String> {
+    return match node.kind() {
+        "flow_node" => {
+            let mut properties = Vec::<String>::new();
+            let mut lines = Vec::<String>::new();
+            for child in node.named_children(&mut node.walk()) {
+                match child.kind() {
+                    "anchor" | "tag" => properties.push(get_text(child, src).to_string()),
+                    _ => lines = render_flow(child, src),
+                }
+            }
+            if lines.is_empty() { return vec![properties.join(" ")]; }
+            if !properties.is_empty() { lines[0] = format!("{} {}", properties.join(" "), lines[0]); }
+            lines
+        },
+        "single_quote_scalar" => vec![normalize_quotes(get_text(node, src))],
+        "flow_sequence" => render_flow_collection(node, src, "[", "]"),
+        "flow_mapping" => render_flow_collection(node, src, "{ ", " }"),
+        "flow_pair" => {
+            let key = node.child_by_field_name("key").map(|x| render_flow(x, src).join(" ")).unwrap_or_default();
+            let mut lines = match node.child_by_field_name("value") {
+                Some(value) => render_flow(value, src),
+                None => vec![String::new()],
+            };
+            lines[0] = if lines[0].is_empty() { format!("{}:", key) } else { format!("{}: {}", key, lines[0]) };
+            lines
+        },
+        // plain and double quoted scalars: the indentation of continuation lines isn't part of the value
+        _ => {
+            let text = get_text(node, src);
+            let mut lines = Vec::<String>::new();
+            for (i, line) in text.split('\n').enumerate() {
+                if i == 0 { lines.push(line.trim_end().to_string()); }
+                else if line.trim().is_empty() { lines.push(String::new()); }
+                else { lines.push(format!("{}{}", INDENT, line.trim())); }
+            }
+            lines
+        },
+    };
+}
+
+// 'text' becomes "text" when that needs no escapes.
+fn normalize_quotes(text: &str) -> String {
+    if text.contains('\n') || text.len() < 2 { return text.to_string(); }
+    let inner = text[1..text.len() - 1].replace("''", "'");
+    if inner.contains('"') || inner.contains('\\') { return text.to_string(); }
+    return format!("\"{}\"", inner);
+}
+
+fn render_flow_collection(node: Node, src: &str, open: &str, close: &str) -> Vec<String> {
+    let children = node.named_children(&mut node.walk()).collect::<Vec<Node>>();
+    if children.is_empty() { return vec![format!("{}{}", open.trim(), close.trim())]; }
+    let has_comments = children.iter().any(|x| x.kind() == "comment");
+    let items = children.iter().filter(|x| x.kind() != "comment").map(|x| render_flow(*x, src)).collect::<Vec<Vec<String>>>();
+    if !has_comments && items.iter().all(|x| x.len() == 1) {
+        let compact = format!("{}{}{}", open, items.iter().map(|x| x[0].as_str()).collect::<Vec<&str>>().join(", "), close);
+        if compact.len() <= COMPACT_WIDTH { return vec![compact]; }
+    }
+    // one element per line; the closing bracket stays indented, since flow
+    // content can't go back to the column of its key
+    let mut lines = vec![open.trim().to_string()];
+    let mut prev_row: Option<usize> = None;
+    let mut items = items.into_iter();
+    for child in &children {
+        let row = child.start_position().row;
+        if child.kind() == "comment" {
+            let comment = get_text(*child, src).trim_end();
+            if prev_row == Some(row) && lines.len() > 1 {
+                let last = lines.len() - 1;
+                lines[last] = format!("{} {}", lines[last], comment);
+            }
+            else {
+                lines.push(format!("{}{}{}", INDENT, INDENT, comment));
+            }
+            continue;
+        }
+        let mut item = indent_lines(items.next().unwrap_or_default(), &INDENT.repeat(2));
+        let last = item.len() - 1;
+        item[last] += ",";
+        lines.extend(item);
+        prev_row = Some(last_row(*child, src));
+    }
+    lines.push(format!("{}{}", INDENT, close.trim()));
+    return lines;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml_format;
+
+    fn format(src: &str) -> String {
+        let ast = yaml_format::parse_existing_yaml_file(src.to_string());
+        return traverse_yaml_ast(ast, src.to_string());
+    }
+
+    #[test]
+    fn quoting() {
+        let src = "\
+name:   'build'
+image: 'ubuntu-latest'
+run: 'echo \"quoted\"'
+note: 'it''s fine'
+env: {A:  1, B: 'two'}
+";
+        let expected = "\
+name: \"build\"
+image: \"ubuntu-latest\"
+run: 'echo \"quoted\"'
+note: \"it's fine\"
+env: { A: 1, B: \"two\" }
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn comments() {
+        let src = "\
+# CI configuration
+on: [push,   pull_request]   # triggers
+steps:
+-   uses: checkout
+# run the tests
+-   run: test
+
+
+-   run: |
+      cargo test
+      cargo clippy
+";
+        let expected = "\
+# CI configuration
+on: [push, pull_request] # triggers
+steps:
+  - uses: checkout
+  # run the tests
+  - run: test
+
+  - run: |
+      cargo test
+      cargo clippy
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn dedented_comments() {
+        let src = "\
+a:
+  b: 1
+# note
+  c: 2
+d:
+  - x
+
+  # before y
+  - y
+  # after y
+# top
+e: 1
+";
+        let expected = "\
+a:
+  b: 1
+  # note
+  c: 2
+d:
+  - x
+
+  # before y
+  - y
+  # after y
+# top
+e: 1
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn comments_inside_sequence_items() {
+        let src = "\
+steps:
+- uses: checkout
+  # pin the toolchain
+- run: rustup default 1.46
+    # indented deeper
+  with: x
+";
+        let expected = "\
+steps:
+  - uses: checkout
+    # pin the toolchain
+  - run: rustup default 1.46
+    # indented deeper
+    with: x
+";
+        assert_eq!(format(src), expected);
+    }
+}